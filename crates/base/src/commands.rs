@@ -1,4 +1,5 @@
 use crate::{
+    event_sinks::EventSinkOpts,
     inspector_server::Inspector,
     rt_worker::{worker_ctx::TerminationToken, worker_pool::WorkerPoolPolicy},
     server::{Server, ServerFlags, ServerHealth, Tls, WorkerEntrypoints},
@@ -26,6 +27,7 @@ pub async fn start_server(
     inspector_option: Option<InspectorOption>,
     jsx_specifier: Option<String>,
     jsx_module: Option<String>,
+    event_sink_opts: Option<EventSinkOpts>,
 ) -> Result<(), Error> {
     let mut server = Server::new(
         ip,
@@ -44,6 +46,7 @@ pub async fn start_server(
         inspector_option.map(Inspector::from_option),
         jsx_specifier,
         jsx_module,
+        event_sink_opts,
     )
     .await?;
 
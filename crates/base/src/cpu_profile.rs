@@ -0,0 +1,128 @@
+use crate::utils::diagnostics::new_output_path;
+use anyhow::{anyhow, bail, Context, Error};
+use deno_core::futures::channel::mpsc;
+use deno_core::serde_json::{self, json, Value};
+use deno_core::{InspectorSessionProxy, LocalInspectorSession};
+use futures_util::future::BoxFuture;
+use futures_util::pin_mut;
+use log::error;
+use sb_core::util::sync::AtomicFlag;
+use sb_workers::context::CpuProfileOpts;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+const CPU_PROFILE_EXT: &str = "cpuprofile";
+
+/// Profiles a worker through a local inspector session until `stop` resolves,
+/// then writes the profile and sends its path to `tx`.
+///
+/// The session is driven from the supervisor runtime, so the profile can be
+/// stopped even while the isolate is busy running a script.
+pub fn spawn_cpu_profile(
+    session_tx: mpsc::UnboundedSender<InspectorSessionProxy>,
+    is_terminated: Arc<AtomicFlag>,
+    opts: CpuProfileOpts,
+    service_path: String,
+    key: Option<Uuid>,
+    stop: BoxFuture<'static, ()>,
+    tx: oneshot::Sender<Result<PathBuf, Error>>,
+) {
+    drop(base_rt::SUPERVISOR_RT.spawn(async move {
+        let result = async move {
+            let (outbound_tx, outbound_rx) = mpsc::unbounded();
+            let (inbound_tx, inbound_rx) = mpsc::unbounded();
+
+            if session_tx
+                .unbounded_send(InspectorSessionProxy {
+                    tx: outbound_tx,
+                    rx: inbound_rx,
+                })
+                .is_err()
+            {
+                bail!("inspector session is not available");
+            }
+
+            let mut session = LocalInspectorSession::new(inbound_tx, outbound_rx);
+            let profile = take_cpu_profile(&mut session, &is_terminated, &opts, stop).await?;
+            let path = new_output_path(
+                &opts.dir,
+                &service_path,
+                key,
+                CPU_PROFILE_EXT,
+                opts.max_count,
+            )?;
+
+            tokio::fs::write(&path, serde_json::to_vec(&profile)?).await?;
+
+            Ok::<_, Error>(path)
+        }
+        .await;
+
+        if let Err(err) = result.as_ref() {
+            error!("failed to take cpu profile: {}", err);
+        }
+
+        let _ = tx.send(result);
+    }));
+}
+
+async fn take_cpu_profile(
+    session: &mut LocalInspectorSession,
+    is_terminated: &AtomicFlag,
+    opts: &CpuProfileOpts,
+    stop: BoxFuture<'static, ()>,
+) -> Result<Value, Error> {
+    post_message(session, is_terminated, "Profiler.enable", None::<Value>).await?;
+
+    if opts.sampling_interval_us > 0 {
+        post_message(
+            session,
+            is_terminated,
+            "Profiler.setSamplingInterval",
+            Some(json!({ "interval": opts.sampling_interval_us })),
+        )
+        .await?;
+    }
+
+    post_message(session, is_terminated, "Profiler.start", None::<Value>).await?;
+    stop.await;
+
+    let mut result = post_message(session, is_terminated, "Profiler.stop", None::<Value>).await?;
+
+    result
+        .get_mut("profile")
+        .map(Value::take)
+        .context("inspector did not return a profile")
+}
+
+/// Posts a message to the session, giving up once the worker is terminated
+/// since the response would never come.
+async fn post_message<T: Serialize>(
+    session: &mut LocalInspectorSession,
+    is_terminated: &AtomicFlag,
+    method: &str,
+    params: Option<T>,
+) -> Result<Value, Error> {
+    let mut int = tokio::time::interval(Duration::from_millis(61));
+    let fut = session.post_message(method, params);
+
+    pin_mut!(fut);
+
+    loop {
+        tokio::select! {
+            _ = int.tick() => {
+                if is_terminated.is_raised() {
+                    return Err(anyhow!("worker has been terminated while profiling"));
+                }
+            }
+
+            res = &mut fut => {
+                return res.map_err(Error::from);
+            }
+        }
+    }
+}
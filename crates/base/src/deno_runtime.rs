@@ -1,3 +1,5 @@
+use crate::cpu_profile::spawn_cpu_profile;
+use crate::heap_snapshot::write_heap_snapshot;
 use crate::inspector_server::Inspector;
 use crate::rt_worker::supervisor::{CPUUsage, CPUUsageMetrics};
 use crate::rt_worker::worker::DuplexStreamEntry;
@@ -20,9 +22,9 @@ use deno_tls::deno_native_certs::load_native_certs;
 use deno_tls::rustls;
 use deno_tls::rustls::RootCertStore;
 use deno_tls::RootCertStoreProvider;
-use futures_util::future::poll_fn;
+use futures_util::future::{poll_fn, BoxFuture};
 use futures_util::task::AtomicWaker;
-use log::{error, trace};
+use log::{error, info, trace};
 use once_cell::sync::{Lazy, OnceCell};
 use sb_core::conn_sync::DenoRuntimeDropToken;
 use sb_core::http::sb_core_http;
@@ -34,16 +36,19 @@ use std::collections::HashMap;
 use std::ffi::c_void;
 use std::fmt;
 use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 use std::task::Poll;
 use std::time::Duration;
-use tokio::sync::{mpsc, Notify};
+use tokio::sync::{mpsc, oneshot, Notify};
 use tokio::time::interval;
 use tokio_util::sync::CancellationToken;
 
 use crate::snapshot;
-use event_worker::events::{EventMetadata, WorkerEventWithMetadata};
-use event_worker::js_interceptors::sb_events_js_interceptors;
+use event_worker::channel::{EventReceiver, EventSender};
+use event_worker::events::{EventMetadata, ShutdownReason};
+use event_worker::js_interceptors::{sb_events_js_interceptors, LogQuota, MinLogLevel};
 use event_worker::sb_user_event_worker;
 use sb_ai::sb_ai;
 use sb_core::cache::CacheSetting;
@@ -52,10 +57,11 @@ use sb_core::external_memory::CustomAllocator;
 use sb_core::net::sb_core_net;
 use sb_core::permissions::{sb_core_permissions, Permissions};
 use sb_core::runtime::sb_core_runtime;
-use sb_core::{sb_core_main_js, MemCheckWaker};
+use sb_core::{sb_core_main_js, MemCheckWaker, WaitUntilTracker};
 use sb_env::sb_env as sb_env_op;
 use sb_fs::file_system::DenoCompileFileSystem;
 use sb_graph::emitter::EmitterFactory;
+use sb_graph::eszip_cache::{EszipCache, EszipCacheKey};
 use sb_graph::import_map::load_import_map;
 use sb_graph::{
     generate_binary_eszip, include_glob_patterns_in_eszip, EszipPayloadKind, STATIC_FS_PREFIX,
@@ -63,7 +69,10 @@ use sb_graph::{
 use sb_module_loader::standalone::create_module_loader_for_standalone_from_eszip_kind;
 use sb_module_loader::RuntimeProviders;
 use sb_node::deno_node;
-use sb_workers::context::{UserWorkerMsgs, WorkerContextInitOpts, WorkerRuntimeOpts};
+use sb_workers::context::{
+    ServiceBindings, UserWorkerMsgs, WorkerContextInitOpts, WorkerDiagnosticMsg,
+    WorkerDiagnosticSender, WorkerRuntimeOpts,
+};
 use sb_workers::sb_user_workers;
 
 const DEFAULT_ALLOC_CHECK_INT_MSEC: u64 = 1000;
@@ -90,6 +99,10 @@ static ALLOC_CHECK_DUR: Lazy<Duration> = Lazy::new(|| {
 pub static SHOULD_DISABLE_DEPRECATED_API_WARNING: OnceCell<bool> = OnceCell::new();
 pub static SHOULD_USE_VERBOSE_DEPRECATED_API_WARNING: OnceCell<bool> = OnceCell::new();
 pub static MAYBE_DENO_VERSION: OnceCell<String> = OnceCell::new();
+pub static MAYBE_ESZIP_CACHE_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+static ESZIP_CACHE: Lazy<EszipCache> =
+    Lazy::new(|| EszipCache::new(MAYBE_ESZIP_CACHE_DIR.get().cloned()));
 
 #[ctor]
 fn init_v8_platform() {
@@ -122,8 +135,14 @@ fn get_error_class_name(e: &AnyError) -> &'static str {
 struct MemCheck {
     drop_token: CancellationToken,
     limit: Option<usize>,
+    soft_limit: Option<usize>,
     waker: Arc<AtomicWaker>,
     notify: Arc<Notify>,
+    soft_limit_notify: Arc<Notify>,
+    is_gc_requested: AtomicBool,
+    heap_snapshot_on_oom: bool,
+    is_oom_heap_snapshot_requested: AtomicBool,
+    is_heap_snapshot_pending: AtomicBool,
     state: Arc<RwLock<MemCheckState>>,
 }
 
@@ -157,20 +176,67 @@ impl MemCheck {
 
         let heap_stats = WorkerHeapStatistics::from(&stats);
         let mut state = self.state.write().unwrap();
+        let mut is_soft_limit_reached = false;
+        let mut is_limit_reached = false;
 
         if !state.exceeded {
             state.current = heap_stats;
 
+            if !state.soft_limit_exceeded && self.soft_limit.is_some_and(|it| total_bytes >= it) {
+                state.soft_limit_exceeded = true;
+                is_soft_limit_reached = true;
+            }
+
             if total_bytes >= limit {
                 state.exceeded = true;
-
-                drop(state);
-                self.notify.notify_waiters();
+                is_limit_reached = true;
             }
         }
 
+        drop(state);
+
+        if is_soft_limit_reached {
+            // NOTE: The GC can't be triggered here since this may be called
+            // from the GC prologue callback; the event loop does it instead.
+            self.is_gc_requested.store(true, Ordering::Release);
+            self.soft_limit_notify.notify_waiters();
+            self.waker.wake();
+        }
+
+        if is_limit_reached {
+            self.request_oom_heap_snapshot();
+            self.notify.notify_waiters();
+        }
+
         total_bytes
     }
+
+    fn take_gc_request(&self) -> bool {
+        self.is_gc_requested.swap(false, Ordering::AcqRel)
+    }
+
+    /// Returns whether a heap snapshot should be written on oom, which is
+    /// only the case once per worker.
+    fn claim_oom_heap_snapshot(&self) -> bool {
+        self.heap_snapshot_on_oom
+            && !self
+                .is_oom_heap_snapshot_requested
+                .swap(true, Ordering::AcqRel)
+    }
+
+    /// Asks the event loop to write a heap snapshot, only once per worker.
+    fn request_oom_heap_snapshot(&self) {
+        if !self.claim_oom_heap_snapshot() {
+            return;
+        }
+
+        self.is_heap_snapshot_pending.store(true, Ordering::Release);
+        self.waker.wake();
+    }
+
+    fn take_heap_snapshot_request(&self) -> bool {
+        self.is_heap_snapshot_pending.swap(false, Ordering::AcqRel)
+    }
 }
 
 pub trait GetRuntimeContext {
@@ -199,6 +265,12 @@ pub struct DenoRuntime<RuntimeContext = ()> {
     mem_check: Arc<MemCheck>,
     waker: Arc<AtomicWaker>,
 
+    pub(crate) beforeunload_tx: mpsc::UnboundedSender<ShutdownReason>,
+    pub(crate) diagnostic_tx: WorkerDiagnosticSender,
+    pub(crate) wait_until: WaitUntilTracker,
+    beforeunload_rx: Option<mpsc::UnboundedReceiver<ShutdownReason>>,
+    diagnostic_rx: Option<mpsc::UnboundedReceiver<WorkerDiagnosticMsg>>,
+
     _phantom_runtime_context: PhantomData<RuntimeContext>,
 }
 
@@ -242,6 +314,7 @@ where
         } = opts;
 
         let drop_token = CancellationToken::default();
+        let maybe_service_bindings = conf.as_user_worker().and_then(ServiceBindings::new);
 
         let base_dir_path = std::env::current_dir().map(|p| p.join(&service_path))?;
         let base_url = Url::from_directory_path(&base_dir_path).unwrap();
@@ -277,8 +350,31 @@ where
         let only_module_code =
             maybe_module_code.is_some() && maybe_eszip.is_none() && !is_some_entry_point;
 
+        // NOTE: Bundles built from inline module code are not cached since
+        // there are no source files to validate them against.
+        let maybe_eszip_cache_key = (maybe_eszip.is_none()
+            && !only_module_code
+            && !no_module_cache)
+            .then(|| EszipCacheKey {
+                service_path: base_dir_path.clone(),
+                maybe_entrypoint: is_some_entry_point.then(|| main_module_url.to_string()),
+                maybe_import_map_path: import_map_path.clone(),
+                maybe_decorator,
+                maybe_jsx_import_source_config: maybe_jsx_import_source_config.clone(),
+                static_patterns: static_patterns.clone(),
+                allow_remote_modules,
+            });
+
+        let maybe_cached_eszip = maybe_eszip_cache_key
+            .as_ref()
+            .and_then(|it| ESZIP_CACHE.get(it));
+
         let eszip = if let Some(eszip_payload) = maybe_eszip {
             eszip_payload
+        } else if let Some(bytes) = maybe_cached_eszip {
+            maybe_arc_import_map = load_import_map(import_map_path.clone())?.map(Arc::new);
+
+            EszipPayloadKind::VecKind(bytes.to_vec())
         } else {
             let mut emitter_factory = EmitterFactory::new();
 
@@ -326,7 +422,10 @@ where
             )
             .await;
 
-            EszipPayloadKind::Eszip(eszip)
+            match maybe_eszip_cache_key.as_ref() {
+                Some(key) => EszipPayloadKind::VecKind(ESZIP_CACHE.insert(key, eszip).to_vec()),
+                None => EszipPayloadKind::Eszip(eszip),
+            }
         };
 
         // Create and populate a root cert store based on environment variable.
@@ -389,6 +488,7 @@ where
             npm_resolver,
             vfs,
             module_loader,
+            source_map_getter,
             module_code,
             static_files,
             npm_snapshot,
@@ -461,14 +561,18 @@ where
         let mut mem_check = MemCheck::default();
 
         if conf.is_user_worker() {
-            let memory_limit =
-                mib_to_bytes(conf.as_user_worker().unwrap().memory_limit_mb) as usize;
+            let user_conf = conf.as_user_worker().unwrap();
+            let memory_limit = mib_to_bytes(user_conf.memory_limit_mb) as usize;
 
             let allocator = CustomAllocator::new(memory_limit);
 
             allocator.set_waker(mem_check.waker.clone());
 
             mem_check.limit = Some(memory_limit);
+            mem_check.soft_limit = (user_conf.memory_soft_limit_mb > 0)
+                .then(|| mib_to_bytes(user_conf.memory_soft_limit_mb) as usize);
+            mem_check.heap_snapshot_on_oom =
+                user_conf.heap_snapshot.as_ref().is_some_and(|it| it.on_oom);
             create_params = Some(
                 deno_core::v8::CreateParams::default()
                     .heap_limits(mib_to_bytes(0) as usize, memory_limit)
@@ -477,16 +581,22 @@ where
         };
 
         let mem_check = Arc::new(mem_check);
+        // NOTE: CPU profiles are taken through a local inspector session.
+        let has_cpu_profile = conf
+            .as_user_worker()
+            .is_some_and(|it| it.cpu_profile.is_some());
+
         let runtime_options = RuntimeOptions {
             extensions,
             is_main: true,
-            inspector: maybe_inspector.is_some(),
+            inspector: maybe_inspector.is_some() || has_cpu_profile,
             create_params,
             get_error_class_fn: Some(&get_error_class_name),
             shared_array_buffer_store: None,
             compiled_wasm_module_store: None,
             startup_snapshot: snapshot::snapshot(),
             module_loader: Some(module_loader),
+            source_map_getter: Some(source_map_getter),
             ..Default::default()
         };
 
@@ -540,6 +650,8 @@ where
             );
         }
 
+        let wait_until = WaitUntilTracker::default();
+
         if is_user_worker {
             js_runtime.v8_isolate().add_gc_prologue_callback(
                 mem_check_gc_prologue_callback_fn,
@@ -547,10 +659,11 @@ where
                 GCType::ALL,
             );
 
-            js_runtime
-                .op_state()
-                .borrow_mut()
-                .put(MemCheckWaker::from(mem_check.waker.clone()));
+            let op_state_rc = js_runtime.op_state();
+            let mut op_state = op_state_rc.borrow_mut();
+
+            op_state.put(MemCheckWaker::from(mem_check.waker.clone()));
+            op_state.put(wait_until.clone());
         }
 
         js_runtime
@@ -566,8 +679,7 @@ where
 
             if conf.is_events_worker() {
                 // if worker is an events worker, assert events_rx is to be available
-                op_state
-                    .put::<mpsc::UnboundedReceiver<WorkerEventWithMetadata>>(events_rx.unwrap());
+                op_state.put::<EventReceiver>(events_rx.unwrap());
             }
 
             if conf.is_main_worker() || conf.is_user_worker() {
@@ -584,12 +696,34 @@ where
                 );
 
                 if let Some(events_msg_tx) = conf.events_msg_tx.clone() {
-                    op_state.put::<mpsc::UnboundedSender<WorkerEventWithMetadata>>(events_msg_tx);
+                    op_state.put::<EventSender>(events_msg_tx);
                     op_state.put::<EventMetadata>(EventMetadata {
                         service_path: conf.service_path.clone(),
                         execution_id: conf.key,
+                        ..Default::default()
                     });
                 }
+
+                if let Some(level) = conf.log_level {
+                    op_state.put(MinLogLevel(level));
+                }
+
+                if conf.log_rate_limit > 0 || conf.log_byte_limit > 0 {
+                    let reporter = op_state
+                        .try_borrow::<EventSender>()
+                        .cloned()
+                        .zip(op_state.try_borrow::<EventMetadata>().cloned());
+
+                    op_state.put(LogQuota::new(
+                        conf.log_rate_limit,
+                        conf.log_byte_limit,
+                        reporter,
+                    ));
+                }
+
+                if let Some(bindings) = maybe_service_bindings {
+                    op_state.put(bindings);
+                }
             }
 
             op_state.put::<sb_env::EnvVars>(env_vars);
@@ -630,6 +764,10 @@ where
             }));
         }
 
+        let (beforeunload_tx, beforeunload_rx) = mpsc::unbounded_channel();
+        let (diagnostic_tx, diagnostic_rx) = mpsc::unbounded_channel();
+        let diagnostic_tx = WorkerDiagnosticSender::new(diagnostic_tx, mem_check.waker.clone());
+
         Ok(Self {
             drop_token,
             js_runtime,
@@ -646,6 +784,12 @@ where
             mem_check,
             waker: Arc::default(),
 
+            beforeunload_tx,
+            diagnostic_tx,
+            wait_until,
+            beforeunload_rx: Some(beforeunload_rx),
+            diagnostic_rx: Some(diagnostic_rx),
+
             _phantom_runtime_context: PhantomData,
         })
     }
@@ -738,6 +882,50 @@ where
         let is_user_worker = self.conf.is_user_worker();
         let global_waker = self.waker.clone();
         let mem_check = is_user_worker.then(|| self.mem_check.clone());
+        let maybe_usage = self.conf.as_user_worker().and_then(|it| it.usage.clone());
+        let mut beforeunload_rx = self.beforeunload_rx.take();
+        let mut diagnostic_rx = self.diagnostic_rx.take();
+        let heap_snapshot_fn = {
+            let maybe_conf = self.conf.as_user_worker();
+            let maybe_opts = maybe_conf.and_then(|it| it.heap_snapshot.clone());
+            let service_path = maybe_conf
+                .and_then(|it| it.service_path.clone())
+                .unwrap_or_default();
+            let key = maybe_conf.and_then(|it| it.key);
+
+            move |isolate: &mut Isolate| match maybe_opts.as_ref() {
+                Some(opts) => write_heap_snapshot(isolate, opts, &service_path, key),
+                None => Err(anyhow!("heap snapshots are not enabled for this worker")),
+            }
+        };
+        let cpu_profile_fn = {
+            let maybe_conf = self.conf.as_user_worker();
+            let maybe_opts = maybe_conf.and_then(|it| it.cpu_profile.clone());
+            let service_path = maybe_conf
+                .and_then(|it| it.service_path.clone())
+                .unwrap_or_default();
+            let key = maybe_conf.and_then(|it| it.key);
+            let is_terminated = self.is_terminated.clone();
+
+            move |js_runtime: &mut JsRuntime,
+                  stop: BoxFuture<'static, ()>,
+                  tx: oneshot::Sender<Result<PathBuf, Error>>| {
+                let Some(opts) = maybe_opts.clone() else {
+                    let _ = tx.send(Err(anyhow!("cpu profiles are not enabled for this worker")));
+                    return;
+                };
+
+                spawn_cpu_profile(
+                    js_runtime.inspector().borrow_mut().get_session_sender(),
+                    is_terminated.clone(),
+                    opts,
+                    service_path.clone(),
+                    key,
+                    stop,
+                    tx,
+                );
+            }
+        };
 
         let poll_result = poll_fn(|cx| unsafe {
             // INVARIANT: Only can steal current task by other threads when LIFO
@@ -785,6 +973,12 @@ where
                     Cow::Borrowed(waker)
                 };
 
+                if let Some(rx) = beforeunload_rx.as_mut() {
+                    while let Ok(reason) = rx.try_recv() {
+                        dispatch_beforeunload_event(&mut js_runtime, reason);
+                    }
+                }
+
                 js_runtime.poll_event_loop(
                     &mut std::task::Context::from_waker(waker.as_ref()),
                     PollEventLoopOptions {
@@ -814,6 +1008,35 @@ where
                 let mem_state = mem_check.as_ref().unwrap();
                 let total_malloced_bytes = mem_state.check(js_runtime.v8_isolate().as_mut());
 
+                if let Some(usage) = maybe_usage.as_ref() {
+                    usage.record(accumulated_cpu_time_ns, total_malloced_bytes);
+                }
+
+                if mem_state.take_gc_request() {
+                    js_runtime.v8_isolate().low_memory_notification();
+                }
+
+                if mem_state.take_heap_snapshot_request() {
+                    match heap_snapshot_fn(js_runtime.v8_isolate().as_mut()) {
+                        Ok(path) => info!("heap snapshot written on oom: {}", path.display()),
+                        Err(err) => error!("failed to write heap snapshot on oom: {}", err),
+                    }
+                }
+
+                if let Some(rx) = diagnostic_rx.as_mut() {
+                    while let Ok(msg) = rx.try_recv() {
+                        match msg {
+                            WorkerDiagnosticMsg::HeapSnapshot(tx) => {
+                                let _ = tx.send(heap_snapshot_fn(js_runtime.v8_isolate().as_mut()));
+                            }
+
+                            WorkerDiagnosticMsg::CpuProfile(stop, tx) => {
+                                cpu_profile_fn(&mut js_runtime, stop, tx);
+                            }
+                        }
+                    }
+                }
+
                 mem_state.waker.register(waker);
 
                 trace!(
@@ -842,7 +1065,12 @@ where
         .await;
 
         let result = match poll_result {
-            Err(err) => Err(anyhow!("event loop error: {}", err)),
+            Err(err) => {
+                // NOTE: The error is kept in the chain so that the details of
+                // an uncaught exception can still be extracted from it.
+                let msg = format!("event loop error: {}", err);
+                Err(err.context(msg))
+            }
             Ok(_) => match mod_result_rx.await {
                 Err(e) => {
                     error!("{}", e.to_string());
@@ -865,12 +1093,61 @@ where
         self.mem_check.state.clone()
     }
 
-    pub fn add_memory_limit_callback<C>(&self, mut cb: C)
+    /// Returns a function that writes a heap snapshot right away if the
+    /// worker is configured to write one when it runs out of memory.
+    ///
+    /// It must only be called on the thread of the isolate, such as from its
+    /// near heap limit callback. The isolate may be stuck allocating and never
+    /// get back to the event loop, so the snapshot can't wait for it.
+    pub fn oom_heap_snapshot_fn(&mut self) -> impl FnMut() + 'static {
+        let mem_check = self.mem_check.clone();
+        let maybe_conf = self.conf.as_user_worker();
+        let maybe_opts = maybe_conf.and_then(|it| it.heap_snapshot.clone());
+        let service_path = maybe_conf
+            .and_then(|it| it.service_path.clone())
+            .unwrap_or_default();
+        let key = maybe_conf.and_then(|it| it.key);
+        let isolate_ptr = self.js_runtime.v8_isolate().as_mut() as *mut Isolate;
+
+        move || {
+            let Some(opts) = maybe_opts.as_ref() else {
+                return;
+            };
+
+            if !mem_check.claim_oom_heap_snapshot() {
+                return;
+            }
+
+            // SAFETY: The function is only called on the thread of the
+            // isolate by a callback that the runtime drops along with it.
+            let isolate = unsafe { &mut *isolate_ptr };
+
+            match write_heap_snapshot(isolate, opts, &service_path, key) {
+                Ok(path) => info!("heap snapshot written on oom: {}", path.display()),
+                Err(err) => error!("failed to write heap snapshot on oom: {}", err),
+            }
+        }
+    }
+
+    pub fn add_memory_limit_callback<C>(&self, cb: C)
     where
         // XXX(Nyannyacha): Should we relax bounds a bit more?
         C: FnMut(MemCheckState) -> bool + Send + 'static,
     {
-        let notify = self.mem_check.notify.clone();
+        self.add_mem_check_callback(self.mem_check.notify.clone(), cb);
+    }
+
+    pub fn add_memory_soft_limit_callback<C>(&self, cb: C)
+    where
+        C: FnMut(MemCheckState) -> bool + Send + 'static,
+    {
+        self.add_mem_check_callback(self.mem_check.soft_limit_notify.clone(), cb);
+    }
+
+    fn add_mem_check_callback<C>(&self, notify: Arc<Notify>, mut cb: C)
+    where
+        C: FnMut(MemCheckState) -> bool + Send + 'static,
+    {
         let drop_token = self.mem_check.drop_token.clone();
         let state = self.mem_check_state();
 
@@ -912,6 +1189,20 @@ where
     }
 }
 
+fn dispatch_beforeunload_event(js_runtime: &mut JsRuntime, reason: ShutdownReason) {
+    let reason = serde_json::to_string(&reason).unwrap_or_else(|_| "null".to_string());
+    let script = format!(
+        "globalThis.dispatchEvent(new CustomEvent('beforeunload', {{ detail: {{ reason: {} }} }}))",
+        reason
+    );
+
+    if let Err(err) =
+        js_runtime.execute_script(located_script_name!(), ModuleCodeString::from(script))
+    {
+        error!("failed to dispatch beforeunload event: {}", err);
+    }
+}
+
 fn get_current_cpu_time_ns() -> Result<i64, Error> {
     get_thread_time().context("can't get current thread time")
 }
@@ -947,15 +1238,17 @@ extern "C" fn mem_check_gc_prologue_callback_fn(
 #[cfg(test)]
 mod test {
     use crate::deno_runtime::DenoRuntime;
+    use crate::heap_snapshot::write_heap_snapshot;
     use crate::rt_worker::worker::DuplexStreamEntry;
     use deno_config::JsxImportSourceConfig;
     use deno_core::error::AnyError;
     use deno_core::{serde_json, serde_v8, v8, FastString, ModuleCodeString, PollEventLoopOptions};
+    use event_worker::events::UncaughtExceptionEvent;
     use sb_graph::emitter::EmitterFactory;
     use sb_graph::{generate_binary_eszip, EszipPayloadKind};
     use sb_workers::context::{
-        MainWorkerRuntimeOpts, UserWorkerMsgs, UserWorkerRuntimeOpts, WorkerContextInitOpts,
-        WorkerRuntimeOpts,
+        HeapSnapshotOpts, MainWorkerRuntimeOpts, UserWorkerMsgs, UserWorkerRuntimeOpts,
+        WorkerContextInitOpts, WorkerRuntimeOpts,
     };
     use serde::de::DeserializeOwned;
     use serde::Serialize;
@@ -1012,6 +1305,7 @@ mod test {
                 },
                 static_patterns: vec![],
                 maybe_jsx_import_source_config: None,
+                maybe_pool_policy: None,
             },
             None,
         )
@@ -1057,6 +1351,7 @@ mod test {
                 },
                 static_patterns: vec![],
                 maybe_jsx_import_source_config: None,
+                maybe_pool_policy: None,
             },
             None,
         )
@@ -1121,6 +1416,7 @@ mod test {
                 },
                 static_patterns: vec![],
                 maybe_jsx_import_source_config: None,
+                maybe_pool_policy: None,
             },
             None,
         )
@@ -1190,6 +1486,7 @@ mod test {
                 },
                 static_patterns,
                 maybe_jsx_import_source_config,
+                maybe_pool_policy: None,
             },
             None,
         )
@@ -1217,7 +1514,8 @@ mod test {
         }
     }
 
-    // User Runtime Should not have access to EdgeRuntime
+    // User Runtime should only have access to `EdgeRuntime.waitUntil`,
+    // `EdgeRuntime.getRequestContext` and its service bindings
     #[tokio::test]
     #[serial]
     async fn test_user_runtime_creation() {
@@ -1230,18 +1528,24 @@ mod test {
         )
         .await;
 
-        {
-            let scope = &mut runtime.js_runtime.handle_scope();
-            let context = scope.get_current_context();
-            let inner_scope = &mut v8::ContextScope::new(scope, context);
-            let global = context.global(inner_scope);
-            let edge_runtime_key: v8::Local<v8::Value> =
-                serde_v8::to_v8(inner_scope, "EdgeRuntime").unwrap();
-            assert!(global
-                .get(inner_scope, edge_runtime_key)
-                .unwrap()
-                .is_undefined(),);
-        }
+        let edge_runtime_keys = runtime
+            .js_runtime
+            .execute_script(
+                "<anon>",
+                ModuleCodeString::from("Object.keys(EdgeRuntime)".to_string()),
+            )
+            .unwrap();
+
+        assert_eq!(
+            runtime
+                .to_value_mut::<Vec<String>>(&edge_runtime_keys)
+                .unwrap(),
+            vec![
+                "waitUntil".to_string(),
+                "getRequestContext".to_string(),
+                "bindings".to_string()
+            ]
+        );
     }
 
     #[tokio::test]
@@ -1638,6 +1942,102 @@ mod test {
         };
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_uncaught_exception_event_is_source_mapped() {
+        let mut user_rt: DenoRuntime =
+            create_basic_user_runtime("./test_cases/uncaught-exception", 20, 1000, &[]).await;
+
+        let (_tx, duplex_stream_rx) = mpsc::unbounded_channel::<DuplexStreamEntry>();
+        let (result, _) = user_rt.run(duplex_stream_rx, None, None).await;
+        let err = result.expect_err("expected an uncaught exception");
+        let ev = UncaughtExceptionEvent::new(&err, 0);
+
+        assert_eq!(ev.class.as_deref(), Some("TypeError"));
+        assert_eq!(ev.message.as_deref(), Some("uncaught from a timer"));
+
+        let frame = ev.frames.first().expect("expected a stack frame");
+
+        assert!(frame
+            .file_name
+            .as_deref()
+            .is_some_and(|it| it.ends_with("uncaught-exception/index.ts")));
+        assert_eq!(frame.function_name.as_deref(), Some("fail"));
+        assert_eq!(frame.line_number, Some(4));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_mem_checker_above_soft_limit() {
+        let (callback_tx, mut callback_rx) = mpsc::unbounded_channel::<()>();
+        let mut user_rt: DenoRuntime = create_runtime(
+            Some("./test_cases/array_buffers"),
+            None,
+            Some(WorkerRuntimeOpts::UserWorker(UserWorkerRuntimeOpts {
+                memory_limit_mb: 64,
+                memory_soft_limit_mb: 10,
+                worker_timeout_ms: 1000,
+                force_create: true,
+                ..Default::default()
+            })),
+            vec![],
+            None,
+        )
+        .await;
+
+        user_rt.add_memory_soft_limit_callback(move |_| {
+            callback_tx.send(()).unwrap();
+            true
+        });
+
+        let (_tx, duplex_stream_rx) = mpsc::unbounded_channel::<DuplexStreamEntry>();
+        let (result, _) = user_rt.run(duplex_stream_rx, None, None).await;
+
+        assert!(result.is_ok(), "expected no errors");
+
+        if timeout(Duration::from_secs(10), callback_rx.recv())
+            .await
+            .is_err()
+        {
+            panic!(
+                "failed to detect a memory soft limit callback invocation within the given time"
+            );
+        }
+
+        let state = *user_rt.mem_check.state.read().unwrap();
+
+        assert!(state.soft_limit_exceeded);
+        assert!(!state.exceeded);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_heap_snapshot_rotation() {
+        let dir = std::env::temp_dir().join(format!("sb_heap_snapshot_{}", std::process::id()));
+        let opts = HeapSnapshotOpts {
+            dir: dir.clone(),
+            max_count: 1,
+            ..Default::default()
+        };
+
+        let _ = fs::remove_dir_all(&dir);
+        let mut user_rt: DenoRuntime =
+            create_basic_user_runtime("./test_cases/array_buffers", 20, 1000, &[]).await;
+
+        let isolate = user_rt.js_runtime.v8_isolate();
+        let first =
+            write_heap_snapshot(isolate, &opts, "./test_cases/array_buffers", None).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let second =
+            write_heap_snapshot(isolate, &opts, "./test_cases/array_buffers", None).unwrap();
+
+        assert!(!first.exists());
+        assert!(fs::metadata(&second).unwrap().len() > 0);
+        assert_eq!(fs::read_dir(second.parent().unwrap()).unwrap().count(), 1);
+    }
+
     async fn test_mem_check_above_limit(
         path: &str,
         static_patterns: &[&str],
@@ -0,0 +1,492 @@
+use crate::rt_worker::worker_ctx::TerminationToken;
+use anyhow::{anyhow, bail, Error};
+use async_trait::async_trait;
+use deno_core::serde_json::{self, Value};
+use event_worker::channel::EventReceiver;
+use event_worker::events::{
+    EventMetadata, LogEvent, LogLevel, WorkerEventWithMetadata, WorkerEvents,
+};
+use log::error;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::future::pending;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep, MissedTickBehavior};
+use url::Url;
+
+mod file;
+mod http;
+mod stdout;
+mod syslog;
+
+pub use syslog::SyslogAddr;
+
+const DEFAULT_SYSLOG_PATH: &str = "/dev/log";
+const RETRY_BACKOFF_MS: u64 = 100;
+/// How many batches may wait for a sink before new ones are discarded.
+const SINK_QUEUE_CAPACITY: usize = 16;
+
+/// Where events are written to.
+#[derive(Debug, Clone)]
+pub enum EventSinkKind {
+    Stdout,
+    /// JSON Lines written to a file that is rotated once it grows past the
+    /// size limit.
+    File(PathBuf),
+    /// RFC 5424 messages, one per event.
+    Syslog(SyslogAddr),
+    /// Batches of events sent as a JSON array in the body of a POST request.
+    Http(Url),
+}
+
+impl FromStr for EventSinkKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "stdout" {
+            return Ok(Self::Stdout);
+        }
+
+        if s == "syslog" {
+            return Ok(Self::Syslog(SyslogAddr::Unix(PathBuf::from(
+                DEFAULT_SYSLOG_PATH,
+            ))));
+        }
+
+        if let Some(path) = s.strip_prefix("file:") {
+            return Ok(Self::File(PathBuf::from(path)));
+        }
+
+        if let Some(addr) = s.strip_prefix("syslog:") {
+            return Ok(Self::Syslog(addr.parse()?));
+        }
+
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Ok(Self::Http(Url::parse(s)?));
+        }
+
+        Err(anyhow!("unknown event sink: {}", s))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventSinkOpts {
+    pub sinks: Vec<EventSinkKind>,
+    /// Only events of these types are written. `None` writes every event.
+    pub event_types: Option<HashSet<String>>,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+    /// How many times writing a batch is retried before it is discarded.
+    pub max_retries: usize,
+    pub file_max_size: u64,
+    pub file_max_count: usize,
+}
+
+impl Default for EventSinkOpts {
+    fn default() -> Self {
+        Self {
+            sinks: vec![],
+            event_types: None,
+            batch_size: 100,
+            flush_interval: Duration::from_secs(1),
+            max_retries: 3,
+            file_max_size: 10 * 1024 * 1024,
+            file_max_count: 5,
+        }
+    }
+}
+
+/// An event serialized the same way the event worker sees it.
+#[derive(Serialize)]
+struct EventRecord {
+    timestamp: u64,
+    event_type: &'static str,
+    event: Value,
+    metadata: EventMetadata,
+}
+
+pub struct SinkRecord {
+    pub line: String,
+    /// Syslog severity of the event.
+    pub severity: u8,
+}
+
+impl SinkRecord {
+    fn new(ev: WorkerEventWithMetadata) -> Result<Self, Error> {
+        let event_type = ev.event.event_type();
+        let severity = severity(&ev.event);
+        let mut event = serde_json::to_value(&ev.event)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        let record = EventRecord {
+            timestamp,
+            event_type,
+            event: event
+                .get_mut(event_type)
+                .map(Value::take)
+                .unwrap_or_default(),
+            metadata: ev.metadata,
+        };
+
+        Ok(Self {
+            line: serde_json::to_string(&record)?,
+            severity,
+        })
+    }
+}
+
+fn severity(event: &WorkerEvents) -> u8 {
+    match event {
+        WorkerEvents::Log(LogEvent { level, .. }) => match level {
+            LogLevel::Debug => 7,
+            LogLevel::Info => 6,
+            LogLevel::Warning => 4,
+            LogLevel::Error => 3,
+        },
+
+        WorkerEvents::BootFailure(_) | WorkerEvents::UncaughtException(_) => 3,
+        WorkerEvents::Shutdown(_)
+        | WorkerEvents::CircuitBreakerOpen(_)
+        | WorkerEvents::PrimaryWorkerRestart(_)
+        | WorkerEvents::EventsDropped(_) => 4,
+
+        _ => 6,
+    }
+}
+
+#[async_trait]
+pub trait EventSink: Send {
+    fn name(&self) -> &'static str;
+
+    /// Writes the records in order. A sink that writes them one at a time
+    /// counts those already written in `written`, so that a retry resumes
+    /// after them instead of writing them twice.
+    async fn write(&mut self, batch: &[SinkRecord], written: &mut usize) -> Result<(), Error>;
+}
+
+async fn new_sink(kind: &EventSinkKind, opts: &EventSinkOpts) -> Result<Box<dyn EventSink>, Error> {
+    Ok(match kind {
+        EventSinkKind::Stdout => Box::new(stdout::StdoutSink),
+        EventSinkKind::File(path) => Box::new(file::FileSink::new(
+            path.clone(),
+            opts.file_max_size,
+            opts.file_max_count,
+        )),
+        EventSinkKind::Syslog(addr) => Box::new(syslog::SyslogSink::new(addr).await?),
+        EventSinkKind::Http(url) => Box::new(http::HttpSink::new(url.clone())),
+    })
+}
+
+/// Writes the events received from `rx` to the configured sinks, in batches.
+///
+/// Once the termination token is signaled, the events left in the channel
+/// are flushed before its outbound is cancelled.
+pub async fn spawn_event_sinks(
+    opts: EventSinkOpts,
+    rx: EventReceiver,
+    termination_token: Option<TerminationToken>,
+) -> Result<(), Error> {
+    if opts.sinks.is_empty() {
+        bail!("no event sink is configured");
+    }
+
+    let mut sinks = vec![];
+
+    for kind in &opts.sinks {
+        sinks.push(SinkQueue::spawn(
+            new_sink(kind, &opts).await?,
+            opts.max_retries,
+        ));
+    }
+
+    drop(tokio::spawn(async move {
+        let mut batch = Vec::with_capacity(opts.batch_size);
+        let mut flush_interval = interval(opts.flush_interval);
+
+        flush_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = async {
+                    match termination_token.as_ref() {
+                        Some(token) => token.inbound.cancelled().await,
+                        None => pending().await,
+                    }
+                } => {
+                    while let Some(ev) = rx.try_recv() {
+                        push_event(&opts, &mut batch, ev);
+                    }
+
+                    break;
+                }
+
+                maybe_ev = rx.recv() => {
+                    let Some(ev) = maybe_ev else {
+                        break;
+                    };
+
+                    push_event(&opts, &mut batch, ev);
+
+                    if batch.len() >= opts.batch_size {
+                        flush(&opts, &sinks, &mut batch);
+                    }
+                }
+
+                _ = flush_interval.tick() => {
+                    flush(&opts, &sinks, &mut batch);
+                }
+            }
+        }
+
+        flush(&opts, &sinks, &mut batch);
+
+        for sink in sinks {
+            sink.close().await;
+        }
+
+        if let Some(token) = termination_token.as_ref() {
+            token.outbound.cancel();
+        }
+    }));
+
+    Ok(())
+}
+
+fn push_event(opts: &EventSinkOpts, batch: &mut Vec<SinkRecord>, ev: WorkerEventWithMetadata) {
+    if let Some(event_types) = opts.event_types.as_ref() {
+        if !event_types.contains(ev.event.event_type()) {
+            return;
+        }
+    }
+
+    match SinkRecord::new(ev) {
+        Ok(record) => batch.push(record),
+        Err(err) => error!("failed to serialize event: {}", err),
+    }
+}
+
+/// Hands the batch over to every sink without waiting for it to be written.
+fn flush(opts: &EventSinkOpts, sinks: &[SinkQueue], batch: &mut Vec<SinkRecord>) {
+    if batch.is_empty() {
+        return;
+    }
+
+    let batch = Arc::new(std::mem::replace(
+        batch,
+        Vec::with_capacity(opts.batch_size),
+    ));
+
+    for sink in sinks {
+        sink.push(batch.clone());
+    }
+}
+
+/// Batches waiting to be written to a sink, by a task of its own so that a
+/// sink retrying a write holds up neither the others nor the receipt of new
+/// events.
+struct SinkQueue {
+    name: &'static str,
+    tx: mpsc::Sender<Arc<Vec<SinkRecord>>>,
+    handle: JoinHandle<()>,
+}
+
+impl SinkQueue {
+    fn spawn(mut sink: Box<dyn EventSink>, max_retries: usize) -> Self {
+        let name = sink.name();
+        let (tx, mut rx) = mpsc::channel::<Arc<Vec<SinkRecord>>>(SINK_QUEUE_CAPACITY);
+        let handle = tokio::spawn(async move {
+            while let Some(batch) = rx.recv().await {
+                write_batch(sink.as_mut(), &batch, max_retries).await;
+            }
+        });
+
+        Self { name, tx, handle }
+    }
+
+    /// Queues the batch, or discards it if the sink is too far behind.
+    fn push(&self, batch: Arc<Vec<SinkRecord>>) {
+        match self.tx.try_send(batch) {
+            Ok(()) => {}
+            Err(TrySendError::Full(batch)) => error!(
+                "{} sink is falling behind, dropping {} events",
+                self.name,
+                batch.len()
+            ),
+            Err(TrySendError::Closed(batch)) => error!(
+                "{} sink is closed, dropping {} events",
+                self.name,
+                batch.len()
+            ),
+        }
+    }
+
+    /// Waits for the batches still queued to be written.
+    async fn close(self) {
+        drop(self.tx);
+
+        if let Err(err) = self.handle.await {
+            error!("{} sink task failed: {}", self.name, err);
+        }
+    }
+}
+
+async fn write_batch(sink: &mut dyn EventSink, batch: &[SinkRecord], max_retries: usize) {
+    let mut written = 0;
+    let mut attempt = 0;
+
+    loop {
+        let mut newly_written = 0;
+        let result = sink.write(&batch[written..], &mut newly_written).await;
+
+        written = (written + newly_written).min(batch.len());
+
+        let Err(err) = result else {
+            break;
+        };
+
+        if attempt >= max_retries {
+            error!(
+                "failed to write {} events to {} sink: {}",
+                batch.len() - written,
+                sink.name(),
+                err
+            );
+
+            break;
+        }
+
+        sleep(Duration::from_millis(RETRY_BACKOFF_MS << attempt.min(10))).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Fails once after writing the given number of records, one at a time.
+    struct FlakySink {
+        lines: Vec<String>,
+        fail_after: Option<usize>,
+    }
+
+    #[async_trait]
+    impl EventSink for FlakySink {
+        fn name(&self) -> &'static str {
+            "flaky"
+        }
+
+        async fn write(&mut self, batch: &[SinkRecord], written: &mut usize) -> Result<(), Error> {
+            for record in batch {
+                if self.fail_after == Some(*written) {
+                    self.fail_after = None;
+                    bail!("connection reset");
+                }
+
+                self.lines.push(record.line.clone());
+                *written += 1;
+            }
+
+            Ok(())
+        }
+    }
+
+    fn records(count: usize) -> Vec<SinkRecord> {
+        (0..count)
+            .map(|it| SinkRecord {
+                line: it.to_string(),
+                severity: 6,
+            })
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_resumes_after_written_records() {
+        let mut sink = FlakySink {
+            lines: vec![],
+            fail_after: Some(2),
+        };
+
+        write_batch(&mut sink, &records(4), 1).await;
+
+        assert_eq!(sink.lines, ["0", "1", "2", "3"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gives_up_after_max_retries() {
+        struct BrokenSink(usize);
+
+        #[async_trait]
+        impl EventSink for BrokenSink {
+            fn name(&self) -> &'static str {
+                "broken"
+            }
+
+            async fn write(&mut self, _: &[SinkRecord], _: &mut usize) -> Result<(), Error> {
+                self.0 += 1;
+                bail!("connection refused");
+            }
+        }
+
+        let mut sink = BrokenSink(0);
+
+        write_batch(&mut sink, &records(1), 3).await;
+
+        assert_eq!(sink.0, 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retrying_sink_does_not_hold_up_others() {
+        struct StuckSink;
+
+        #[async_trait]
+        impl EventSink for StuckSink {
+            fn name(&self) -> &'static str {
+                "stuck"
+            }
+
+            async fn write(&mut self, _: &[SinkRecord], _: &mut usize) -> Result<(), Error> {
+                pending().await
+            }
+        }
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        struct ForwardSink(mpsc::UnboundedSender<String>);
+
+        #[async_trait]
+        impl EventSink for ForwardSink {
+            fn name(&self) -> &'static str {
+                "forward"
+            }
+
+            async fn write(&mut self, batch: &[SinkRecord], _: &mut usize) -> Result<(), Error> {
+                for record in batch {
+                    self.0.send(record.line.clone())?;
+                }
+
+                Ok(())
+            }
+        }
+
+        let opts = EventSinkOpts::default();
+        let sinks = [
+            SinkQueue::spawn(Box::new(StuckSink), 0),
+            SinkQueue::spawn(Box::new(ForwardSink(tx)), 0),
+        ];
+
+        // the queue of the stuck sink overflows in the meantime
+        for _ in 0..SINK_QUEUE_CAPACITY * 2 {
+            flush(&opts, &sinks, &mut records(1));
+            assert_eq!(rx.recv().await.as_deref(), Some("0"));
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use super::stdout::to_json_lines;
+use super::{EventSink, SinkRecord};
+use anyhow::{bail, Error};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+/// Appends events as JSON Lines to a file.
+///
+/// Once the file would grow past `max_size`, it is renamed to `<path>.1`
+/// (shifting the older ones up to `<path>.<max_count>`) and a new one is
+/// started.
+pub struct FileSink {
+    path: PathBuf,
+    max_size: u64,
+    max_count: usize,
+    file: Option<File>,
+    size: u64,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf, max_size: u64, max_count: usize) -> Self {
+        Self {
+            path,
+            max_size,
+            max_count,
+            file: None,
+            size: 0,
+        }
+    }
+
+    fn rotated_path(&self, idx: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+
+        path.push(format!(".{}", idx));
+        path.into()
+    }
+
+    async fn open(&mut self) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+
+        self.size = file.metadata().await?.len();
+        self.file = Some(file);
+
+        Ok(())
+    }
+
+    async fn rotate(&mut self) -> Result<(), Error> {
+        self.file = None;
+
+        if self.max_count == 0 {
+            fs::remove_file(&self.path).await?;
+            return Ok(());
+        }
+
+        for idx in (1..self.max_count).rev() {
+            let _ = fs::rename(self.rotated_path(idx), self.rotated_path(idx + 1)).await;
+        }
+
+        fs::rename(&self.path, self.rotated_path(1)).await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventSink for FileSink {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn write(&mut self, batch: &[SinkRecord], _written: &mut usize) -> Result<(), Error> {
+        let buf = to_json_lines(batch);
+
+        if self.file.is_none() {
+            self.open().await?;
+        }
+
+        if self.max_size > 0 && self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate().await?;
+            self.open().await?;
+        }
+
+        let Some(file) = self.file.as_mut() else {
+            bail!("event sink file is not open");
+        };
+
+        file.write_all(&buf).await?;
+        file.flush().await?;
+        self.size += buf.len() as u64;
+
+        Ok(())
+    }
+}
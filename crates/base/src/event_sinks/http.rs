@@ -0,0 +1,52 @@
+use super::{EventSink, SinkRecord};
+use anyhow::Error;
+use async_trait::async_trait;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use url::Url;
+
+/// Posts each batch of events as a JSON array.
+pub struct HttpSink {
+    client: Client,
+    url: Url,
+}
+
+impl HttpSink {
+    pub fn new(url: Url) -> Self {
+        Self {
+            client: Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl EventSink for HttpSink {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    async fn write(&mut self, batch: &[SinkRecord], _written: &mut usize) -> Result<(), Error> {
+        let mut body = String::from("[");
+
+        for (idx, record) in batch.iter().enumerate() {
+            if idx > 0 {
+                body.push(',');
+            }
+
+            body.push_str(&record.line);
+        }
+
+        body.push(']');
+
+        self.client
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
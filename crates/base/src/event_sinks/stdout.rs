@@ -0,0 +1,33 @@
+use super::{EventSink, SinkRecord};
+use anyhow::Error;
+use async_trait::async_trait;
+use tokio::io::{self, AsyncWriteExt};
+
+pub struct StdoutSink;
+
+#[async_trait]
+impl EventSink for StdoutSink {
+    fn name(&self) -> &'static str {
+        "stdout"
+    }
+
+    async fn write(&mut self, batch: &[SinkRecord], _written: &mut usize) -> Result<(), Error> {
+        let mut stdout = io::stdout();
+
+        stdout.write_all(&to_json_lines(batch)).await?;
+        stdout.flush().await?;
+
+        Ok(())
+    }
+}
+
+pub fn to_json_lines(batch: &[SinkRecord]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(batch.iter().map(|it| it.line.len() + 1).sum());
+
+    for record in batch {
+        buf.extend_from_slice(record.line.as_bytes());
+        buf.push(b'\n');
+    }
+
+    buf
+}
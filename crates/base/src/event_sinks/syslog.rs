@@ -0,0 +1,103 @@
+use super::{EventSink, SinkRecord};
+use anyhow::{anyhow, Error};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tokio::net::UdpSocket;
+
+#[cfg(unix)]
+use tokio::net::UnixDatagram;
+
+const FACILITY_USER: u8 = 1;
+const APP_NAME: &str = "edge-runtime";
+
+#[derive(Debug, Clone)]
+pub enum SyslogAddr {
+    Udp(String),
+    Unix(PathBuf),
+}
+
+impl FromStr for SyslogAddr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(anyhow!("syslog address is empty"));
+        }
+
+        Ok(if s.starts_with('/') {
+            Self::Unix(PathBuf::from(s))
+        } else {
+            Self::Udp(s.to_string())
+        })
+    }
+}
+
+enum SyslogSocket {
+    Udp(UdpSocket),
+    #[cfg(unix)]
+    Unix(UnixDatagram),
+}
+
+/// Sends each event as an RFC 5424 message.
+pub struct SyslogSink {
+    socket: SyslogSocket,
+}
+
+impl SyslogSink {
+    pub async fn new(addr: &SyslogAddr) -> Result<Self, Error> {
+        let socket = match addr {
+            SyslogAddr::Udp(addr) => {
+                let socket = UdpSocket::bind("0.0.0.0:0").await?;
+
+                socket.connect(addr).await?;
+                SyslogSocket::Udp(socket)
+            }
+
+            #[cfg(unix)]
+            SyslogAddr::Unix(path) => {
+                let socket = UnixDatagram::unbound()?;
+
+                socket.connect(path)?;
+                SyslogSocket::Unix(socket)
+            }
+
+            #[cfg(not(unix))]
+            SyslogAddr::Unix(_) => {
+                return Err(anyhow!("unix syslog sockets are not supported"));
+            }
+        };
+
+        Ok(Self { socket })
+    }
+}
+
+#[async_trait]
+impl EventSink for SyslogSink {
+    fn name(&self) -> &'static str {
+        "syslog"
+    }
+
+    async fn write(&mut self, batch: &[SinkRecord], written: &mut usize) -> Result<(), Error> {
+        for record in batch {
+            // NOTE: The timestamp and the hostname are left to the syslog
+            // daemon, the event itself carries its own timestamp.
+            let msg = format!(
+                "<{}>1 - - {} - - - {}",
+                FACILITY_USER * 8 + record.severity,
+                APP_NAME,
+                record.line
+            );
+
+            match &self.socket {
+                SyslogSocket::Udp(socket) => socket.send(msg.as_bytes()).await?,
+                #[cfg(unix)]
+                SyslogSocket::Unix(socket) => socket.send(msg.as_bytes()).await?,
+            };
+
+            *written += 1;
+        }
+
+        Ok(())
+    }
+}
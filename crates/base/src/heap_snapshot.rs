@@ -0,0 +1,59 @@
+use crate::utils::diagnostics::new_output_path;
+use anyhow::{anyhow, Error};
+use deno_core::v8;
+use sb_workers::context::HeapSnapshotOpts;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+const HEAP_SNAPSHOT_EXT: &str = "heapsnapshot";
+
+/// Writes a heap snapshot of the isolate and returns its path.
+///
+/// A snapshot growing past `max_size_mb` is discarded rather than truncated,
+/// since a partial snapshot can't be loaded anyway.
+pub fn write_heap_snapshot(
+    isolate: &mut v8::Isolate,
+    opts: &HeapSnapshotOpts,
+    service_path: &str,
+    key: Option<Uuid>,
+) -> Result<PathBuf, Error> {
+    let path = new_output_path(
+        &opts.dir,
+        service_path,
+        key,
+        HEAP_SNAPSHOT_EXT,
+        opts.max_count,
+    )?;
+
+    let max_size = (opts.max_size_mb > 0).then(|| opts.max_size_mb as usize * 1024 * 1024);
+    let mut writer = BufWriter::new(File::create(&path)?);
+    let mut written = 0usize;
+    let mut result = Ok(());
+
+    isolate.take_heap_snapshot(|chunk| {
+        written += chunk.len();
+
+        if max_size.is_some_and(|it| written > it) {
+            result = Err(anyhow!("heap snapshot has exceeded the size limit"));
+            return false;
+        }
+
+        if let Err(err) = writer.write_all(chunk) {
+            result = Err(err.into());
+            return false;
+        }
+
+        true
+    });
+
+    if let Err(err) = result.and_then(|_| writer.flush().map_err(Error::from)) {
+        drop(writer);
+        let _ = fs::remove_file(&path);
+
+        return Err(err);
+    }
+
+    Ok(path)
+}
@@ -2,12 +2,15 @@ extern crate core;
 
 pub mod commands;
 pub mod deno_runtime;
+pub mod event_sinks;
 pub mod macros;
 pub mod rt_worker;
 pub mod server;
 pub mod snapshot;
 pub mod utils;
 
+mod cpu_profile;
+mod heap_snapshot;
 mod inspector_server;
 mod timeout;
 
@@ -25,6 +25,7 @@ macro_rules! integration_test_listen_fut {
             None,
             Some("https://esm.sh/preact".to_string()),
             Some("jsx-runtime".to_string()),
+            None,
         )
         .boxed()
     }};
@@ -1,10 +1,9 @@
 use crate::deno_runtime::DenoRuntime;
 use crate::rt_worker::supervisor::CPUUsageMetrics;
+use crate::rt_worker::utils::classify_boot_failure;
 use crate::rt_worker::worker::{DuplexStreamEntry, HandleCreationType, Worker, WorkerHandler};
 use anyhow::Error;
-use event_worker::events::{
-    BootFailureEvent, EventLoopCompletedEvent, UncaughtExceptionEvent, WorkerEvents,
-};
+use event_worker::events::{EventLoopCompletedEvent, UncaughtExceptionEvent, WorkerEvents};
 use log::error;
 use std::any::Any;
 use std::time::Duration;
@@ -14,9 +13,7 @@ use tokio::sync::oneshot::Receiver;
 impl WorkerHandler for Worker {
     fn handle_error(&self, error: Error) -> Result<WorkerEvents, Error> {
         log::error!("{}", error);
-        Ok(WorkerEvents::BootFailure(BootFailureEvent {
-            msg: error.to_string(),
-        }))
+        Ok(WorkerEvents::BootFailure(classify_boot_failure(&error)))
     }
 
     fn handle_creation<'r>(
@@ -47,10 +44,9 @@ impl WorkerHandler for Worker {
                             err_string.as_str()
                         );
 
-                        Ok(WorkerEvents::UncaughtException(UncaughtExceptionEvent {
-                            exception: err_string,
-                            cpu_time_used: cpu_usage_ms as usize,
-                        }))
+                        Ok(WorkerEvents::UncaughtException(
+                            UncaughtExceptionEvent::new(&err, cpu_usage_ms as usize),
+                        ))
                     }
                 }
 
@@ -1,4 +1,5 @@
 pub mod implementation;
+pub mod primary_worker;
 pub mod supervisor;
 pub mod utils;
 pub mod worker;
@@ -0,0 +1,288 @@
+use crate::rt_worker::worker_ctx::{TerminationToken, WorkerCtx};
+use crate::utils::send_event_if_event_worker_available;
+use anyhow::Error;
+use event_worker::channel::EventSender;
+use event_worker::events::{EventMetadata, PrimaryWorkerRestartEvent, WorkerEvents};
+use futures_util::future::BoxFuture;
+use http::{header, StatusCode};
+use hyper::{Body, Response};
+use log::{error, info};
+use sb_workers::context::{WorkerExit, WorkerKind, WorkerRequestMsg};
+use std::future::pending;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
+
+/// How a main or events worker that has left its event loop is booted again.
+#[derive(Debug, Clone, Copy)]
+pub struct PrimaryWorkerRestartPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl PrimaryWorkerRestartPolicy {
+    /// Returns `None` if `initial_backoff_ms` is zero, which disables restarts.
+    pub fn new(initial_backoff_ms: u64, max_backoff_ms: u64) -> Option<Self> {
+        (initial_backoff_ms > 0).then(|| Self {
+            initial_backoff: Duration::from_millis(initial_backoff_ms),
+            max_backoff: Duration::from_millis(max_backoff_ms.max(initial_backoff_ms)),
+        })
+    }
+
+    fn backoff(&self, attempt: usize) -> Duration {
+        let exp = u32::try_from(attempt).unwrap_or(u32::MAX);
+
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(exp))
+            .min(self.max_backoff)
+    }
+}
+
+/// Answers a request that arrived while the main worker was being restarted.
+fn respond_unavailable(msg: WorkerRequestMsg, retry_after: Duration) {
+    let res = Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header(
+            header::RETRY_AFTER,
+            (retry_after.as_secs_f64().ceil() as u64).max(1),
+        )
+        .body(Body::from("main worker is restarting"))
+        .unwrap();
+
+    let _ = msg.res_tx.send(Ok(res));
+}
+
+pub type PrimaryWorkerBootFn = Box<
+    dyn Fn(Option<TerminationToken>) -> BoxFuture<'static, Result<WorkerCtx, Error>> + Send + Sync,
+>;
+
+/// A booted incarnation of a primary worker.
+pub struct PrimaryWorkerIncarnation {
+    exit: WorkerExit,
+    msg_tx: mpsc::UnboundedSender<WorkerRequestMsg>,
+    token: Option<TerminationToken>,
+    booted_at: Instant,
+}
+
+impl PrimaryWorkerIncarnation {
+    pub fn new(ctx: &WorkerCtx, token: Option<TerminationToken>) -> Self {
+        Self {
+            exit: ctx.exit.clone(),
+            msg_tx: ctx.msg_tx.clone(),
+            token,
+            booted_at: Instant::now(),
+        }
+    }
+}
+
+/// Boots a primary worker again with an exponential backoff whenever it
+/// leaves its event loop.
+///
+/// Requests to the main worker go through a channel that is kept open across
+/// restarts, while the events worker keeps accepting events from the same
+/// event channel.
+///
+/// Each incarnation gets its own termination token, so that the one of the
+/// supervisor is only signaled once the worker has been terminated for good.
+pub struct PrimaryWorkerSupervisor {
+    pub kind: WorkerKind,
+    pub service_path: String,
+    pub policy: PrimaryWorkerRestartPolicy,
+    pub termination_token: Option<TerminationToken>,
+    pub events_tx: Option<EventSender>,
+    pub boot: PrimaryWorkerBootFn,
+}
+
+impl PrimaryWorkerSupervisor {
+    pub fn new_token(&self) -> Option<TerminationToken> {
+        self.termination_token
+            .as_ref()
+            .map(|_| TerminationToken::new())
+    }
+
+    pub fn spawn(
+        self,
+        first: PrimaryWorkerIncarnation,
+        front_rx: Option<mpsc::UnboundedReceiver<WorkerRequestMsg>>,
+    ) {
+        drop(tokio::spawn(self.run(first, front_rx)));
+    }
+
+    async fn run(
+        self,
+        first: PrimaryWorkerIncarnation,
+        mut front_rx: Option<mpsc::UnboundedReceiver<WorkerRequestMsg>>,
+    ) {
+        let mut current = Some(first);
+        let mut attempt = 0usize;
+        let mut restart_at = None::<Instant>;
+        let mut maybe_boot = None::<(
+            Option<TerminationToken>,
+            BoxFuture<'static, Result<WorkerCtx, Error>>,
+        )>;
+
+        loop {
+            let maybe_exit = current.as_ref().map(|it| it.exit.clone());
+
+            tokio::select! {
+                biased;
+
+                _ = async {
+                    match self.termination_token.as_ref() {
+                        Some(token) => token.inbound.cancelled().await,
+                        None => pending().await,
+                    }
+                } => {
+                    break;
+                }
+
+                _ = async {
+                    match maybe_exit {
+                        Some(exit) => exit.exited().await,
+                        None => pending().await,
+                    }
+                } => {
+                    let Some(incarnation) = current.take() else {
+                        continue;
+                    };
+
+                    // NOTE: The exited worker is kept alive until its token is
+                    // cancelled.
+                    if let Some(token) = incarnation.token.as_ref() {
+                        token.cancel();
+                    }
+
+                    if incarnation.booted_at.elapsed() >= self.policy.max_backoff {
+                        attempt = 0;
+                    }
+
+                    let reason = incarnation
+                        .exit
+                        .error()
+                        .await
+                        .map(|it| it.to_string())
+                        .unwrap_or_else(|| String::from("event loop completed"));
+
+                    restart_at = Some(self.schedule_restart(&mut attempt, reason));
+                }
+
+                _ = async {
+                    match restart_at {
+                        Some(at) => sleep_until(at).await,
+                        None => pending().await,
+                    }
+                } => {
+                    let token = self.new_token();
+
+                    restart_at = None;
+                    maybe_boot = Some((token.clone(), (self.boot)(token)));
+                }
+
+                result = async {
+                    match maybe_boot.as_mut() {
+                        Some((_, fut)) => fut.await,
+                        None => pending().await,
+                    }
+                } => {
+                    let Some((token, _)) = maybe_boot.take() else {
+                        continue;
+                    };
+
+                    match result {
+                        Ok(ctx) => {
+                            info!("{} worker has been restarted: {}", self.kind, self.service_path);
+                            current = Some(PrimaryWorkerIncarnation::new(&ctx, token));
+                        }
+
+                        Err(err) => {
+                            restart_at = Some(self.schedule_restart(&mut attempt, err.to_string()));
+                        }
+                    }
+                }
+
+                Some(msg) = async {
+                    match front_rx.as_mut() {
+                        Some(rx) => rx.recv().await,
+                        None => pending().await,
+                    }
+                } => {
+                    let msg = match current.as_ref() {
+                        Some(incarnation) => match incarnation.msg_tx.send(msg) {
+                            Ok(_) => continue,
+                            Err(err) => err.0,
+                        },
+
+                        None => msg,
+                    };
+
+                    let retry_after = restart_at
+                        .map(|it| it.saturating_duration_since(Instant::now()))
+                        .unwrap_or_default();
+
+                    respond_unavailable(msg, retry_after);
+                }
+            }
+        }
+
+        if let Some((token, fut)) = maybe_boot.take() {
+            if let (Ok(_), Some(token)) = (fut.await, token) {
+                token.cancel_and_wait().await;
+            }
+        }
+
+        if let Some(token) = current.and_then(|it| it.token) {
+            token.cancel_and_wait().await;
+        }
+
+        if let Some(token) = self.termination_token.as_ref() {
+            token.outbound.cancel();
+        }
+    }
+
+    fn schedule_restart(&self, attempt: &mut usize, reason: String) -> Instant {
+        let backoff = self.policy.backoff(*attempt);
+
+        *attempt += 1;
+
+        error!(
+            "{} worker is not running, restarting in {}ms (attempt: {}, reason: {})",
+            self.kind,
+            backoff.as_millis(),
+            attempt,
+            reason
+        );
+
+        send_event_if_event_worker_available(
+            self.events_tx.clone(),
+            WorkerEvents::PrimaryWorkerRestart(PrimaryWorkerRestartEvent {
+                attempt: *attempt,
+                backoff_ms: backoff.as_millis() as u64,
+                reason: Some(reason),
+            }),
+            EventMetadata {
+                service_path: Some(self.service_path.clone()),
+                ..Default::default()
+            },
+        );
+
+        Instant::now() + backoff
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PrimaryWorkerRestartPolicy;
+    use std::time::Duration;
+
+    #[test]
+    fn test_restart_backoff_is_exponential_and_capped() {
+        let policy = PrimaryWorkerRestartPolicy::new(100, 1000).unwrap();
+
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(800));
+        assert_eq!(policy.backoff(4), Duration::from_millis(1000));
+        assert_eq!(policy.backoff(usize::MAX), Duration::from_millis(1000));
+        assert!(PrimaryWorkerRestartPolicy::new(0, 1000).is_none());
+    }
+}
@@ -1,17 +1,28 @@
 pub mod strategy_per_request;
 pub mod strategy_per_worker;
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    future::pending,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
 use cpu_timer::{CPUAlarmVal, CPUTimer};
 use deno_core::v8::IsolateHandle;
 use enum_as_inner::EnumAsInner;
-use futures_util::task::AtomicWaker;
+use event_worker::events::ShutdownReason;
+use futures_util::{future::BoxFuture, task::AtomicWaker, FutureExt};
 use log::error;
+use once_cell::sync::Lazy;
+use sb_core::WaitUntilTracker;
 use sb_workers::context::{Timing, UserWorkerMsgs, UserWorkerRuntimeOpts};
-use tokio::sync::{
-    mpsc::{self, UnboundedReceiver},
-    oneshot,
+use tokio::{
+    sync::{
+        mpsc::{self, UnboundedReceiver},
+        oneshot,
+    },
+    time::Instant,
 };
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
@@ -87,12 +98,12 @@ impl CPUTimerParam {
 
         Some((
             CPUTimer::start(
-                if policy.is_per_worker() {
-                    self.soft_limit_ms
-                } else {
+                if policy.is_request_scoped() {
                     self.hard_limit_ms
+                } else {
+                    self.soft_limit_ms
                 },
-                if policy.is_per_request() {
+                if policy.is_request_scoped() {
                     0
                 } else {
                     self.hard_limit_ms
@@ -127,10 +138,13 @@ pub struct Arguments {
     pub supervisor_policy: SupervisorPolicy,
     pub timing: Option<Timing>,
     pub memory_limit_rx: mpsc::UnboundedReceiver<()>,
+    pub memory_soft_limit_rx: mpsc::UnboundedReceiver<()>,
     pub pool_msg_tx: Option<mpsc::UnboundedSender<UserWorkerMsgs>>,
     pub isolate_memory_usage_tx: oneshot::Sender<IsolateMemoryStats>,
     pub thread_safe_handle: IsolateHandle,
     pub waker: Arc<AtomicWaker>,
+    pub beforeunload_tx: mpsc::UnboundedSender<ShutdownReason>,
+    pub wait_until: WaitUntilTracker,
     pub tokens: Tokens,
 }
 
@@ -145,9 +159,241 @@ pub enum CPUUsageMetrics {
     Leave(CPUUsage),
 }
 
+/// Supervises a user worker, enforcing its limits until it has to be shut
+/// down.
+///
+/// Embedders can provide their own implementation through
+/// [`register_supervisor`] and select it with the returned policy.
+pub trait Supervisor: Send + Sync + 'static {
+    /// Returns the reason of the shutdown along with the CPU time the worker
+    /// has used, in milliseconds.
+    fn supervise(&self, args: Arguments) -> BoxFuture<'static, (ShutdownReason, i64)>;
+}
+
+struct PerWorkerSupervisor;
+
+impl Supervisor for PerWorkerSupervisor {
+    fn supervise(&self, args: Arguments) -> BoxFuture<'static, (ShutdownReason, i64)> {
+        strategy_per_worker::supervise(args).boxed()
+    }
+}
+
+struct PerRequestSupervisor {
+    oneshot: bool,
+}
+
+impl Supervisor for PerRequestSupervisor {
+    fn supervise(&self, args: Arguments) -> BoxFuture<'static, (ShutdownReason, i64)> {
+        strategy_per_request::supervise(args, self.oneshot).boxed()
+    }
+}
+
+static CUSTOM_SUPERVISORS: Lazy<RwLock<HashMap<&'static str, Arc<dyn Supervisor>>>> =
+    Lazy::new(RwLock::default);
+
+/// Registers a custom supervisor under `name`, replacing any previous one,
+/// and returns the policy that selects it. The policy can also be parsed from
+/// `custom:<name>` from then on.
+///
+/// If `per_request` is set, the worker pool hands a worker only one request
+/// at a time, as with `SupervisorPolicy::PerRequest`.
+pub fn register_supervisor(
+    name: &'static str,
+    per_request: bool,
+    supervisor: impl Supervisor,
+) -> SupervisorPolicy {
+    CUSTOM_SUPERVISORS
+        .write()
+        .unwrap()
+        .insert(name, Arc::new(supervisor));
+
+    SupervisorPolicy::register_custom(name, per_request)
+}
+
+/// Returns the supervisor selected by the given policy, or `None` if it names
+/// a custom supervisor that has not been registered.
+pub fn get_supervisor(policy: SupervisorPolicy) -> Option<Arc<dyn Supervisor>> {
+    match policy {
+        SupervisorPolicy::PerWorker => Some(Arc::new(PerWorkerSupervisor)),
+        SupervisorPolicy::PerRequest { oneshot } => {
+            Some(Arc::new(PerRequestSupervisor { oneshot }))
+        }
+        SupervisorPolicy::Custom { name, .. } => {
+            CUSTOM_SUPERVISORS.read().unwrap().get(name).cloned()
+        }
+    }
+}
+
+/// Lets the isolate know why it is about to be retired or terminated by
+/// dispatching a `beforeunload` event, at most once per worker.
+///
+/// The event may be dispatched ahead of time, such as at the half-time of the
+/// wall clock limit. The grace period is still granted once, when the isolate
+/// is about to be terminated.
+pub struct ShutdownNotifier {
+    tx: mpsc::UnboundedSender<ShutdownReason>,
+    waker: Arc<AtomicWaker>,
+    grace_period: Duration,
+    is_dispatched: bool,
+    is_grace_period_consumed: bool,
+}
+
+impl ShutdownNotifier {
+    pub fn new(
+        tx: mpsc::UnboundedSender<ShutdownReason>,
+        waker: Arc<AtomicWaker>,
+        grace_period_ms: u64,
+    ) -> Self {
+        Self {
+            tx,
+            waker,
+            grace_period: Duration::from_millis(grace_period_ms),
+            is_dispatched: false,
+            is_grace_period_consumed: false,
+        }
+    }
+
+    /// Dispatches `beforeunload` without waiting for the handlers. Returns
+    /// `false` if the isolate has already been notified.
+    pub fn notify(&mut self, reason: ShutdownReason) -> bool {
+        if self.is_dispatched {
+            return false;
+        }
+
+        self.is_dispatched = true;
+
+        if self.tx.send(reason).is_err() {
+            return false;
+        }
+
+        self.waker.wake();
+        true
+    }
+
+    /// Dispatches `beforeunload` unless it already has been, and waits for
+    /// the grace period before the caller terminates the isolate. The wait is
+    /// cut short if the isolate hits its memory limit or a CPU alarm fires in
+    /// the meantime.
+    pub async fn notify_and_wait(
+        &mut self,
+        reason: ShutdownReason,
+        memory_limit_rx: &mut UnboundedReceiver<()>,
+        cpu_alarms_rx: Option<&mut UnboundedReceiver<()>>,
+    ) {
+        if self.grace_period.is_zero() || self.is_grace_period_consumed {
+            return;
+        }
+
+        self.is_grace_period_consumed = true;
+        self.notify(reason);
+
+        if self.tx.is_closed() {
+            return;
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(self.grace_period) => {}
+            Some(_) = memory_limit_rx.recv() => {
+                error!("memory limit reached during the shutdown grace period");
+            }
+            Some(_) = wait_cpu_alarm(cpu_alarms_rx) => {
+                error!("CPU time limit reached during the shutdown grace period");
+            }
+        }
+    }
+}
+
+async fn wait_background_deadline(maybe_deadline: Option<Instant>) {
+    match maybe_deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => pending().await,
+    }
+}
+
 async fn wait_cpu_alarm(maybe_alarm: Option<&mut UnboundedReceiver<()>>) -> Option<()> {
     match maybe_alarm {
         Some(alarm) => Some(alarm.recv().await?),
         None => None,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use event_worker::events::ShutdownReason;
+    use futures_util::task::AtomicWaker;
+    use tokio::sync::mpsc;
+
+    use super::ShutdownNotifier;
+
+    #[tokio::test]
+    async fn test_grace_period_is_granted_after_early_notification() {
+        let (tx, mut rx) = mpsc::unbounded_channel::<ShutdownReason>();
+        let (_memory_limit_tx, mut memory_limit_rx) = mpsc::unbounded_channel::<()>();
+        let is_handled = Arc::new(AtomicBool::new(false));
+
+        // NOTE: Stands in for the isolate, whose `beforeunload` handler takes
+        // a while to settle.
+        let isolate = tokio::spawn({
+            let is_handled = is_handled.clone();
+
+            async move {
+                let mut reasons = vec![];
+
+                while let Some(reason) = rx.recv().await {
+                    reasons.push(reason);
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    is_handled.store(true, Ordering::Release);
+                }
+
+                reasons
+            }
+        });
+
+        let mut notifier = ShutdownNotifier::new(tx, Arc::new(AtomicWaker::new()), 500);
+
+        // at the half-time of the wall clock limit
+        assert!(notifier.notify(ShutdownReason::WallClockTime));
+        assert!(!is_handled.load(Ordering::Acquire));
+
+        // at the wall clock limit
+        notifier
+            .notify_and_wait(ShutdownReason::WallClockTime, &mut memory_limit_rx, None)
+            .await;
+
+        assert!(is_handled.load(Ordering::Acquire));
+
+        drop(notifier);
+
+        let reasons = isolate.await.unwrap();
+
+        assert_eq!(reasons.len(), 1);
+        assert!(matches!(reasons[0], ShutdownReason::WallClockTime));
+    }
+
+    #[tokio::test]
+    async fn test_grace_period_is_granted_once() {
+        let (tx, _rx) = mpsc::unbounded_channel::<ShutdownReason>();
+        let (_memory_limit_tx, mut memory_limit_rx) = mpsc::unbounded_channel::<()>();
+        let mut notifier = ShutdownNotifier::new(tx, Arc::new(AtomicWaker::new()), 300);
+
+        let started_at = tokio::time::Instant::now();
+
+        notifier
+            .notify_and_wait(ShutdownReason::CPUTime, &mut memory_limit_rx, None)
+            .await;
+
+        assert!(started_at.elapsed() >= Duration::from_millis(300));
+
+        let started_at = tokio::time::Instant::now();
+
+        notifier
+            .notify_and_wait(ShutdownReason::CPUTime, &mut memory_limit_rx, None)
+            .await;
+
+        assert!(started_at.elapsed() < Duration::from_millis(300));
+    }
+}
@@ -9,7 +9,8 @@ use sb_workers::context::{Timing, TimingStatus, UserWorkerMsgs};
 use tokio::time::Instant;
 
 use crate::rt_worker::supervisor::{
-    handle_interrupt, wait_cpu_alarm, CPUUsage, CPUUsageMetrics, IsolateInterruptData, Tokens,
+    handle_interrupt, wait_background_deadline, wait_cpu_alarm, CPUUsage, CPUUsageMetrics,
+    IsolateInterruptData, ShutdownNotifier, Tokens,
 };
 
 use super::Arguments;
@@ -23,9 +24,13 @@ pub async fn supervise(args: Arguments, oneshot: bool) -> (ShutdownReason, i64)
         cpu_timer_param,
         cpu_usage_metrics_rx,
         mut memory_limit_rx,
+        mut memory_soft_limit_rx,
         pool_msg_tx,
         isolate_memory_usage_tx,
         thread_safe_handle,
+        waker,
+        beforeunload_tx,
+        wait_until,
         tokens: Tokens {
             termination,
             supervise,
@@ -41,8 +46,13 @@ pub async fn supervise(args: Arguments, oneshot: bool) -> (ShutdownReason, i64)
 
     let (cpu_timer, mut cpu_alarms_rx) = cpu_timer.unzip();
     let (_, hard_limit_ms) = cpu_timer_param.limits();
+    let mut shutdown_notifier = ShutdownNotifier::new(
+        beforeunload_tx,
+        waker,
+        runtime_opts.shutdown_grace_period_ms,
+    );
 
-    let _guard = scopeguard::guard(is_retired, |v| {
+    let is_retired = scopeguard::guard(is_retired, |v| {
         v.raise();
     });
 
@@ -57,6 +67,11 @@ pub async fn supervise(args: Arguments, oneshot: bool) -> (ShutdownReason, i64)
     let mut complete_reason = None::<ShutdownReason>;
     let mut req_ack_count = 0usize;
     let mut req_start_ack = false;
+    let mut memory_soft_limit_reached = false;
+
+    let background_budget = Duration::from_millis(runtime_opts.background_budget_ms);
+    let mut background_deadline = None::<Instant>;
+    let mut is_background_budget_exceeded = false;
 
     let wall_clock_limit_ms = runtime_opts.worker_timeout_ms;
     let is_wall_clock_limit_disabled = wall_clock_limit_ms == 0;
@@ -180,10 +195,31 @@ pub async fn supervise(args: Arguments, oneshot: bool) -> (ShutdownReason, i64)
                 error!("memory limit reached for the worker: isolate: {:?}", key);
                 complete_reason = Some(ShutdownReason::Memory);
             }
+
+            Some(_) = memory_soft_limit_rx.recv(), if !memory_soft_limit_reached => {
+                error!("memory soft limit reached: isolate: {:?}", key);
+                memory_soft_limit_reached = true;
+                is_retired.raise();
+                shutdown_notifier.notify(ShutdownReason::MemorySoftLimit);
+
+                if !req_start_ack {
+                    complete_reason = Some(ShutdownReason::MemorySoftLimit);
+                }
+            }
+
+            _ = wait_until.settled(), if background_deadline.is_some() => {
+                complete_reason = Some(ShutdownReason::EarlyDrop);
+            }
+
+            _ = wait_background_deadline(background_deadline) => {
+                error!("background budget exceeded: isolate: {:?}", key);
+                is_background_budget_exceeded = true;
+                complete_reason = Some(ShutdownReason::EarlyDrop);
+            }
         }
 
         match complete_reason.take() {
-            Some(ShutdownReason::EarlyDrop) if !oneshot => {
+            Some(ShutdownReason::EarlyDrop) if !oneshot && !memory_soft_limit_reached => {
                 req_start_ack = false;
                 wall_clock_duration_alert
                     .as_mut()
@@ -198,7 +234,36 @@ pub async fn supervise(args: Arguments, oneshot: bool) -> (ShutdownReason, i64)
                 continue;
             }
 
+            // NOTE: The response has been sent, but the promises passed to
+            // `EdgeRuntime.waitUntil` may still be running. Hold off the
+            // retirement until they settle or the background budget runs
+            // out; CPU and memory limits are still enforced meanwhile.
+            Some(ShutdownReason::EarlyDrop)
+                if !is_background_budget_exceeded
+                    && !background_budget.is_zero()
+                    && wait_until.pending() > 0 =>
+            {
+                background_deadline.get_or_insert_with(|| Instant::now() + background_budget);
+                continue;
+            }
+
             Some(reason) => {
+                let reason = match reason {
+                    ShutdownReason::EarlyDrop if memory_soft_limit_reached => {
+                        ShutdownReason::MemorySoftLimit
+                    }
+
+                    reason => reason,
+                };
+
+                // NOTE: Hard limits are enforced right away; for the others,
+                // the isolate gets a chance to flush its work.
+                if !matches!(reason, ShutdownReason::CPUTime | ShutdownReason::Memory) {
+                    shutdown_notifier
+                        .notify_and_wait(reason, &mut memory_limit_rx, cpu_alarms_rx.as_mut())
+                        .await;
+                }
+
                 let data_ptr_mut = Box::into_raw(Box::new(IsolateInterruptData {
                     should_terminate: true,
                     isolate_memory_usage_tx: Some(isolate_memory_usage_tx),
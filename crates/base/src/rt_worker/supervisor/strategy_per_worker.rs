@@ -6,8 +6,11 @@ use std::thread::ThreadId;
 use event_worker::events::ShutdownReason;
 use log::error;
 use sb_workers::context::{Timing, TimingStatus, UserWorkerMsgs};
+use tokio::time::Instant;
 
-use crate::rt_worker::supervisor::{wait_cpu_alarm, CPUUsage, Tokens};
+use crate::rt_worker::supervisor::{
+    wait_background_deadline, wait_cpu_alarm, CPUUsage, ShutdownNotifier, Tokens,
+};
 
 use super::{handle_interrupt, Arguments, CPUUsageMetrics, IsolateInterruptData};
 
@@ -17,12 +20,16 @@ pub async fn supervise(args: Arguments) -> (ShutdownReason, i64) {
         runtime_opts,
         timing,
         mut memory_limit_rx,
+        mut memory_soft_limit_rx,
         cpu_timer,
         cpu_timer_param,
         cpu_usage_metrics_rx,
         pool_msg_tx,
         isolate_memory_usage_tx,
         thread_safe_handle,
+        waker,
+        beforeunload_tx,
+        wait_until,
         tokens: Tokens {
             termination,
             supervise,
@@ -33,12 +40,18 @@ pub async fn supervise(args: Arguments) -> (ShutdownReason, i64) {
     let Timing {
         status: TimingStatus { demand, is_retired },
         req: (_, mut req_end_rx),
+        mut req_timeout_rx,
     } = timing.unwrap_or_default();
 
     let (cpu_timer, mut cpu_alarms_rx) = cpu_timer.unzip();
     let (soft_limit_ms, hard_limit_ms) = cpu_timer_param.limits();
+    let mut shutdown_notifier = ShutdownNotifier::new(
+        beforeunload_tx,
+        waker,
+        runtime_opts.shutdown_grace_period_ms,
+    );
 
-    let _guard = scopeguard::guard(is_retired, |v| {
+    let is_retired = scopeguard::guard(is_retired, |v| {
         v.raise();
     });
 
@@ -50,9 +63,17 @@ pub async fn supervise(args: Arguments) -> (ShutdownReason, i64) {
     let mut cpu_usage_ms = 0i64;
 
     let mut cpu_time_soft_limit_reached = false;
+    let mut memory_soft_limit_reached = false;
+    let mut request_wall_clock_violations = 0u32;
+    let mut request_wall_clock_limit_reached = false;
     let mut wall_clock_alerts = 0;
     let mut req_ack_count = 0usize;
 
+    let background_budget = Duration::from_millis(runtime_opts.background_budget_ms);
+    let mut background_deadline = None::<Instant>;
+    let mut is_background_budget_exceeded = false;
+    let mut is_early_drop_requested = false;
+
     let wall_clock_limit_ms = runtime_opts.worker_timeout_ms;
     let is_wall_clock_limit_disabled = wall_clock_limit_ms == 0;
 
@@ -100,6 +121,10 @@ pub async fn supervise(args: Arguments) -> (ShutdownReason, i64) {
                     None => pending().await,
                 }
             } => {
+                shutdown_notifier
+                    .notify_and_wait(ShutdownReason::TerminationRequested, &mut memory_limit_rx, cpu_alarms_rx.as_mut())
+                    .await;
+
                 terminate_fn();
                 return (ShutdownReason::TerminationRequested, cpu_usage_ms);
             }
@@ -139,11 +164,10 @@ pub async fn supervise(args: Arguments) -> (ShutdownReason, i64) {
                             } else if cpu_usage_ms >= soft_limit_ms as i64 && !cpu_time_soft_limit_reached {
                                 error!("CPU time soft limit reached: isolate: {:?}", key);
                                 cpu_time_soft_limit_reached = true;
+                                shutdown_notifier.notify(ShutdownReason::CPUTime);
 
                                 if req_ack_count == demand.load(Ordering::Acquire) {
-                                    terminate_fn();
-                                    error!("early termination due to the last request being completed: isolate: {:?}", key);
-                                    return (ShutdownReason::EarlyDrop, cpu_usage_ms);
+                                    is_early_drop_requested = true;
                                 }
                             }
                         }
@@ -156,11 +180,10 @@ pub async fn supervise(args: Arguments) -> (ShutdownReason, i64) {
                     if !cpu_time_soft_limit_reached {
                         error!("CPU time soft limit reached: isolate: {:?}", key);
                         cpu_time_soft_limit_reached = true;
+                        shutdown_notifier.notify(ShutdownReason::CPUTime);
 
                         if req_ack_count == demand.load(Ordering::Acquire) {
-                            terminate_fn();
-                            error!("early termination due to the last request being completed: isolate: {:?}", key);
-                            return (ShutdownReason::EarlyDrop, cpu_usage_ms);
+                            is_early_drop_requested = true;
                         }
                     } else {
                         terminate_fn();
@@ -173,7 +196,11 @@ pub async fn supervise(args: Arguments) -> (ShutdownReason, i64) {
             Some(_) = req_end_rx.recv() => {
                 req_ack_count += 1;
 
-                if !cpu_time_soft_limit_reached {
+                let is_retiring = cpu_time_soft_limit_reached
+                    || memory_soft_limit_reached
+                    || request_wall_clock_limit_reached;
+
+                if !is_retiring {
                     if let Some(tx) = pool_msg_tx.clone() {
                         if tx.send(UserWorkerMsgs::Idle(key)).is_err() {
                             error!("failed to send idle msg to pool: {:?}", key);
@@ -181,13 +208,11 @@ pub async fn supervise(args: Arguments) -> (ShutdownReason, i64) {
                     }
                 }
 
-                if !cpu_time_soft_limit_reached || req_ack_count != demand.load(Ordering::Acquire) {
+                if !is_retiring || req_ack_count != demand.load(Ordering::Acquire) {
                     continue;
                 }
 
-                terminate_fn();
-                error!("early termination due to the last request being completed: isolate: {:?}", key);
-                return (ShutdownReason::EarlyDrop, cpu_usage_ms);
+                is_early_drop_requested = true;
             }
 
             _ = wall_clock_duration_alert.tick(), if !is_wall_clock_limit_disabled => {
@@ -197,9 +222,14 @@ pub async fn supervise(args: Arguments) -> (ShutdownReason, i64) {
                 } else if wall_clock_alerts == 1 {
                     error!("wall clock duration warning: isolate: {:?}", key);
                     wall_clock_alerts += 1;
+                    shutdown_notifier.notify(ShutdownReason::WallClockTime);
                 } else {
                     let is_in_flight_req_exists = req_ack_count != demand.load(Ordering::Acquire);
 
+                    shutdown_notifier
+                        .notify_and_wait(ShutdownReason::WallClockTime, &mut memory_limit_rx, cpu_alarms_rx.as_mut())
+                        .await;
+
                     terminate_fn();
 
                     error!("wall clock duration reached: isolate: {:?} (in_flight_req_exists = {})", key, is_in_flight_req_exists);
@@ -213,6 +243,89 @@ pub async fn supervise(args: Arguments) -> (ShutdownReason, i64) {
                 error!("memory limit reached for the worker: isolate: {:?}", key);
                 return (ShutdownReason::Memory, cpu_usage_ms);
             }
+
+            Some(_) = memory_soft_limit_rx.recv(), if !memory_soft_limit_reached => {
+                error!("memory soft limit reached: isolate: {:?}", key);
+                memory_soft_limit_reached = true;
+                is_retired.raise();
+                shutdown_notifier.notify(ShutdownReason::MemorySoftLimit);
+
+                if req_ack_count == demand.load(Ordering::Acquire) {
+                    is_early_drop_requested = true;
+                }
+            }
+
+            Some(_) = req_timeout_rx.recv() => {
+                request_wall_clock_violations += 1;
+                error!(
+                    "request wall clock limit reached: isolate: {:?} (violations = {})",
+                    key, request_wall_clock_violations
+                );
+
+                let max_violations = runtime_opts.max_request_wall_clock_violations;
+
+                if max_violations > 0
+                    && request_wall_clock_violations >= max_violations
+                    && !request_wall_clock_limit_reached
+                {
+                    error!("retiring the worker due to repeated request timeouts: isolate: {:?}", key);
+                    request_wall_clock_limit_reached = true;
+                    is_retired.raise();
+                    shutdown_notifier.notify(ShutdownReason::WallClockTime);
+
+                    if req_ack_count == demand.load(Ordering::Acquire) {
+                        is_early_drop_requested = true;
+                    }
+                }
+            }
+
+            _ = wait_until.settled(), if background_deadline.is_some() => {}
+
+            _ = wait_background_deadline(background_deadline) => {
+                error!("background budget exceeded: isolate: {:?}", key);
+                is_background_budget_exceeded = true;
+            }
+        }
+
+        if !is_early_drop_requested {
+            continue;
+        }
+
+        if req_ack_count != demand.load(Ordering::Acquire) {
+            // a new request has arrived in the meantime
+            is_early_drop_requested = false;
+            background_deadline = None;
+            continue;
         }
+
+        // NOTE: Hold off the retirement until the promises passed to
+        // `EdgeRuntime.waitUntil` settle or the background budget runs out.
+        if !is_background_budget_exceeded
+            && !background_budget.is_zero()
+            && wait_until.pending() > 0
+        {
+            background_deadline.get_or_insert_with(|| Instant::now() + background_budget);
+            continue;
+        }
+
+        let reason = if memory_soft_limit_reached {
+            ShutdownReason::MemorySoftLimit
+        } else {
+            ShutdownReason::EarlyDrop
+        };
+
+        // NOTE: Same as the per request strategy, the isolate gets a chance
+        // to flush its work before it is retired.
+        shutdown_notifier
+            .notify_and_wait(reason, &mut memory_limit_rx, cpu_alarms_rx.as_mut())
+            .await;
+
+        terminate_fn();
+        error!(
+            "early termination due to the last request being completed: isolate: {:?}",
+            key
+        );
+
+        return (reason, cpu_usage_ms);
     }
 }
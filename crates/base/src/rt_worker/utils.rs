@@ -1,4 +1,14 @@
-use event_worker::events::{EventMetadata, WorkerEventWithMetadata};
+use anyhow::Error;
+use deno_ast::ParseDiagnostic;
+use deno_core::error::JsError;
+use deno_core::ModuleResolutionError;
+use deno_npm::resolution::{
+    NpmPackageVersionResolutionError, NpmResolutionError, PackageReqNotFoundError,
+};
+use eszip::deno_graph::{ModuleError, ModuleGraphError, ResolutionError};
+use event_worker::channel::EventSender;
+use event_worker::events::{BootFailureEvent, BootFailureKind, EventMetadata};
+use import_map::ImportMapError;
 use sb_workers::context::{UserWorkerMsgs, WorkerRuntimeOpts};
 use tokio::sync::mpsc::UnboundedSender;
 use tokio_util::sync::CancellationToken;
@@ -7,7 +17,7 @@ use uuid::Uuid;
 type WorkerCoreConfig = (
     Option<Uuid>,
     Option<UnboundedSender<UserWorkerMsgs>>,
-    Option<UnboundedSender<WorkerEventWithMetadata>>,
+    Option<EventSender>,
     Option<CancellationToken>,
     String,
 );
@@ -32,17 +42,227 @@ pub fn parse_worker_conf(conf: &WorkerRuntimeOpts) -> WorkerCoreConfig {
 }
 
 pub fn get_event_metadata(conf: &WorkerRuntimeOpts) -> EventMetadata {
-    let mut event_metadata = EventMetadata {
-        service_path: None,
-        execution_id: None,
-    };
+    let mut event_metadata = EventMetadata::default();
     if conf.is_user_worker() {
         let conf = conf.as_user_worker().unwrap();
         event_metadata = EventMetadata {
             service_path: conf.service_path.clone(),
             execution_id: conf.key,
+            ..Default::default()
         };
     }
 
     event_metadata
 }
+
+/// Classifies why a worker failed to boot and extracts the location of the
+/// failure from the error.
+pub fn classify_boot_failure(err: &Error) -> BootFailureEvent {
+    let msg = err.to_string();
+
+    if let Some(js_error) = err.chain().find_map(|it| it.downcast_ref::<JsError>()) {
+        // NOTE: A syntax error thrown while compiling a module has no stack
+        // frames besides its location.
+        let kind = if js_error.name.as_deref() == Some("SyntaxError") && js_error.frames.len() <= 1
+        {
+            BootFailureKind::Transpile
+        } else {
+            BootFailureKind::TopLevelException
+        };
+
+        let mut failure = BootFailureEvent::new(kind, msg);
+
+        match js_error.frames.iter().find(|it| it.file_name.is_some()) {
+            Some(frame) => {
+                failure.specifier.clone_from(&frame.file_name);
+                failure.line = frame.line_number.map(|it| it as u32);
+                failure.column = frame.column_number.map(|it| it as u32);
+            }
+
+            None => set_location(&mut failure, false),
+        }
+
+        return failure;
+    }
+
+    if let Some(err) = err
+        .chain()
+        .find_map(|it| it.downcast_ref::<ModuleResolutionError>())
+    {
+        let mut failure = BootFailureEvent::new(BootFailureKind::ModuleResolution, msg);
+
+        if let ModuleResolutionError::ImportPrefixMissing(specifier, referrer) = err {
+            failure.specifier = Some(specifier.clone());
+            failure.referrer.clone_from(referrer);
+        }
+
+        return failure;
+    }
+
+    let kind = err
+        .chain()
+        .find_map(classify_error)
+        .unwrap_or(BootFailureKind::Unknown);
+
+    let is_resolution_error = matches!(
+        kind,
+        BootFailureKind::ModuleResolution | BootFailureKind::NpmResolution
+    );
+
+    let mut failure = BootFailureEvent::new(kind, msg);
+
+    set_location(&mut failure, is_resolution_error);
+    failure
+}
+
+/// Returns the kind of boot failure a single error of the chain stands for,
+/// or `None` if it is not of a known type.
+fn classify_error(err: &(dyn std::error::Error + 'static)) -> Option<BootFailureKind> {
+    let module_error = match err.downcast_ref::<ModuleGraphError>() {
+        Some(ModuleGraphError::ModuleError(it)) => Some(it),
+        _ => err.downcast_ref::<ModuleError>(),
+    };
+
+    if let Some(module_error) = module_error {
+        return Some(match module_error {
+            ModuleError::ParseErr(..) => BootFailureKind::Transpile,
+            _ => BootFailureKind::ModuleResolution,
+        });
+    }
+
+    if err.is::<ParseDiagnostic>() {
+        Some(BootFailureKind::Transpile)
+    } else if err.is::<ModuleGraphError>()
+        || err.is::<ResolutionError>()
+        || err.is::<ImportMapError>()
+    {
+        Some(BootFailureKind::ModuleResolution)
+    } else if err.is::<NpmResolutionError>()
+        || err.is::<PackageReqNotFoundError>()
+        || err.is::<NpmPackageVersionResolutionError>()
+    {
+        Some(BootFailureKind::NpmResolution)
+    } else {
+        None
+    }
+}
+
+/// Fills the location of the failure from its message, where deno reports it
+/// as `<specifier>:<line>:<column>` and quotes the module it couldn't
+/// resolve.
+///
+/// For a resolution error, the location is the one of the import, so it
+/// belongs to the referrer.
+fn set_location(failure: &mut BootFailureEvent, is_resolution_error: bool) {
+    let maybe_location = failure.msg.split_whitespace().find_map(|token| {
+        let token = token.trim_matches(|it: char| matches!(it, '"' | '\'' | '(' | ')' | ',' | '.'));
+        let mut parts = token.rsplitn(3, ':');
+        let column = parts.next()?.parse::<u32>().ok()?;
+        let line = parts.next()?.parse::<u32>().ok()?;
+        let specifier = parts.next().filter(|it| it.contains("://"))?;
+
+        Some((specifier.to_string(), line, column))
+    });
+
+    let maybe_quoted = failure
+        .msg
+        .split('"')
+        .nth(1)
+        .filter(|it| !it.is_empty())
+        .map(String::from);
+
+    if let Some((specifier, line, column)) = maybe_location {
+        failure.line = Some(line);
+        failure.column = Some(column);
+
+        if is_resolution_error {
+            failure.referrer = Some(specifier);
+        } else {
+            failure.specifier = Some(specifier);
+        }
+    }
+
+    if is_resolution_error {
+        failure.specifier = failure.specifier.take().or(maybe_quoted);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use anyhow::{anyhow, Error};
+    use deno_ast::{MediaType, ParseParams, SourceTextInfo};
+    use deno_core::{ModuleResolutionError, ModuleSpecifier};
+    use deno_npm::resolution::PackageReqNotFoundError;
+    use deno_semver::package::PackageReq;
+    use eszip::deno_graph::{ModuleError, ModuleGraphError};
+    use event_worker::events::BootFailureKind;
+
+    use super::classify_boot_failure;
+
+    #[test]
+    fn test_classify_parse_diagnostic_as_transpile() {
+        let diagnostic = deno_ast::parse_module(ParseParams {
+            specifier: ModuleSpecifier::parse("file:///main.ts").unwrap(),
+            text_info: SourceTextInfo::from_string("import * from \"./foo.ts\";".into()),
+            media_type: MediaType::TypeScript,
+            capture_tokens: false,
+            scope_analysis: false,
+            maybe_syntax: None,
+        })
+        .unwrap_err();
+
+        let failure = classify_boot_failure(&Error::from(diagnostic));
+
+        assert_eq!(failure.kind, BootFailureKind::Transpile);
+        assert_eq!(failure.specifier.as_deref(), Some("file:///main.ts"));
+        assert_eq!(failure.line, Some(1));
+    }
+
+    #[test]
+    fn test_classify_missing_module_as_module_resolution() {
+        let err = ModuleGraphError::ModuleError(ModuleError::Missing(
+            ModuleSpecifier::parse("file:///missing.ts").unwrap(),
+            None,
+        ));
+
+        let failure = classify_boot_failure(&Error::from(err));
+
+        assert_eq!(failure.kind, BootFailureKind::ModuleResolution);
+    }
+
+    #[test]
+    fn test_classify_import_prefix_missing_as_module_resolution() {
+        let err = ModuleResolutionError::ImportPrefixMissing(
+            "foo".into(),
+            Some("file:///main.ts".into()),
+        );
+
+        let failure = classify_boot_failure(&Error::from(err));
+
+        assert_eq!(failure.kind, BootFailureKind::ModuleResolution);
+        assert_eq!(failure.specifier.as_deref(), Some("foo"));
+        assert_eq!(failure.referrer.as_deref(), Some("file:///main.ts"));
+    }
+
+    #[test]
+    fn test_classify_npm_error_behind_context_as_npm_resolution() {
+        let err = Error::from(PackageReqNotFoundError(
+            PackageReq::from_str("chalk@5").unwrap(),
+        ))
+        .context("failed to load the main module");
+
+        let failure = classify_boot_failure(&err);
+
+        assert_eq!(failure.kind, BootFailureKind::NpmResolution);
+    }
+
+    #[test]
+    fn test_classify_unknown_error() {
+        // NOTE: The message alone must not decide the kind.
+        let failure = classify_boot_failure(&anyhow!("npm package timed out in time"));
+
+        assert_eq!(failure.kind, BootFailureKind::Unknown);
+    }
+}
@@ -1,23 +1,26 @@
 use crate::deno_runtime::DenoRuntime;
 use crate::inspector_server::Inspector;
 use crate::rt_worker::supervisor;
-use crate::rt_worker::utils::{get_event_metadata, parse_worker_conf};
+use crate::rt_worker::utils::{classify_boot_failure, get_event_metadata, parse_worker_conf};
 use crate::rt_worker::worker_ctx::create_supervisor;
 use crate::utils::send_event_if_event_worker_available;
 use anyhow::{anyhow, Error};
 use base_mem_check::MemCheckState;
+use event_worker::channel::EventSender;
 use event_worker::events::{
     EventLoopCompletedEvent, EventMetadata, ShutdownEvent, ShutdownReason, UncaughtExceptionEvent,
-    WorkerEventWithMetadata, WorkerEvents, WorkerMemoryUsed,
+    WorkerEvents, WorkerMemoryUsed,
 };
 use futures_util::FutureExt;
 use log::{debug, error};
 use sb_core::{MetricSource, RuntimeMetricSource, WorkerMetricSource};
-use sb_workers::context::{UserWorkerMsgs, WorkerContextInitOpts, WorkerExit, WorkerExitStatus};
+use sb_workers::context::{
+    UserWorkerMsgs, WorkerContextInitOpts, WorkerDiagnosticSender, WorkerExit, WorkerExitStatus,
+};
+use sb_workers::errors::WorkerError;
 use std::any::Any;
 use std::future::{pending, Future};
 use std::pin::Pin;
-use tokio::io;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot::{self, Receiver, Sender};
 use tokio::time::Instant;
@@ -28,10 +31,12 @@ use super::supervisor::CPUUsageMetrics;
 use super::worker_ctx::TerminationToken;
 use super::worker_pool::SupervisorPolicy;
 
+pub type WorkerBootResult = Result<(MetricSource, WorkerDiagnosticSender), Error>;
+
 #[derive(Clone)]
 pub struct Worker {
     pub worker_boot_start_time: Instant,
-    pub events_msg_tx: Option<UnboundedSender<WorkerEventWithMetadata>>,
+    pub events_msg_tx: Option<EventSender>,
     pub pool_msg_tx: Option<UnboundedSender<UserWorkerMsgs>>,
     pub cancel: Option<CancellationToken>,
     pub event_metadata: EventMetadata,
@@ -42,7 +47,7 @@ pub struct Worker {
 }
 
 pub type HandleCreationType<'r> = Pin<Box<dyn Future<Output = Result<WorkerEvents, Error>> + 'r>>;
-pub type DuplexStreamEntry = (io::DuplexStream, Option<CancellationToken>);
+pub use sb_core::net::DuplexStreamEntry;
 
 pub trait WorkerHandler: Send {
     fn handle_error(&self, error: Error) -> Result<WorkerEvents, Error>;
@@ -93,7 +98,7 @@ impl Worker {
             UnboundedSender<DuplexStreamEntry>,
             UnboundedReceiver<DuplexStreamEntry>,
         ),
-        booter_signal: Sender<Result<MetricSource, Error>>,
+        booter_signal: Sender<WorkerBootResult>,
         exit: WorkerExit,
         termination_token: Option<TerminationToken>,
         inspector: Option<Inspector>,
@@ -111,6 +116,7 @@ impl Worker {
         let timing = opts.timing.take();
         let worker_kind = opts.conf.to_worker_kind();
         let maybe_main_worker_opts = opts.conf.as_main_worker().cloned();
+        let maybe_usage = opts.conf.as_user_worker().and_then(|it| it.usage.clone());
 
         let cancel = self.cancel.clone();
         let rt = if worker_kind.is_user_worker() {
@@ -121,6 +127,7 @@ impl Worker {
 
         let _worker_handle = rt.spawn_pinned(move || {
             tokio::task::spawn_local(async move {
+                let mut event_metadata = event_metadata;
                 let (maybe_cpu_usage_metrics_tx, maybe_cpu_usage_metrics_rx) = worker_kind
                     .is_user_worker()
                     .then(unbounded_channel::<CPUUsageMetrics>)
@@ -150,7 +157,8 @@ impl Worker {
                             }
                         };
 
-                        let _ = booter_signal.send(Ok(metric_src));
+                        let _ = booter_signal
+                            .send(Ok((metric_src, new_runtime.diagnostic_tx.clone())));
 
                         // CPU TIMER
                         let (termination_event_tx, termination_event_rx) =
@@ -159,10 +167,9 @@ impl Worker {
                         let _cpu_timer;
                         let mut supervise_cancel_token = None;
 
-                        // TODO: Allow customization of supervisor
                         let termination_fut = if worker_kind.is_user_worker() {
                             // cputimer is returned from supervisor and assigned here to keep it in scope.
-                            let Ok((maybe_timer, cancel_token)) = create_supervisor(
+                            let (maybe_timer, cancel_token) = match create_supervisor(
                                 worker_key.unwrap_or(Uuid::nil()),
                                 &mut new_runtime,
                                 supervisor_policy,
@@ -172,8 +179,12 @@ impl Worker {
                                 cancel,
                                 timing,
                                 termination_token.clone(),
-                            ) else {
-                                return;
+                            ) {
+                                Ok(it) => it,
+                                Err(err) => {
+                                    error!("failed to create supervisor: {}", err);
+                                    return;
+                                }
                             };
 
                             _cpu_timer = maybe_timer;
@@ -234,10 +245,14 @@ impl Worker {
                             pending().boxed()
                         };
 
-                        let _guard = scopeguard::guard((), |_| {
+                        let crash_msg_tx = pool_msg_tx.clone();
+                        let mut shutdown_guard = scopeguard::guard(false, |is_clean_exit| {
                             worker_key.and_then(|worker_key_unwrapped| {
                                 pool_msg_tx.map(|tx| {
-                                    if let Err(err) = tx.send(UserWorkerMsgs::Shutdown(worker_key_unwrapped)) {
+                                    if let Err(err) = tx.send(UserWorkerMsgs::Shutdown(
+                                        worker_key_unwrapped,
+                                        is_clean_exit,
+                                    )) {
                                         error!(
                                             "failed to send the shutdown signal to user worker pool: {:?}",
                                             err
@@ -271,17 +286,36 @@ impl Worker {
                                 )
                                 .await;
 
+                            *shutdown_guard = matches!(
+                                result,
+                                Ok(WorkerEvents::Shutdown(ShutdownEvent {
+                                    reason: ShutdownReason::EarlyDrop,
+                                    ..
+                                })) | Ok(WorkerEvents::EventLoopCompleted(_))
+                            );
+
                             let maybe_uncaught_exception_event = match result.as_ref() {
                                 Ok(WorkerEvents::UncaughtException(ev)) => Some(ev.clone()),
-                                Err(err) => Some(UncaughtExceptionEvent {
-                                    cpu_time_used: 0,
-                                    exception: err.to_string()
-                                }),
+                                Err(err) => Some(UncaughtExceptionEvent::new(err, 0)),
 
                                 _ => None
                             };
 
                             if let Some(ev) = maybe_uncaught_exception_event {
+                                // NOTE: The request that was in flight, if
+                                // any, is reported along with the exception.
+                                event_metadata.request_id = maybe_usage
+                                    .as_ref()
+                                    .and_then(|it| it.in_flight_request_id());
+
+                                // let the pool know so it can trip the circuit breaker
+                                if let Some((key, tx)) = worker_key.zip(crash_msg_tx) {
+                                    let _ = tx.send(UserWorkerMsgs::UncaughtException(
+                                        key,
+                                        ev.exception.clone(),
+                                    ));
+                                }
+
                                 exit.set(WorkerExitStatus::WithUncaughtException(ev)).await;
 
                                 if let Some(token) = supervise_cancel_token.as_ref() {
@@ -292,6 +326,8 @@ impl Worker {
                             result
                         };
 
+                        exit.notify_exited();
+
                         if let Some(token) = termination_token.as_ref() {
                             if !worker_kind.is_user_worker() {
                                 let _ = termination_fut.await;
@@ -303,8 +339,9 @@ impl Worker {
                     }
 
                     Err(err) => {
-                        let _ = booter_signal
-                            .send(Err(anyhow!("worker boot error {}", err.to_string())));
+                        let _ = booter_signal.send(Err(anyhow!(WorkerError::BootFailed(
+                            classify_boot_failure(&err)
+                        ))));
                         method_cloner.handle_error(err)
                     }
                 };
@@ -3,17 +3,20 @@ use crate::inspector_server::Inspector;
 use crate::timeout::{self, CancelOnWriteTimeout, ReadTimeoutStream};
 use crate::utils::send_event_if_event_worker_available;
 
-use crate::rt_worker::worker::{Worker, WorkerHandler};
+use crate::rt_worker::primary_worker::{
+    PrimaryWorkerIncarnation, PrimaryWorkerRestartPolicy, PrimaryWorkerSupervisor,
+};
+use crate::rt_worker::worker::{Worker, WorkerBootResult, WorkerHandler};
 use crate::rt_worker::worker_pool::WorkerPool;
 use anyhow::{anyhow, bail, Error};
 use base_mem_check::MemCheckState;
 use cpu_timer::CPUTimer;
 use deno_config::JsxImportSourceConfig;
 use deno_core::{InspectorSessionProxy, LocalInspectorSession};
-use event_worker::events::{
-    BootEvent, ShutdownEvent, WorkerEventWithMetadata, WorkerEvents, WorkerMemoryUsed,
-};
-use futures_util::pin_mut;
+use event_worker::channel::{EventReceiver, EventSender};
+use event_worker::events::{BootEvent, ShutdownEvent, WorkerEvents, WorkerMemoryUsed};
+use futures_util::future::BoxFuture;
+use futures_util::{pin_mut, FutureExt};
 use http::StatusCode;
 use http_utils::io::Upgraded2;
 use http_utils::utils::{emit_status_code, get_upgrade_type};
@@ -21,11 +24,12 @@ use hyper::client::conn::http1;
 use hyper::upgrade::OnUpgrade;
 use hyper::{Body, Request, Response};
 use log::{debug, error};
+use sb_core::conn_sync::RequestContext;
 use sb_core::{MetricSource, SharedMetricSource};
 use sb_graph::{DecoratorType, EszipPayloadKind};
 use sb_workers::context::{
     EventWorkerRuntimeOpts, MainWorkerRuntimeOpts, Timing, UserWorkerMsgs, WorkerContextInitOpts,
-    WorkerExit, WorkerKind, WorkerRequestMsg, WorkerRuntimeOpts,
+    WorkerDiagnosticSender, WorkerExit, WorkerKind, WorkerRequestMsg, WorkerRuntimeOpts,
 };
 use sb_workers::errors::WorkerError;
 use std::future::pending;
@@ -102,11 +106,12 @@ async fn handle_request(
     let (ours, theirs) = io::duplex(1024);
     let WorkerRequestMsg {
         mut req,
-        res_tx,
+        mut res_tx,
         conn_token,
     } = msg;
 
-    let _ = duplex_stream_tx.send((theirs, conn_token.clone()));
+    let context = req.extensions_mut().remove::<RequestContext>();
+    let _ = duplex_stream_tx.send((theirs, conn_token.clone(), context));
     let req_upgrade_type = get_upgrade_type(req.headers());
     let req_upgrade = req_upgrade_type
         .clone()
@@ -169,6 +174,11 @@ async fn handle_request(
         _ = maybe_cancel_fut => {
             Ok(emit_status_code(http::StatusCode::GATEWAY_TIMEOUT, None, false))
         }
+        // the caller has given up on the request, such as when the request
+        // exceeds its wall clock limit
+        _ = res_tx.closed() => {
+            return Ok(());
+        }
     };
 
     let Ok(res) = res else {
@@ -259,7 +269,12 @@ pub fn create_supervisor(
     timing: Option<Timing>,
     termination_token: Option<TerminationToken>,
 ) -> Result<(Option<CPUTimer>, CancellationToken), Error> {
+    let Some(supervisor) = supervisor::get_supervisor(supervisor_policy) else {
+        bail!("supervisor is not registered: {:?}", supervisor_policy);
+    };
+
     let (memory_limit_tx, memory_limit_rx) = mpsc::unbounded_channel();
+    let (memory_soft_limit_tx, memory_soft_limit_rx) = mpsc::unbounded_channel();
     let (waker, thread_safe_handle) = {
         let js_runtime = &mut worker_runtime.js_runtime;
         (
@@ -272,6 +287,8 @@ pub fn create_supervisor(
     let conf = worker_runtime.conf.as_user_worker().unwrap().clone();
     let mem_check_state = worker_runtime.mem_check_state();
     let is_termination_requested = worker_runtime.is_termination_requested.clone();
+    let beforeunload_tx = worker_runtime.beforeunload_tx.clone();
+    let wait_until = worker_runtime.wait_until.clone();
 
     let giveup_process_requests_token = cancel.clone();
     let supervise_cancel_token = CancellationToken::new();
@@ -311,9 +328,27 @@ pub fn create_supervisor(
         }
     });
 
+    worker_runtime.add_memory_soft_limit_callback(move |_| {
+        debug!("memory soft limit triggered: isolate: {:?}", key);
+
+        if memory_soft_limit_tx.send(()).is_err() {
+            error!(
+                "failed to send memory soft limit reached notification(isolate may already be terminating): isolate: {:?}",
+                key
+            );
+        }
+
+        true
+    });
+
+    let mut heap_snapshot_fn = worker_runtime.oom_heap_snapshot_fn();
+
     worker_runtime.js_runtime.add_near_heap_limit_callback({
         let send_fn = send_memory_limit_fn;
         move |current, _| {
+            // NOTE: The snapshot is written before the supervisor is asked to
+            // terminate the isolate.
+            heap_snapshot_fn();
             send_fn("v8");
 
             // give an allowance on current limit (until the isolate is
@@ -349,22 +384,17 @@ pub fn create_supervisor(
                 supervisor_policy,
                 timing,
                 memory_limit_rx,
+                memory_soft_limit_rx,
                 pool_msg_tx,
                 isolate_memory_usage_tx,
                 thread_safe_handle,
                 waker: waker.clone(),
+                beforeunload_tx,
+                wait_until,
                 tokens,
             };
 
-            let (reason, cpu_usage_ms) = {
-                use supervisor::*;
-                match supervisor_policy {
-                    SupervisorPolicy::PerWorker => strategy_per_worker::supervise(args).await,
-                    SupervisorPolicy::PerRequest { oneshot, .. } => {
-                        strategy_per_request::supervise(args, oneshot).await
-                    }
-                }
-            };
+            let (reason, cpu_usage_ms) = supervisor.supervise(args).await;
 
             // NOTE: Sending a signal to the pooler that it is the user worker going
             // disposed down and will not accept awaiting subsequent requests, so
@@ -566,6 +596,7 @@ impl CreateWorkerArgs {
 pub struct WorkerCtx {
     pub metric: MetricSource,
     pub msg_tx: mpsc::UnboundedSender<WorkerRequestMsg>,
+    pub diagnostic_tx: WorkerDiagnosticSender,
     pub exit: WorkerExit,
 }
 
@@ -575,8 +606,7 @@ pub async fn create_worker<Opt: Into<CreateWorkerArgs>>(
     maybe_request_idle_timeout: Option<u64>,
 ) -> Result<WorkerCtx, Error> {
     let (duplex_stream_tx, duplex_stream_rx) = mpsc::unbounded_channel::<DuplexStreamEntry>();
-    let (worker_boot_result_tx, worker_boot_result_rx) =
-        oneshot::channel::<Result<MetricSource, Error>>();
+    let (worker_boot_result_tx, worker_boot_result_rx) = oneshot::channel::<WorkerBootResult>();
 
     let CreateWorkerArgs(worker_init_opts, maybe_supervisor_policy, maybe_termination_token) =
         init_opts.into();
@@ -648,7 +678,7 @@ pub async fn create_worker<Opt: Into<CreateWorkerArgs>>(
                 bail!(err)
             }
 
-            Ok(metric) => {
+            Ok((metric, diagnostic_tx)) => {
                 let elapsed = worker_struct_ref
                     .worker_boot_start_time
                     .elapsed()
@@ -665,6 +695,7 @@ pub async fn create_worker<Opt: Into<CreateWorkerArgs>>(
                 Ok(WorkerCtx {
                     metric,
                     msg_tx: worker_req_tx,
+                    diagnostic_tx,
                     exit,
                 })
             }
@@ -731,42 +762,79 @@ pub async fn create_main_worker(
     termination_token: Option<TerminationToken>,
     inspector: Option<Inspector>,
     jsx: Option<JsxImportSourceConfig>,
+    maybe_restart_policy: Option<PrimaryWorkerRestartPolicy>,
+    worker_events_tx: Option<EventSender>,
 ) -> Result<mpsc::UnboundedSender<WorkerRequestMsg>, Error> {
-    let mut service_path = main_worker_path.clone();
-    let mut maybe_eszip = None;
-    if let Some(ext) = main_worker_path.extension() {
-        if ext == "eszip" {
-            service_path = main_worker_path.parent().unwrap().to_path_buf();
-            maybe_eszip = Some(EszipPayloadKind::VecKind(std::fs::read(main_worker_path)?));
+    let service_path = main_worker_path.to_string_lossy().to_string();
+    let boot = move |termination_token: Option<TerminationToken>| -> BoxFuture<
+        'static,
+        Result<WorkerCtx, Error>,
+    > {
+        let main_worker_path = main_worker_path.clone();
+        let import_map_path = import_map_path.clone();
+        let runtime_opts = runtime_opts.clone();
+        let maybe_entrypoint = maybe_entrypoint.clone();
+        let inspector = inspector.clone();
+        let jsx = jsx.clone();
+
+        async move {
+            let mut service_path = main_worker_path.clone();
+            let mut maybe_eszip = None;
+            if let Some(ext) = main_worker_path.extension() {
+                if ext == "eszip" {
+                    service_path = main_worker_path.parent().unwrap().to_path_buf();
+                    maybe_eszip = Some(EszipPayloadKind::VecKind(std::fs::read(main_worker_path)?));
+                }
+            }
+
+            create_worker(
+                (
+                    WorkerContextInitOpts {
+                        service_path,
+                        import_map_path,
+                        no_module_cache,
+                        events_rx: None,
+                        timing: None,
+                        maybe_eszip,
+                        maybe_entrypoint,
+                        maybe_decorator,
+                        maybe_module_code: None,
+                        conf: WorkerRuntimeOpts::MainWorker(runtime_opts),
+                        env_vars: std::env::vars().collect(),
+                        static_patterns: vec![],
+                        maybe_jsx_import_source_config: jsx,
+                        maybe_pool_policy: None,
+                    },
+                    termination_token,
+                ),
+                inspector,
+                None,
+            )
+            .await
+            .map_err(|err| anyhow!("main worker boot error: {}", err))
         }
-    }
+        .boxed()
+    };
 
-    let ctx = create_worker(
-        (
-            WorkerContextInitOpts {
-                service_path,
-                import_map_path,
-                no_module_cache,
-                events_rx: None,
-                timing: None,
-                maybe_eszip,
-                maybe_entrypoint,
-                maybe_decorator,
-                maybe_module_code: None,
-                conf: WorkerRuntimeOpts::MainWorker(runtime_opts),
-                env_vars: std::env::vars().collect(),
-                static_patterns: vec![],
-                maybe_jsx_import_source_config: jsx,
-            },
-            termination_token,
-        ),
-        inspector,
-        None,
-    )
-    .await
-    .map_err(|err| anyhow!("main worker boot error: {}", err))?;
+    let Some(policy) = maybe_restart_policy else {
+        return Ok(boot(termination_token).await?.msg_tx);
+    };
+
+    let (front_tx, front_rx) = mpsc::unbounded_channel();
+    let supervisor = PrimaryWorkerSupervisor {
+        kind: WorkerKind::MainWorker,
+        service_path,
+        policy,
+        termination_token,
+        events_tx: worker_events_tx,
+        boot: Box::new(boot),
+    };
+
+    let (ctx, token) = boot_first_incarnation(&supervisor).await?;
+
+    supervisor.spawn(PrimaryWorkerIncarnation::new(&ctx, token), Some(front_rx));
 
-    Ok(ctx.msg_tx)
+    Ok(front_tx)
 }
 
 pub async fn create_events_worker(
@@ -775,59 +843,115 @@ pub async fn create_events_worker(
     no_module_cache: bool,
     maybe_entrypoint: Option<String>,
     maybe_decorator: Option<DecoratorType>,
+    events_tx: EventSender,
+    events_rx: EventReceiver,
     termination_token: Option<TerminationToken>,
-) -> Result<(WorkerCtx, mpsc::UnboundedSender<WorkerEventWithMetadata>), Error> {
-    let (events_tx, events_rx) = mpsc::unbounded_channel::<WorkerEventWithMetadata>();
-
-    let mut service_path = events_worker_path.clone();
-    let mut maybe_eszip = None;
-    if let Some(ext) = events_worker_path.extension() {
-        if ext == "eszip" {
-            service_path = events_worker_path.parent().unwrap().to_path_buf();
-            maybe_eszip = Some(EszipPayloadKind::VecKind(std::fs::read(
-                events_worker_path,
-            )?));
+    maybe_restart_policy: Option<PrimaryWorkerRestartPolicy>,
+) -> Result<WorkerCtx, Error> {
+    let service_path = events_worker_path.to_string_lossy().to_string();
+    let boot = move |termination_token: Option<TerminationToken>| -> BoxFuture<
+        'static,
+        Result<WorkerCtx, Error>,
+    > {
+        let events_worker_path = events_worker_path.clone();
+        let import_map_path = import_map_path.clone();
+        let maybe_entrypoint = maybe_entrypoint.clone();
+        let events_rx = events_rx.clone();
+
+        async move {
+            let mut service_path = events_worker_path.clone();
+            let mut maybe_eszip = None;
+            if let Some(ext) = events_worker_path.extension() {
+                if ext == "eszip" {
+                    service_path = events_worker_path.parent().unwrap().to_path_buf();
+                    maybe_eszip = Some(EszipPayloadKind::VecKind(std::fs::read(
+                        events_worker_path,
+                    )?));
+                }
+            }
+
+            create_worker(
+                (
+                    WorkerContextInitOpts {
+                        service_path,
+                        no_module_cache,
+                        import_map_path,
+                        env_vars: std::env::vars().collect(),
+                        events_rx: Some(events_rx),
+                        timing: None,
+                        maybe_eszip,
+                        maybe_entrypoint,
+                        maybe_decorator,
+                        maybe_module_code: None,
+                        conf: WorkerRuntimeOpts::EventsWorker(EventWorkerRuntimeOpts {}),
+                        static_patterns: vec![],
+                        maybe_jsx_import_source_config: None,
+                        maybe_pool_policy: None,
+                    },
+                    termination_token,
+                ),
+                None,
+                None,
+            )
+            .await
+            .map_err(|err| anyhow!("events worker boot error: {}", err))
         }
-    }
+        .boxed()
+    };
 
-    let ctx = create_worker(
-        (
-            WorkerContextInitOpts {
-                service_path,
-                no_module_cache,
-                import_map_path,
-                env_vars: std::env::vars().collect(),
-                events_rx: Some(events_rx),
-                timing: None,
-                maybe_eszip,
-                maybe_entrypoint,
-                maybe_decorator,
-                maybe_module_code: None,
-                conf: WorkerRuntimeOpts::EventsWorker(EventWorkerRuntimeOpts {}),
-                static_patterns: vec![],
-                maybe_jsx_import_source_config: None,
-            },
-            termination_token,
-        ),
-        None,
-        None,
-    )
-    .await
-    .map_err(|err| anyhow!("events worker boot error: {}", err))?;
+    let Some(policy) = maybe_restart_policy else {
+        return boot(termination_token).await;
+    };
+
+    let supervisor = PrimaryWorkerSupervisor {
+        kind: WorkerKind::EventsWorker,
+        service_path,
+        policy,
+        termination_token,
+        // NOTE: Restart events of the events worker stay in the event channel
+        // until it is back.
+        events_tx: Some(events_tx),
+        boot: Box::new(boot),
+    };
 
-    Ok((ctx, events_tx))
+    let (ctx, token) = boot_first_incarnation(&supervisor).await?;
+
+    supervisor.spawn(PrimaryWorkerIncarnation::new(&ctx, token), None);
+
+    Ok(ctx)
+}
+
+async fn boot_first_incarnation(
+    supervisor: &PrimaryWorkerSupervisor,
+) -> Result<(WorkerCtx, Option<TerminationToken>), Error> {
+    let token = supervisor.new_token();
+
+    match (supervisor.boot)(token.clone()).await {
+        Ok(ctx) => Ok((ctx, token)),
+        Err(err) => {
+            // NOTE: The worker was booted with its own token, so the one of
+            // the supervisor must be signaled here as it would have been
+            // without restarts.
+            if let Some(token) = supervisor.termination_token.as_ref() {
+                token.outbound.cancel();
+            }
+
+            Err(err)
+        }
+    }
 }
 
 pub async fn create_user_worker_pool(
     policy: WorkerPoolPolicy,
-    worker_event_sender: Option<mpsc::UnboundedSender<WorkerEventWithMetadata>>,
+    worker_event_sender: Option<EventSender>,
     termination_token: Option<TerminationToken>,
     static_patterns: Vec<String>,
     inspector: Option<Inspector>,
     jsx: Option<JsxImportSourceConfig>,
     request_idle_timeout: Option<u64>,
 ) -> Result<(SharedMetricSource, mpsc::UnboundedSender<UserWorkerMsgs>), Error> {
-    let metric_src = SharedMetricSource::default();
+    let metric_src = SharedMetricSource::default()
+        .with_event_drop_counters(worker_event_sender.as_ref().map(EventSender::drop_counters));
     let (user_worker_msgs_tx, mut user_worker_msgs_rx) =
         mpsc::unbounded_channel::<UserWorkerMsgs>();
 
@@ -850,6 +974,8 @@ pub async fn create_user_worker_pool(
             // Note: Keep this loop non-blocking. Spawn a task to run blocking calls.
             // Handle errors within tasks and log them - do not bubble up errors.
             loop {
+                let maybe_queue_deadline = worker_pool.next_queue_deadline();
+
                 tokio::select! {
                     _ = async {
                         if let Some(token) = token {
@@ -869,6 +995,15 @@ pub async fn create_user_worker_pool(
                         }
                     }
 
+                    _ = async {
+                        match maybe_queue_deadline {
+                            Some(deadline) => tokio::time::sleep_until(deadline).await,
+                            None => pending::<()>().await,
+                        }
+                    } => {
+                        worker_pool.expire_queued_requests();
+                    }
+
                     msg = user_worker_msgs_rx.recv() => {
                         match msg {
                             None => break,
@@ -888,6 +1023,7 @@ pub async fn create_user_worker_pool(
 
                             Some(UserWorkerMsgs::Created(key, profile)) => {
                                 worker_pool.add_user_worker(key, profile);
+                                worker_pool.dispatch_queued_requests();
                             }
 
                             Some(UserWorkerMsgs::SendRequest(key, req, res_tx, conn_token)) => {
@@ -896,10 +1032,61 @@ pub async fn create_user_worker_pool(
 
                             Some(UserWorkerMsgs::Idle(key)) => {
                                 worker_pool.idle(&key);
+                                worker_pool.dispatch_queued_requests();
+                            }
+
+                            Some(UserWorkerMsgs::CreateFailed(service_path, err)) => {
+                                worker_pool.boot_failed(service_path, err);
+                                worker_pool.dispatch_queued_requests();
+                            }
+
+                            Some(UserWorkerMsgs::UncaughtException(key, exception)) => {
+                                worker_pool.crashed(&key, exception);
+                            }
+
+                            Some(UserWorkerMsgs::TakeHeapSnapshot(key, tx)) => {
+                                worker_pool.take_heap_snapshot(&key, tx);
+                            }
+
+                            Some(UserWorkerMsgs::TakeCpuProfile(key, duration, tx)) => {
+                                worker_pool.take_cpu_profile(&key, duration, tx);
+                            }
+
+                            Some(UserWorkerMsgs::ListWorkers(tx)) => {
+                                if tx.send(worker_pool.list_workers()).is_err() {
+                                    error!("main worker receiver dropped");
+                                }
+                            }
+
+                            Some(UserWorkerMsgs::GetWorkerStats(key, tx)) => {
+                                if tx.send(worker_pool.worker_stats(&key)).is_err() {
+                                    error!("main worker receiver dropped");
+                                }
+                            }
+
+                            Some(UserWorkerMsgs::TerminateWorker(key, tx)) => {
+                                let terminated = worker_pool.terminate_worker(&key);
+
+                                worker_pool.dispatch_queued_requests();
+
+                                if tx.send(terminated).is_err() {
+                                    error!("main worker receiver dropped");
+                                }
+                            }
+
+                            Some(UserWorkerMsgs::RetireWorker(key, tx)) => {
+                                let retired = worker_pool.retire_worker(&key);
+
+                                worker_pool.dispatch_queued_requests();
+
+                                if tx.send(retired).is_err() {
+                                    error!("main worker receiver dropped");
+                                }
                             }
 
-                            Some(UserWorkerMsgs::Shutdown(key)) => {
-                                worker_pool.shutdown(&key);
+                            Some(UserWorkerMsgs::Shutdown(key, is_clean_exit)) => {
+                                worker_pool.shutdown(&key, is_clean_exit);
+                                worker_pool.dispatch_queued_requests();
 
                                 if termination_requested && worker_pool.user_workers.is_empty() {
                                     if let Some(token) = token {
@@ -1,73 +1,54 @@
 use crate::inspector_server::Inspector;
 use crate::rt_worker::worker_ctx::{create_worker, send_user_worker_request};
 use crate::server::ServerFlags;
+use crate::timeout::CancelAtDeadline;
+use crate::utils::send_event_if_event_worker_available;
 use anyhow::{anyhow, bail, Context, Error};
-use enum_as_inner::EnumAsInner;
-use event_worker::events::WorkerEventWithMetadata;
+use event_worker::channel::EventSender;
+use event_worker::events::{
+    CircuitBreakerEvent, EventMetadata, RequestEndEvent, RequestStartEvent, WorkerEvents,
+};
+use futures_util::FutureExt;
 use http::Request;
 use hyper::Body;
-use log::error;
+use log::{debug, error};
 use sb_core::util::sync::AtomicFlag;
 use sb_core::SharedMetricSource;
 use sb_workers::context::{
-    CreateUserWorkerResult, SendRequestResult, Timing, TimingStatus, UserWorkerMsgs,
-    UserWorkerProfile, WorkerContextInitOpts, WorkerRuntimeOpts,
+    CreateUserWorkerResult, RequestUsage, SendRequestResult, ServicePoolPolicy, Timing,
+    TimingStatus, UserWorkerMsgs, UserWorkerProfile, UserWorkerStats, WorkerContextInitOpts,
+    WorkerDiagnosticMsg, WorkerDiagnosticSender, WorkerRuntimeOpts, WorkerUsage,
 };
 use sb_workers::errors::WorkerError;
-use std::collections::{HashMap, HashSet};
-use std::convert::Infallible;
-use std::str::FromStr;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot::Sender;
-use tokio::sync::{mpsc, Notify, OwnedSemaphorePermit, Semaphore, TryAcquireError};
+use tokio::sync::{mpsc, oneshot, Notify, OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 use super::worker_ctx::TerminationToken;
 
-#[derive(Debug, Clone, Copy, EnumAsInner)]
-pub enum SupervisorPolicy {
-    PerWorker,
-    PerRequest { oneshot: bool },
-}
-
-impl Default for SupervisorPolicy {
-    fn default() -> Self {
-        Self::PerWorker
-    }
-}
-
-impl FromStr for SupervisorPolicy {
-    type Err = Infallible;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "per_worker" => Ok(Self::PerWorker),
-            "per_request" => Ok(Self::PerRequest { oneshot: false }),
-            "oneshot" => Ok(Self::PerRequest { oneshot: true }),
-            _ => unreachable!(),
-        }
-    }
-}
-
-impl SupervisorPolicy {
-    pub fn oneshot() -> Self {
-        Self::PerRequest { oneshot: true }
-    }
+pub use sb_workers::context::{QueueFullBehavior, SupervisorPolicy};
 
-    pub fn is_oneshot(&self) -> bool {
-        matches!(self, Self::PerRequest { oneshot: true })
-    }
-}
+/// Header whose value is used as the request id of the request events.
+const REQUEST_ID_HEADER: &str = "x-request-id";
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct WorkerPoolPolicy {
     supervisor_policy: SupervisorPolicy,
     max_parallelism: usize,
+    max_total_parallelism: Option<usize>,
     request_wait_timeout_ms: u64,
+    max_queue_size: usize,
+    queue_full_behavior: QueueFullBehavior,
+    queue_weight: u32,
+    circuit_breaker_threshold: usize,
+    circuit_breaker_cooldown_ms: u64,
 }
 
 impl Default for WorkerPoolPolicy {
@@ -80,7 +61,13 @@ impl Default for WorkerPoolPolicy {
         Self {
             supervisor_policy: SupervisorPolicy::default(),
             max_parallelism: available_parallelism,
+            max_total_parallelism: None,
             request_wait_timeout_ms: 10000,
+            max_queue_size: 1000,
+            queue_full_behavior: QueueFullBehavior::default(),
+            queue_weight: 1,
+            circuit_breaker_threshold: 0,
+            circuit_breaker_cooldown_ms: 30000,
         }
     }
 }
@@ -96,10 +83,109 @@ impl WorkerPoolPolicy {
         Self {
             supervisor_policy: supervisor.into().unwrap_or(default.supervisor_policy),
             max_parallelism: max_parallelism.into().unwrap_or(default.max_parallelism),
+            max_total_parallelism: server_flags.max_total_parallelism,
             request_wait_timeout_ms: server_flags
                 .request_wait_timeout_ms
                 .unwrap_or(default.request_wait_timeout_ms),
+            max_queue_size: server_flags
+                .request_queue_size
+                .unwrap_or(default.max_queue_size),
+            queue_full_behavior: server_flags
+                .request_queue_full_behavior
+                .unwrap_or(default.queue_full_behavior),
+            queue_weight: default.queue_weight,
+            circuit_breaker_threshold: server_flags
+                .circuit_breaker_threshold
+                .unwrap_or(default.circuit_breaker_threshold),
+            circuit_breaker_cooldown_ms: server_flags
+                .circuit_breaker_cooldown_ms
+                .unwrap_or(default.circuit_breaker_cooldown_ms),
+        }
+    }
+
+    /// Returns a copy of this policy with the per-service values applied on
+    /// top of it.
+    pub fn with_overrides(&self, overrides: Option<&ServicePoolPolicy>) -> Self {
+        let Some(overrides) = overrides else {
+            return self.clone();
+        };
+
+        let supervisor_policy = overrides
+            .supervisor_policy
+            .unwrap_or(self.supervisor_policy);
+
+        // NOTE: Same as the CLI, the `oneshot` policy forcibly fixes the
+        // maximum parallelism to `1`.
+        let max_parallelism = if supervisor_policy.is_oneshot() {
+            1
+        } else {
+            overrides.max_parallelism.unwrap_or(self.max_parallelism)
+        };
+
+        Self {
+            supervisor_policy,
+            max_parallelism,
+            max_total_parallelism: self.max_total_parallelism,
+            request_wait_timeout_ms: overrides
+                .request_wait_timeout_ms
+                .unwrap_or(self.request_wait_timeout_ms),
+            max_queue_size: overrides.max_queue_size.unwrap_or(self.max_queue_size),
+            queue_full_behavior: self.queue_full_behavior,
+            queue_weight: overrides.queue_weight.unwrap_or(self.queue_weight).max(1),
+            circuit_breaker_threshold: self.circuit_breaker_threshold,
+            circuit_breaker_cooldown_ms: self.circuit_breaker_cooldown_ms,
+        }
+    }
+}
+
+/// Keeps track of the consecutive failures of a service, so that a service
+/// that keeps crashing fails fast instead of booting new workers over and
+/// over again.
+#[derive(Debug, Default)]
+struct CircuitBreaker {
+    failures: usize,
+    last_error: Option<String>,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Returns the cached error if the breaker is open at the given instant.
+    fn check(&self, now: Instant) -> Option<&str> {
+        match self.open_until {
+            Some(until) if now < until => self.last_error.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Records a failure and returns `true` if the breaker has just opened.
+    ///
+    /// Once the cooldown has passed, the breaker lets new workers through
+    /// again, but it keeps the failure count, so the next failure opens it
+    /// right away.
+    fn record_failure(
+        &mut self,
+        err: String,
+        threshold: usize,
+        cooldown: Duration,
+        now: Instant,
+    ) -> bool {
+        self.failures += 1;
+        self.last_error = Some(err);
+
+        if threshold == 0 || self.failures < threshold || self.check(now).is_some() {
+            return false;
         }
+
+        self.open_until = Some(now + cooldown);
+        true
+    }
+
+    /// Records a success and returns `true` if the breaker was open.
+    fn record_success(&mut self) -> bool {
+        let was_open = self.open_until.is_some();
+
+        *self = Self::default();
+        was_open
     }
 }
 
@@ -126,25 +212,64 @@ impl std::hash::Hash for WorkerId {
     }
 }
 
+type CreateUserWorkerResultSender = Sender<Result<CreateUserWorkerResult, Error>>;
+
+/// A worker creation request that is waiting for a free slot in the pool.
+struct QueuedRequest {
+    worker_options: WorkerContextInitOpts,
+    tx: CreateUserWorkerResultSender,
+    termination_token: Option<TerminationToken>,
+    enqueued_at: Instant,
+}
+
+impl QueuedRequest {
+    fn wait_time_ms(&self) -> usize {
+        self.enqueued_at.elapsed().as_millis() as usize
+    }
+
+    fn reject(self, err: Error) {
+        if self.tx.send(Err(err)).is_err() {
+            error!("main worker receiver dropped");
+        }
+    }
+}
+
+struct WorkerPermits {
+    service: OwnedSemaphorePermit,
+    pool: Option<OwnedSemaphorePermit>,
+}
+
 // Simple implementation of Round Robin for the Active Workers
 pub struct ActiveWorkerRegistry {
     workers: HashSet<WorkerId>,
     next: Option<usize>,
-    notify_pair: (flume::Sender<Option<Uuid>>, flume::Receiver<Option<Uuid>>),
     sem: Arc<Semaphore>,
+    policy: WorkerPoolPolicy,
+    queue: VecDeque<QueuedRequest>,
+    deficit: u32,
 }
 
 impl ActiveWorkerRegistry {
-    fn new(max_parallelism: usize) -> Self {
+    fn new(policy: WorkerPoolPolicy) -> Self {
         Self {
             workers: HashSet::default(),
             next: Option::default(),
-            notify_pair: flume::unbounded(),
-            sem: Arc::new(Semaphore::const_new(max_parallelism)),
+            sem: Arc::new(Semaphore::const_new(policy.max_parallelism)),
+            policy,
+            queue: VecDeque::default(),
+            deficit: 0,
         }
     }
 
-    fn mark_used_and_try_advance(&mut self, policy: SupervisorPolicy) -> Option<&Uuid> {
+    fn queue_deadline(&self) -> Option<Instant> {
+        self.queue
+            .front()
+            .map(|it| it.enqueued_at + Duration::from_millis(self.policy.request_wait_timeout_ms))
+    }
+
+    fn mark_used_and_try_advance(&mut self) -> Option<&Uuid> {
+        let policy = self.policy.supervisor_policy;
+
         if self.workers.is_empty() {
             let _ = self.next.take();
             return None;
@@ -158,12 +283,18 @@ impl ActiveWorkerRegistry {
 
         match self.workers.iter().nth(idx).cloned() {
             Some(WorkerId(key, true)) => match policy {
-                SupervisorPolicy::PerWorker => {
+                SupervisorPolicy::PerWorker
+                | SupervisorPolicy::Custom {
+                    per_request: false, ..
+                } => {
                     self.next = Some(idx + 1);
                     self.workers.get(&key).map(|it| &it.0)
                 }
 
-                SupervisorPolicy::PerRequest { .. } => {
+                SupervisorPolicy::PerRequest { .. }
+                | SupervisorPolicy::Custom {
+                    per_request: true, ..
+                } => {
                     let key = self
                         .workers
                         .replace(WorkerId(key, false))
@@ -181,18 +312,11 @@ impl ActiveWorkerRegistry {
         }
     }
 
-    fn mark_idle(&mut self, key: &Uuid, policy: SupervisorPolicy) {
+    fn mark_idle(&mut self, key: &Uuid) {
         if let Some(WorkerId(key, mark)) = self.workers.get(key).cloned() {
-            if policy.is_per_request() {
-                if mark {
-                    return;
-                }
-
+            if self.policy.supervisor_policy.is_request_scoped() && !mark {
                 let _ = self.workers.replace(WorkerId(key, true));
             }
-
-            let (notify_tx, _) = self.notify_pair.clone();
-            let _ = notify_tx.send(Some(key));
         }
     }
 }
@@ -205,6 +329,8 @@ impl ActiveWorkerRegistry {
 // create_worker returns true if an active_worker is available for service_path (force create
 // retires current one adds new one)
 // send_request is called with UUID
+// requests that can't be served right away wait in a bounded queue per service
+// and are dispatched in weighted (deficit) round robin order across services
 pub struct WorkerPool {
     pub policy: WorkerPoolPolicy,
     pub metric_src: SharedMetricSource,
@@ -214,19 +340,28 @@ pub struct WorkerPool {
     pub maybe_inspector: Option<Inspector>,
     pub maybe_request_idle_timeout: Option<u64>,
 
+    pool_sem: Option<Arc<Semaphore>>,
+    pending_services: VecDeque<String>,
+    breakers: HashMap<String, CircuitBreaker>,
+    crashed_workers: HashSet<Uuid>,
+
     // TODO: refactor this out of worker pool
-    pub worker_event_sender: Option<mpsc::UnboundedSender<WorkerEventWithMetadata>>,
+    pub worker_event_sender: Option<EventSender>,
 }
 
 impl WorkerPool {
     pub(crate) fn new(
         policy: WorkerPoolPolicy,
         metric_src: SharedMetricSource,
-        worker_event_sender: Option<UnboundedSender<WorkerEventWithMetadata>>,
+        worker_event_sender: Option<EventSender>,
         worker_pool_msgs_tx: mpsc::UnboundedSender<UserWorkerMsgs>,
         inspector: Option<Inspector>,
         request_idle_timeout: Option<u64>,
     ) -> Self {
+        let pool_sem = policy
+            .max_total_parallelism
+            .map(|it| Arc::new(Semaphore::new(it)));
+
         Self {
             policy,
             metric_src,
@@ -236,13 +371,17 @@ impl WorkerPool {
             maybe_inspector: inspector,
             maybe_request_idle_timeout: request_idle_timeout,
             worker_pool_msgs_tx,
+            pool_sem,
+            pending_services: VecDeque::default(),
+            breakers: HashMap::default(),
+            crashed_workers: HashSet::default(),
         }
     }
 
     pub fn create_user_worker(
         &mut self,
-        mut worker_options: WorkerContextInitOpts,
-        tx: Sender<Result<CreateUserWorkerResult, Error>>,
+        worker_options: WorkerContextInitOpts,
+        tx: CreateUserWorkerResultSender,
         termination_token: Option<TerminationToken>,
     ) {
         let service_path = worker_options
@@ -251,9 +390,8 @@ impl WorkerPool {
             .unwrap_or("")
             .to_string();
 
-        let is_oneshot_policy = self.policy.supervisor_policy.is_oneshot();
-        let inspector = self.maybe_inspector.clone();
-        let request_idle_timeout = self.maybe_request_idle_timeout;
+        let policy = self.service_policy(&service_path, worker_options.maybe_pool_policy.as_ref());
+        let is_oneshot_policy = policy.supervisor_policy.is_oneshot();
 
         let force_create = worker_options
             .conf
@@ -273,129 +411,317 @@ impl WorkerPool {
             return;
         }
 
-        enum FlowAfterFence {
-            Stop,
-            Resend(Sender<Result<CreateUserWorkerResult, Error>>),
-            Create(
-                Option<OwnedSemaphorePermit>,
-                Sender<Result<CreateUserWorkerResult, Error>>,
-            ),
+        // NOTE: The breaker only refuses new workers; the workers that are
+        // still alive keep serving requests.
+        if let Some(err) = self
+            .breakers
+            .get(&service_path)
+            .and_then(|it| it.check(Instant::now()))
+        {
+            if tx.send(Err(anyhow!(err.to_string()))).is_err() {
+                error!("main worker receiver dropped")
+            }
+            return;
         }
 
-        let wait_fence_fut = {
-            let registry = self
-                .active_workers
-                .entry(service_path.clone())
-                .or_insert_with(|| ActiveWorkerRegistry::new(self.policy.max_parallelism));
-
-            let sem = registry.sem.clone();
-            let (_, notify_rx) = registry.notify_pair.clone();
-            let wait_timeout =
-                tokio::time::sleep(Duration::from_millis(self.policy.request_wait_timeout_ms));
-
-            async move {
-                use FlowAfterFence::*;
-
-                match sem.clone().try_acquire_owned() {
-                    Ok(permit) => return Create(Some(permit), tx),
-                    Err(TryAcquireError::NoPermits) if force_create => {
-                        // NOTE(Nyannyacha): Do we need to consider counting the
-                        // permit count (that means it affects maximum
-                        // parallelism) if in the force creation mode?
-                        return Create(None, tx);
-                    }
+        let has_waiters = !self
+            .active_workers
+            .entry(service_path.clone())
+            .or_insert_with(|| ActiveWorkerRegistry::new(policy))
+            .queue
+            .is_empty();
+
+        // NOTE: Requests that are already waiting in the queue go first, so a
+        // new request may only skip the queue if it forces the creation.
+        if !has_waiters || force_create {
+            match self.try_acquire_permits(&service_path) {
+                Some(permits) => {
+                    self.spawn_user_worker(
+                        service_path,
+                        worker_options,
+                        tx,
+                        termination_token,
+                        Some(permits),
+                    );
+
+                    return;
+                }
 
-                    _ => {}
+                None if force_create => {
+                    // NOTE(Nyannyacha): Do we need to consider counting the
+                    // permit count (that means it affects maximum
+                    // parallelism) if in the force creation mode?
+                    self.spawn_user_worker(
+                        service_path,
+                        worker_options,
+                        tx,
+                        termination_token,
+                        None,
+                    );
+                    return;
+                }
+
+                None => {}
+            }
+        }
+
+        self.enqueue(
+            service_path,
+            QueuedRequest {
+                worker_options,
+                tx,
+                termination_token,
+                enqueued_at: Instant::now(),
+            },
+        );
+
+        // NOTE: Looking for an active worker above may have retired some
+        // workers and released their permits.
+        self.dispatch_queued_requests();
+    }
+
+    /// Dispatches waiting requests while there is room in the pool.
+    ///
+    /// Services take turns in deficit round robin order: each turn, a service
+    /// may dispatch as many requests as its queue weight allows, so a busy
+    /// service cannot starve the others when the pool is saturated.
+    pub fn dispatch_queued_requests(&mut self) {
+        let mut ring = std::mem::take(&mut self.pending_services);
+
+        loop {
+            let mut progressed = false;
+            let mut next_ring = VecDeque::with_capacity(ring.len());
+            let mut spent = VecDeque::new();
+
+            while let Some(service_path) = ring.pop_front() {
+                if let Some(registry) = self.active_workers.get_mut(&service_path) {
+                    if registry.deficit == 0 {
+                        registry.deficit = registry.policy.queue_weight;
+                    }
+                } else {
+                    continue;
                 }
 
-                tokio::pin!(wait_timeout);
                 loop {
-                    tokio::select! {
-                        maybe_key = notify_rx.recv_async() => {
-                            match maybe_key {
-                                Err(x) => {
-                                    if tx.send(Err(anyhow!("worker channel is no longer valid: {}", x))).is_err() {
-                                        error!("main worker receiver dropped");
-                                    }
-                                    return Stop;
-                                }
+                    let registry = self.active_workers.get_mut(&service_path).unwrap();
 
-                                Ok(Some(_)) => return Resend(tx),
-                                Ok(None) => {
-                                    if let Ok(permit) = sem.clone().try_acquire_owned() {
-                                        return Create(Some(permit), tx);
-                                    }
-                                }
-                            }
-                        },
+                    if registry.deficit == 0 {
+                        break;
+                    }
 
-                        () = &mut wait_timeout => {
-                            if tx.send(Err(anyhow!("worker did not respond in time"))).is_err() {
-                                error!("main worker receiver dropped");
-                            }
-                            return Stop;
+                    let Some(req) = registry.queue.pop_front() else {
+                        break;
+                    };
+
+                    if req.tx.is_closed() {
+                        self.metric_src.incl_rejected_queued_requests();
+                        continue;
+                    }
+
+                    match self.try_dispatch(&service_path, req) {
+                        Ok(()) => {
+                            progressed = true;
+                            self.active_workers.get_mut(&service_path).unwrap().deficit -= 1;
+                        }
+
+                        Err(req) => {
+                            self.active_workers
+                                .get_mut(&service_path)
+                                .unwrap()
+                                .queue
+                                .push_front(req);
+
+                            break;
                         }
                     }
                 }
+
+                let registry = self.active_workers.get_mut(&service_path).unwrap();
+
+                if registry.queue.is_empty() {
+                    registry.deficit = 0;
+                } else if registry.deficit == 0 {
+                    spent.push_back(service_path);
+                } else {
+                    next_ring.push_back(service_path);
+                }
             }
-        };
 
-        let worker_pool_msgs_tx = self.worker_pool_msgs_tx.clone();
-        let events_msg_tx = self.worker_event_sender.clone();
-        let supervisor_policy = self.policy.supervisor_policy;
+            next_ring.append(&mut spent);
+            ring = next_ring;
 
-        drop(tokio::spawn(async move {
-            let (permit, tx) = match wait_fence_fut.await {
-                FlowAfterFence::Stop => return,
-                FlowAfterFence::Resend(tx) => {
-                    let WorkerContextInitOpts {
-                        service_path,
-                        no_module_cache,
-                        import_map_path,
-                        env_vars,
-                        conf,
-                        maybe_eszip,
-                        maybe_module_code,
-                        maybe_entrypoint,
-                        maybe_decorator,
-                        maybe_jsx_import_source_config,
-                        ..
-                    } = worker_options;
+            if !progressed || ring.is_empty() {
+                break;
+            }
+        }
 
-                    if worker_pool_msgs_tx
-                        .send(UserWorkerMsgs::Create(
-                            WorkerContextInitOpts {
-                                service_path,
-                                no_module_cache,
-                                import_map_path,
-                                env_vars,
-                                events_rx: None,
-                                timing: None,
-                                conf,
-                                maybe_eszip,
-                                maybe_module_code,
-                                maybe_entrypoint,
-                                maybe_decorator,
-                                static_patterns: vec![],
-                                maybe_jsx_import_source_config,
-                            },
-                            tx,
-                        ))
-                        .is_err()
-                    {
-                        error!("main worker receiver dropped");
-                    }
+        self.pending_services = ring;
+    }
 
-                    return;
+    /// Rejects waiting requests that have exceeded the request wait timeout of
+    /// their service.
+    pub fn expire_queued_requests(&mut self) {
+        let now = Instant::now();
+
+        for registry in self.active_workers.values_mut() {
+            while let Some(deadline) = registry.queue_deadline() {
+                let is_closed = registry.queue.front().map_or(false, |it| it.tx.is_closed());
+
+                if deadline > now && !is_closed {
+                    break;
                 }
 
-                FlowAfterFence::Create(permit, tx) => (permit, tx),
+                let req = registry.queue.pop_front().unwrap();
+
+                self.metric_src.incl_rejected_queued_requests();
+                req.reject(anyhow!(WorkerError::RequestWaitTimedOut));
+            }
+        }
+
+        self.pending_services.retain(|it| {
+            self.active_workers
+                .get(it)
+                .map_or(false, |registry| !registry.queue.is_empty())
+        });
+    }
+
+    /// Returns the earliest point in time at which a waiting request times out.
+    pub fn next_queue_deadline(&self) -> Option<Instant> {
+        self.pending_services
+            .iter()
+            .filter_map(|it| self.active_workers.get(it))
+            .filter_map(ActiveWorkerRegistry::queue_deadline)
+            .min()
+    }
+
+    pub fn queue_depth(&self, service_path: &str) -> usize {
+        self.active_workers
+            .get(service_path)
+            .map_or(0, |it| it.queue.len())
+    }
+
+    fn enqueue(&mut self, service_path: String, req: QueuedRequest) {
+        let registry = self
+            .active_workers
+            .get_mut(&service_path)
+            .expect("registry must be initialized at this point");
+
+        if registry.queue.len() >= registry.policy.max_queue_size {
+            let evicted = match registry.policy.queue_full_behavior {
+                QueueFullBehavior::DropOldest if !registry.queue.is_empty() => {
+                    registry.queue.pop_front()
+                }
+
+                _ => {
+                    self.metric_src.incl_rejected_requests();
+                    req.reject(anyhow!("request queue of the service is full"));
+                    return;
+                }
             };
 
+            if let Some(evicted) = evicted {
+                self.metric_src.incl_rejected_queued_requests();
+                evicted.reject(anyhow!("request was evicted from the queue of the service"));
+            }
+        }
+
+        registry.queue.push_back(req);
+        self.metric_src.incl_queued_requests();
+
+        debug!(
+            "request queued: service_path={} depth={}",
+            service_path,
+            registry.queue.len()
+        );
+
+        if !self.pending_services.contains(&service_path) {
+            self.pending_services.push_back(service_path);
+        }
+    }
+
+    fn try_dispatch(
+        &mut self,
+        service_path: &String,
+        req: QueuedRequest,
+    ) -> Result<(), QueuedRequest> {
+        if let Some(key) = self.maybe_active_worker(service_path, false) {
+            self.metric_src.incl_dequeued_requests(req.wait_time_ms());
+
+            if req.tx.send(Ok(CreateUserWorkerResult { key })).is_err() {
+                error!("main worker receiver dropped")
+            }
+
+            return Ok(());
+        }
+
+        // NOTE: Same as a new request, a queued one is refused a new worker
+        // while the breaker of its service is open.
+        if let Some(err) = self
+            .breakers
+            .get(service_path)
+            .and_then(|it| it.check(Instant::now()))
+            .map(String::from)
+        {
+            self.metric_src.incl_rejected_queued_requests();
+            req.reject(anyhow!(err));
+            return Ok(());
+        }
+
+        let Some(permits) = self.try_acquire_permits(service_path) else {
+            return Err(req);
+        };
+
+        self.metric_src.incl_dequeued_requests(req.wait_time_ms());
+        self.spawn_user_worker(
+            service_path.clone(),
+            req.worker_options,
+            req.tx,
+            req.termination_token,
+            Some(permits),
+        );
+
+        Ok(())
+    }
+
+    fn try_acquire_permits(&self, service_path: &String) -> Option<WorkerPermits> {
+        let registry = self.active_workers.get(service_path)?;
+        let service = registry.sem.clone().try_acquire_owned().ok()?;
+        let pool = match self.pool_sem.as_ref() {
+            Some(sem) => Some(sem.clone().try_acquire_owned().ok()?),
+            None => None,
+        };
+
+        Some(WorkerPermits { service, pool })
+    }
+
+    fn spawn_user_worker(
+        &self,
+        service_path: String,
+        mut worker_options: WorkerContextInitOpts,
+        tx: CreateUserWorkerResultSender,
+        termination_token: Option<TerminationToken>,
+        permits: Option<WorkerPermits>,
+    ) {
+        let supervisor_policy = self
+            .active_workers
+            .get(&service_path)
+            .map(|it| it.policy.supervisor_policy)
+            .unwrap_or(self.policy.supervisor_policy);
+
+        let inspector = self.maybe_inspector.clone();
+        let request_idle_timeout = self.maybe_request_idle_timeout;
+        let worker_pool_msgs_tx = self.worker_pool_msgs_tx.clone();
+        let events_msg_tx = self.worker_event_sender.clone();
+
+        drop(tokio::spawn(async move {
             let Ok(mut user_worker_rt_opts) = worker_options.conf.into_user_worker() else {
                 return;
             };
 
+            // NOTE: Every user worker gets a termination token so that it can
+            // be terminated explicitly by the main worker.
+            let termination_token = termination_token.unwrap_or_default();
+
             let uuid = uuid::Uuid::new_v4();
             let cancel = CancellationToken::new();
             let (req_start_timing_tx, req_start_timing_rx) =
@@ -407,9 +733,24 @@ impl WorkerPool {
             };
 
             let (req_end_timing_tx, req_end_timing_rx) = mpsc::unbounded_channel::<()>();
+            let (req_timeout_tx, req_timeout_rx) = mpsc::unbounded_channel::<()>();
+
+            // NOTE: Request scoped workers are already bounded by their own
+            // wall clock limit.
+            let request_wall_clock_limit = (!supervisor_policy.is_request_scoped()
+                && user_worker_rt_opts.request_wall_clock_limit_ms > 0)
+                .then(|| Duration::from_millis(user_worker_rt_opts.request_wall_clock_limit_ms));
+
+            let cpu_profile_sample_rate = user_worker_rt_opts
+                .cpu_profile
+                .as_ref()
+                .map_or(0.0, |it| it.sample_rate);
+
+            let usage = Arc::new(WorkerUsage::default());
 
             user_worker_rt_opts.service_path = Some(service_path.clone());
             user_worker_rt_opts.key = Some(uuid);
+            user_worker_rt_opts.usage = Some(usage.clone());
 
             user_worker_rt_opts.pool_msg_tx = Some(worker_pool_msgs_tx.clone());
             user_worker_rt_opts.events_msg_tx = events_msg_tx;
@@ -418,26 +759,47 @@ impl WorkerPool {
             worker_options.timing = Some(Timing {
                 status: status.clone(),
                 req: (req_start_timing_rx, req_end_timing_rx),
+                req_timeout_rx,
             });
 
             worker_options.conf = WorkerRuntimeOpts::UserWorker(user_worker_rt_opts);
 
             match create_worker(
-                (worker_options, supervisor_policy, termination_token.clone()),
+                (
+                    worker_options,
+                    supervisor_policy,
+                    Some(termination_token.clone()),
+                ),
                 inspector,
                 request_idle_timeout,
             )
             .await
             {
                 Ok(ctx) => {
+                    let (permit, pool_permit) = match permits {
+                        Some(WorkerPermits { service, pool }) => {
+                            (Some(Arc::new(service)), pool.map(Arc::new))
+                        }
+
+                        None => (None, None),
+                    };
+
                     let profile = UserWorkerProfile {
                         worker_request_msg_tx: ctx.msg_tx,
                         timing_tx_pair: (req_start_timing_tx, req_end_timing_tx),
+                        req_timeout_tx,
+                        request_wall_clock_limit,
+                        diagnostic_tx: ctx.diagnostic_tx,
+                        cpu_profile_sample_rate,
+                        usage,
                         service_path,
-                        permit: permit.map(Arc::new),
+                        permit,
+                        pool_permit,
                         status: status.clone(),
                         exit: ctx.exit,
                         cancel,
+                        termination: termination_token.inbound.clone(),
+                        created_at: std::time::Instant::now(),
                     };
 
                     if worker_pool_msgs_tx
@@ -453,6 +815,16 @@ impl WorkerPool {
                     status.demand.fetch_add(1, Ordering::Release);
                 }
                 Err(e) => {
+                    // NOTE: The permits must be released before the pool tries
+                    // to dispatch the waiting requests again.
+                    drop(permits);
+
+                    if worker_pool_msgs_tx
+                        .send(UserWorkerMsgs::CreateFailed(service_path, e.to_string()))
+                        .is_err()
+                    {
+                        error!("user worker msgs receiver dropped")
+                    }
                     if tx.send(Err(e)).is_err() {
                         error!("main worker receiver dropped")
                     } else {
@@ -467,11 +839,11 @@ impl WorkerPool {
         let registry = self
             .active_workers
             .entry(profile.service_path.clone())
-            .or_insert_with(|| ActiveWorkerRegistry::new(self.policy.max_parallelism));
+            .or_insert_with(|| ActiveWorkerRegistry::new(self.policy.clone()));
+
+        let is_per_worker = !registry.policy.supervisor_policy.is_request_scoped();
 
-        registry
-            .workers
-            .insert(WorkerId(key, self.policy.supervisor_policy.is_per_worker()));
+        registry.workers.insert(WorkerId(key, is_per_worker));
 
         self.user_workers.insert(key, profile);
         self.metric_src.incl_active_user_workers();
@@ -486,15 +858,30 @@ impl WorkerPool {
     ) {
         let _: Result<(), Error> = match self.user_workers.get(key) {
             Some(worker) => {
-                let policy = self.policy.supervisor_policy;
+                let policy = self
+                    .active_workers
+                    .get(&worker.service_path)
+                    .map(|it| it.policy.supervisor_policy)
+                    .unwrap_or(self.policy.supervisor_policy);
                 let profile = worker.clone();
                 let exit = worker.exit.clone();
                 let cancel = worker.cancel.clone();
                 let (req_start_tx, req_end_tx) = profile.timing_tx_pair.clone();
+                let req_timeout_tx = profile.req_timeout_tx.clone();
+                let request_wall_clock_limit = profile.request_wall_clock_limit;
+                let request_events = RequestEvents::new(
+                    self.worker_event_sender.clone(),
+                    &req,
+                    &profile.service_path,
+                    *key,
+                );
+                let maybe_cpu_profile_stop_tx = (profile.cpu_profile_sample_rate > 0.0
+                    && rand::random::<f64>() < profile.cpu_profile_sample_rate)
+                    .then(|| start_request_cpu_profile(&profile.diagnostic_tx));
 
                 // Create a closure to handle the request and send the response
                 let request_handler = async move {
-                    if !policy.is_per_worker() {
+                    if policy.is_request_scoped() {
                         if cancel.is_cancelled() {
                             bail!(exit
                                 .error()
@@ -532,19 +919,102 @@ impl WorkerPool {
                         }
                     }
 
-                    let result = send_user_worker_request(
+                    let usage_tracker = profile
+                        .usage
+                        .track(request_events.metadata.request_id.clone());
+
+                    request_events.start();
+
+                    let send_fut = send_user_worker_request(
                         profile.worker_request_msg_tx,
                         req,
                         cancel,
                         exit,
                         conn_token,
-                    )
-                    .await;
+                    );
+
+                    // NOTE: The wall clock limit covers the whole request, up
+                    // to the end of its response body.
+                    let maybe_deadline = request_wall_clock_limit.map(|it| Instant::now() + it);
+                    let result = match maybe_deadline {
+                        Some(deadline) => match tokio::time::timeout_at(deadline, send_fut).await {
+                            Ok(result) => result.map(|res| {
+                                res.map(|body| {
+                                    Body::wrap_stream(CancelAtDeadline::new(
+                                        body,
+                                        deadline,
+                                        anyhow!(WorkerError::RequestTimedOut),
+                                    ))
+                                })
+                            }),
+
+                            Err(_) => {
+                                // let the supervisor keep track of the violations
+                                let _ = req_timeout_tx.send(());
+                                Err(anyhow!(WorkerError::RequestTimedOut))
+                            }
+                        },
+
+                        None => send_fut.await,
+                    };
 
                     match result {
-                        Ok(req) => Ok((req, req_end_tx)),
+                        Ok(res) => {
+                            let status = res.status().as_u16();
+                            let (end_tx, mut end_rx) = mpsc::unbounded_channel::<u64>();
+                            let (usage_tx, usage_rx) = oneshot::channel();
+
+                            // NOTE: The request is accounted for until its
+                            // response body is done, and so is a sampled
+                            // request profiled.
+                            drop(tokio::spawn(async move {
+                                let maybe_bytes = match maybe_deadline {
+                                    Some(deadline) => {
+                                        match tokio::time::timeout_at(deadline, end_rx.recv()).await
+                                        {
+                                            Ok(maybe_bytes) => maybe_bytes,
+                                            Err(_) => {
+                                                let _ = req_timeout_tx.send(());
+                                                end_rx.recv().await
+                                            }
+                                        }
+                                    }
+
+                                    None => end_rx.recv().await,
+                                };
+
+                                if maybe_bytes.is_some() {
+                                    let _ = req_end_tx.send(());
+                                }
+
+                                if let Some(stop_tx) = maybe_cpu_profile_stop_tx {
+                                    let _ = stop_tx.send(());
+                                }
+
+                                let usage = usage_tracker.finish();
+
+                                request_events.end(
+                                    Some(status),
+                                    maybe_bytes.unwrap_or_default(),
+                                    &usage,
+                                    None,
+                                );
+
+                                let _ = usage_tx.send(usage);
+                            }));
+
+                            Ok((res, end_tx, usage_rx))
+                        }
                         Err(err) => {
                             let _ = req_end_tx.send(());
+
+                            request_events.end(
+                                None,
+                                0,
+                                &usage_tracker.finish(),
+                                Some(err.to_string()),
+                            );
+
                             error!("failed to send request to user worker: {}", err.to_string());
                             Err(err)
                         }
@@ -574,33 +1044,180 @@ impl WorkerPool {
         };
     }
 
+    pub fn take_heap_snapshot(&self, key: &Uuid, tx: Sender<Result<PathBuf, Error>>) {
+        self.send_diagnostic_msg(key, WorkerDiagnosticMsg::HeapSnapshot(tx));
+    }
+
+    pub fn take_cpu_profile(
+        &self,
+        key: &Uuid,
+        duration: Duration,
+        tx: Sender<Result<PathBuf, Error>>,
+    ) {
+        self.send_diagnostic_msg(
+            key,
+            WorkerDiagnosticMsg::CpuProfile(tokio::time::sleep(duration).boxed(), tx),
+        );
+    }
+
+    fn send_diagnostic_msg(&self, key: &Uuid, msg: WorkerDiagnosticMsg) {
+        let result = match self.user_workers.get(key) {
+            Some(profile) => profile
+                .diagnostic_tx
+                .send(msg)
+                .map_err(|err| (err.0, "user worker is not running")),
+
+            None => Err((msg, "user worker not available")),
+        };
+
+        if let Err((msg, reason)) = result {
+            let (WorkerDiagnosticMsg::HeapSnapshot(tx) | WorkerDiagnosticMsg::CpuProfile(_, tx)) =
+                msg;
+
+            if tx.send(Err(anyhow!(reason))).is_err() {
+                error!("main worker receiver dropped")
+            }
+        }
+    }
+
+    pub fn list_workers(&self) -> Vec<UserWorkerStats> {
+        self.user_workers
+            .iter()
+            .map(|(key, profile)| profile.stats(key))
+            .collect()
+    }
+
+    pub fn worker_stats(&self, key: &Uuid) -> Option<UserWorkerStats> {
+        self.user_workers.get(key).map(|it| it.stats(key))
+    }
+
+    /// Stops routing new requests to the worker. It keeps serving the
+    /// requests already sent to it until it exits on its own.
+    pub fn retire_worker(&mut self, key: &Uuid) -> bool {
+        let Some(profile) = self.user_workers.get(key) else {
+            return false;
+        };
+
+        profile.status.is_retired.raise();
+        self.retire(key);
+
+        true
+    }
+
+    /// Retires the worker and requests its termination. It is removed from
+    /// the pool once its supervisor has shut it down.
+    pub fn terminate_worker(&mut self, key: &Uuid) -> bool {
+        if !self.retire_worker(key) {
+            return false;
+        }
+
+        if let Some(profile) = self.user_workers.get(key) {
+            profile.termination.cancel();
+        }
+
+        true
+    }
+
     pub fn idle(&mut self, key: &Uuid) {
         if let Some(registry) = self
             .user_workers
             .get_mut(key)
             .and_then(|it| self.active_workers.get_mut(&it.service_path))
         {
-            registry.mark_idle(key, self.policy.supervisor_policy);
+            registry.mark_idle(key);
         }
     }
 
-    pub fn shutdown(&mut self, key: &Uuid) {
+    /// Releases an exited worker. Only clean exits count as successes for the
+    /// circuit breaker; workers killed by a resource limit or terminated on
+    /// request leave its state untouched.
+    pub fn shutdown(&mut self, key: &Uuid, is_clean_exit: bool) {
         self.retire(key);
 
-        let Some((notify_tx, _)) = self
-            .user_workers
-            .remove(key)
-            .and_then(|it| self.active_workers.get(&it.service_path))
-            .map(|it| it.notify_pair.clone())
-        else {
+        let Some(profile) = self.user_workers.remove(key) else {
             return;
         };
 
-        let _ = notify_tx.send(None);
+        if !self.crashed_workers.remove(key) && is_clean_exit {
+            self.record_success(profile.service_path);
+        }
 
         self.metric_src.decl_active_user_workers();
     }
 
+    /// Records a worker of the given service that has failed to boot.
+    pub fn boot_failed(&mut self, service_path: String, err: String) {
+        self.record_failure(service_path, err);
+    }
+
+    /// Records a worker that has exited with an uncaught exception.
+    pub fn crashed(&mut self, key: &Uuid, exception: String) {
+        let Some(service_path) = self.user_workers.get(key).map(|it| it.service_path.clone())
+        else {
+            return;
+        };
+
+        self.crashed_workers.insert(*key);
+        self.record_failure(service_path, exception);
+    }
+
+    fn record_failure(&mut self, service_path: String, err: String) {
+        let threshold = self.policy.circuit_breaker_threshold;
+
+        if threshold == 0 {
+            return;
+        }
+
+        let cooldown = Duration::from_millis(self.policy.circuit_breaker_cooldown_ms);
+        let breaker = self.breakers.entry(service_path.clone()).or_default();
+
+        if !breaker.record_failure(err, threshold, cooldown, Instant::now()) {
+            return;
+        }
+
+        error!(
+            "circuit breaker opened: {} ({} consecutive failures)",
+            service_path, breaker.failures
+        );
+
+        send_event_if_event_worker_available(
+            self.worker_event_sender.clone(),
+            WorkerEvents::CircuitBreakerOpen(CircuitBreakerEvent {
+                failures: breaker.failures,
+                msg: breaker.last_error.clone(),
+            }),
+            EventMetadata {
+                service_path: Some(service_path),
+                ..Default::default()
+            },
+        );
+    }
+
+    fn record_success(&mut self, service_path: String) {
+        let Some(breaker) = self.breakers.get_mut(&service_path) else {
+            return;
+        };
+
+        let failures = breaker.failures;
+
+        if !breaker.record_success() {
+            return;
+        }
+
+        debug!("circuit breaker closed: {}", service_path);
+        send_event_if_event_worker_available(
+            self.worker_event_sender.clone(),
+            WorkerEvents::CircuitBreakerClose(CircuitBreakerEvent {
+                failures,
+                msg: None,
+            }),
+            EventMetadata {
+                service_path: Some(service_path),
+                ..Default::default()
+            },
+        );
+    }
+
     fn retire(&mut self, key: &Uuid) {
         if let Some(profile) = self.user_workers.get_mut(key) {
             let registry = self
@@ -609,11 +1226,7 @@ impl WorkerPool {
                 .expect("registry must be initialized at this point");
 
             let _ = profile.permit.take();
-            let (notify_tx, _) = registry.notify_pair.clone();
-
-            for _ in 0..notify_tx.receiver_count() {
-                let _ = notify_tx.send(None);
-            }
+            let _ = profile.pool_permit.take();
 
             if registry.workers.contains(key) {
                 registry.workers.remove(key);
@@ -622,15 +1235,28 @@ impl WorkerPool {
         }
     }
 
+    /// Resolves the pool policy of the given service. Overrides are only
+    /// honoured when the service's registry is created; after that, the
+    /// registry keeps the policy it was created with.
+    fn service_policy(
+        &self,
+        service_path: &String,
+        overrides: Option<&ServicePoolPolicy>,
+    ) -> WorkerPoolPolicy {
+        match self.active_workers.get(service_path) {
+            Some(registry) => registry.policy.clone(),
+            None => self.policy.with_overrides(overrides),
+        }
+    }
+
     fn maybe_active_worker(&mut self, service_path: &String, force_create: bool) -> Option<Uuid> {
         if force_create {
             return None;
         }
 
         let registry = self.active_workers.get_mut(service_path)?;
-        let policy = self.policy.supervisor_policy;
 
-        let mut advance_fn = move || registry.mark_used_and_try_advance(policy).copied();
+        let mut advance_fn = move || registry.mark_used_and_try_advance().copied();
         let worker_uuid = advance_fn()?;
 
         match self
@@ -655,3 +1281,422 @@ impl WorkerPool {
         }
     }
 }
+
+/// Emits the lifecycle events of a request sent to a user worker.
+struct RequestEvents {
+    tx: Option<EventSender>,
+    metadata: EventMetadata,
+    method: String,
+    path: String,
+}
+
+impl RequestEvents {
+    fn new(tx: Option<EventSender>, req: &Request<Body>, service_path: &str, key: Uuid) -> Self {
+        // NOTE: An id given by the caller is kept so that the events can be
+        // correlated with its own logs.
+        let request_id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|it| it.to_str().ok())
+            .map(String::from)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        Self {
+            tx,
+            metadata: EventMetadata {
+                service_path: Some(service_path.to_string()),
+                execution_id: Some(key),
+                request_id: Some(request_id),
+            },
+            method: req.method().to_string(),
+            path: req.uri().path().to_string(),
+        }
+    }
+
+    fn start(&self) {
+        send_event_if_event_worker_available(
+            self.tx.clone(),
+            WorkerEvents::RequestStart(RequestStartEvent {
+                method: self.method.clone(),
+                path: self.path.clone(),
+            }),
+            self.metadata.clone(),
+        );
+    }
+
+    fn end(self, status: Option<u16>, bytes: u64, usage: &RequestUsage, msg: Option<String>) {
+        send_event_if_event_worker_available(
+            self.tx,
+            WorkerEvents::RequestEnd(RequestEndEvent {
+                method: self.method,
+                path: self.path,
+                status,
+                duration_ms: usage.wall_time_ms,
+                bytes,
+                cpu_time_ms: usage.cpu_time_ms,
+                peak_heap_delta_bytes: usage.peak_heap_delta_bytes,
+                msg,
+            }),
+            self.metadata,
+        );
+    }
+}
+
+/// Starts profiling a worker until the returned sender is used or dropped.
+fn start_request_cpu_profile(diagnostic_tx: &WorkerDiagnosticSender) -> oneshot::Sender<()> {
+    let (stop_tx, stop_rx) = oneshot::channel::<()>();
+    let (result_tx, _) = oneshot::channel();
+
+    // NOTE: Failures are already logged by the profiler.
+    let _ = diagnostic_tx.send(WorkerDiagnosticMsg::CpuProfile(
+        stop_rx.map(|_| ()).boxed(),
+        result_tx,
+    ));
+
+    stop_tx
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Error;
+    use event_worker::events::ShutdownReason;
+    use futures_util::future::BoxFuture;
+    use futures_util::FutureExt;
+    use sb_core::SharedMetricSource;
+    use sb_workers::context::{
+        CreateUserWorkerResult, ServicePoolPolicy, UserWorkerRuntimeOpts, WorkerContextInitOpts,
+        WorkerRuntimeOpts,
+    };
+    use sb_workers::errors::WorkerError;
+
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use tokio::sync::{mpsc, oneshot};
+    use tokio::time::Instant;
+
+    use super::{
+        CircuitBreaker, QueueFullBehavior, SupervisorPolicy, WorkerPool, WorkerPoolPolicy,
+    };
+    use crate::rt_worker::supervisor::{
+        get_supervisor, register_supervisor, Arguments, Supervisor,
+    };
+    use crate::server::ServerFlags;
+
+    fn base_policy() -> WorkerPoolPolicy {
+        WorkerPoolPolicy::new(
+            SupervisorPolicy::PerWorker,
+            8,
+            ServerFlags {
+                request_wait_timeout_ms: Some(1000),
+                request_queue_size: Some(100),
+                request_queue_full_behavior: Some(QueueFullBehavior::DropOldest),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_pool_policy_without_overrides() {
+        let policy = base_policy().with_overrides(None);
+
+        assert!(policy.supervisor_policy.is_per_worker());
+        assert_eq!(policy.max_parallelism, 8);
+        assert_eq!(policy.request_wait_timeout_ms, 1000);
+        assert_eq!(policy.max_queue_size, 100);
+        assert_eq!(policy.queue_weight, 1);
+    }
+
+    #[test]
+    fn test_pool_policy_partial_overrides() {
+        let policy = base_policy().with_overrides(Some(&ServicePoolPolicy {
+            max_parallelism: Some(32),
+            ..Default::default()
+        }));
+
+        assert!(policy.supervisor_policy.is_per_worker());
+        assert_eq!(policy.max_parallelism, 32);
+        assert_eq!(policy.request_wait_timeout_ms, 1000);
+    }
+
+    #[test]
+    fn test_pool_policy_oneshot_override_fixes_parallelism() {
+        let policy = base_policy().with_overrides(Some(&ServicePoolPolicy {
+            supervisor_policy: Some(SupervisorPolicy::oneshot()),
+            max_parallelism: Some(4),
+            request_wait_timeout_ms: Some(500),
+            ..Default::default()
+        }));
+
+        assert!(policy.supervisor_policy.is_oneshot());
+        assert_eq!(policy.max_parallelism, 1);
+        assert_eq!(policy.request_wait_timeout_ms, 500);
+    }
+
+    #[test]
+    fn test_pool_policy_queue_overrides() {
+        let policy = base_policy().with_overrides(Some(&ServicePoolPolicy {
+            max_queue_size: Some(16),
+            queue_weight: Some(4),
+            ..Default::default()
+        }));
+
+        assert_eq!(policy.max_queue_size, 16);
+        assert_eq!(policy.queue_weight, 4);
+        assert!(policy.queue_full_behavior.is_drop_oldest());
+    }
+
+    #[test]
+    fn test_pool_policy_custom_supervisor_override() {
+        struct NoopSupervisor;
+
+        impl Supervisor for NoopSupervisor {
+            fn supervise(&self, _args: Arguments) -> BoxFuture<'static, (ShutdownReason, i64)> {
+                async { (ShutdownReason::TerminationRequested, 0) }.boxed()
+            }
+        }
+
+        let custom = register_supervisor("noop", true, NoopSupervisor);
+        let policy = base_policy().with_overrides(Some(&ServicePoolPolicy {
+            supervisor_policy: Some(custom),
+            ..Default::default()
+        }));
+
+        assert!(policy.supervisor_policy.is_request_scoped());
+        assert!(!policy.supervisor_policy.is_oneshot());
+        assert!(get_supervisor(policy.supervisor_policy).is_some());
+        assert!(matches!(
+            "custom:noop".parse::<SupervisorPolicy>(),
+            Ok(SupervisorPolicy::Custom {
+                name: "noop",
+                per_request: true
+            })
+        ));
+        assert!("custom:unknown".parse::<SupervisorPolicy>().is_err());
+        assert!(get_supervisor(SupervisorPolicy::Custom {
+            name: "unknown",
+            per_request: false
+        })
+        .is_none());
+    }
+
+    #[test]
+    fn test_circuit_breaker_opens_after_threshold() {
+        let cooldown = Duration::from_secs(30);
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::default();
+
+        assert!(!breaker.record_failure("boom".into(), 3, cooldown, now));
+        assert!(!breaker.record_failure("boom".into(), 3, cooldown, now));
+        assert!(breaker.check(now).is_none());
+
+        assert!(breaker.record_failure("last boom".into(), 3, cooldown, now));
+        assert_eq!(breaker.check(now), Some("last boom"));
+
+        // half open: new workers are let through, but the next failure opens
+        // the breaker again
+        let later = now + cooldown;
+
+        assert!(breaker.check(later).is_none());
+        assert!(breaker.record_failure("boom".into(), 3, cooldown, later));
+
+        assert!(breaker.record_success());
+        assert!(breaker.check(later).is_none());
+        assert!(!breaker.record_success());
+    }
+
+    type CreateResultRx = oneshot::Receiver<Result<CreateUserWorkerResult, Error>>;
+
+    /// Returns a pool that can't spawn any worker until permits are added to
+    /// its pool semaphore, so every request is queued.
+    fn saturated_pool(max_queue_size: usize, queue_full_behavior: QueueFullBehavior) -> WorkerPool {
+        saturated_pool_with(ServerFlags {
+            request_queue_size: Some(max_queue_size),
+            request_queue_full_behavior: Some(queue_full_behavior),
+            ..Default::default()
+        })
+    }
+
+    fn saturated_pool_with(flags: ServerFlags) -> WorkerPool {
+        let (tx, _) = mpsc::unbounded_channel();
+        let policy = WorkerPoolPolicy::new(
+            SupervisorPolicy::PerWorker,
+            8,
+            ServerFlags {
+                max_total_parallelism: Some(0),
+                request_wait_timeout_ms: Some(1000),
+                ..flags
+            },
+        );
+
+        WorkerPool::new(policy, SharedMetricSource::default(), None, tx, None, None)
+    }
+
+    fn request(pool: &mut WorkerPool, service_path: &str, queue_weight: u32) -> CreateResultRx {
+        let (tx, rx) = oneshot::channel();
+        let worker_options = WorkerContextInitOpts {
+            service_path: PathBuf::from(service_path),
+            no_module_cache: false,
+            import_map_path: None,
+            env_vars: HashMap::new(),
+            events_rx: None,
+            timing: None,
+            conf: WorkerRuntimeOpts::UserWorker(UserWorkerRuntimeOpts::default()),
+            maybe_eszip: None,
+            maybe_module_code: None,
+            maybe_entrypoint: None,
+            maybe_decorator: None,
+            static_patterns: vec![],
+            maybe_jsx_import_source_config: None,
+            maybe_pool_policy: Some(ServicePoolPolicy {
+                queue_weight: Some(queue_weight),
+                ..Default::default()
+            }),
+        };
+
+        pool.create_user_worker(worker_options, tx, None);
+        rx
+    }
+
+    fn add_pool_permits(pool: &WorkerPool, permits: usize) {
+        pool.pool_sem.as_ref().unwrap().add_permits(permits);
+    }
+
+    fn rejection(rx: &mut CreateResultRx) -> Error {
+        match rx.try_recv() {
+            Ok(Err(err)) => err,
+            Ok(Ok(_)) => panic!("request was not rejected"),
+            Err(err) => panic!("request has no result: {}", err),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queued_requests_are_dispatched_by_weight() {
+        let mut pool = saturated_pool(100, QueueFullBehavior::Reject);
+        let _rxs = (0..4)
+            .flat_map(|_| [request(&mut pool, "a", 3), request(&mut pool, "b", 1)])
+            .collect::<Vec<_>>();
+
+        assert_eq!(pool.queue_depth("a"), 4);
+        assert_eq!(pool.queue_depth("b"), 4);
+        assert_eq!(pool.metric_src.queued_requests(), 8);
+
+        add_pool_permits(&pool, 4);
+        pool.dispatch_queued_requests();
+
+        assert_eq!(pool.queue_depth("a"), 1);
+        assert_eq!(pool.queue_depth("b"), 3);
+
+        add_pool_permits(&pool, 2);
+        pool.dispatch_queued_requests();
+
+        assert_eq!(pool.queue_depth("a"), 0);
+        assert_eq!(pool.queue_depth("b"), 2);
+        assert_eq!(pool.metric_src.queued_requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_busy_service_does_not_starve_others() {
+        let mut pool = saturated_pool(100, QueueFullBehavior::Reject);
+        let _busy = (0..10)
+            .map(|_| request(&mut pool, "busy", 1))
+            .collect::<Vec<_>>();
+
+        let _quiet = request(&mut pool, "quiet", 1);
+
+        add_pool_permits(&pool, 2);
+        pool.dispatch_queued_requests();
+
+        assert_eq!(pool.queue_depth("busy"), 9);
+        assert_eq!(pool.queue_depth("quiet"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_rejects_new_requests() {
+        let mut pool = saturated_pool(2, QueueFullBehavior::Reject);
+        let mut first = request(&mut pool, "a", 1);
+        let _second = request(&mut pool, "a", 1);
+        let mut third = request(&mut pool, "a", 1);
+
+        assert_eq!(pool.queue_depth("a"), 2);
+        assert!(first.try_recv().is_err());
+        assert_eq!(
+            rejection(&mut third).to_string(),
+            "request queue of the service is full"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_drops_oldest_request() {
+        let mut pool = saturated_pool(2, QueueFullBehavior::DropOldest);
+        let mut first = request(&mut pool, "a", 1);
+        let _second = request(&mut pool, "a", 1);
+        let mut third = request(&mut pool, "a", 1);
+
+        assert_eq!(pool.queue_depth("a"), 2);
+        assert_eq!(
+            rejection(&mut first).to_string(),
+            "request was evicted from the queue of the service"
+        );
+        assert!(third.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_open_breaker_rejects_queued_requests() {
+        let mut pool = saturated_pool_with(ServerFlags {
+            circuit_breaker_threshold: Some(1),
+            ..Default::default()
+        });
+        let mut rxs = (0..2)
+            .map(|_| request(&mut pool, "a", 1))
+            .collect::<Vec<_>>();
+
+        pool.boot_failed("a".to_string(), "boom".to_string());
+        add_pool_permits(&pool, 2);
+        pool.dispatch_queued_requests();
+
+        // no worker was spawned for the queued requests
+        assert_eq!(pool.queue_depth("a"), 0);
+        assert_eq!(pool.pool_sem.as_ref().unwrap().available_permits(), 2);
+        assert_eq!(pool.metric_src.queued_requests(), 0);
+
+        for rx in &mut rxs {
+            assert_eq!(rejection(rx).to_string(), "boom");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_queued_requests_expire_after_wait_timeout() {
+        let mut pool = saturated_pool(100, QueueFullBehavior::Reject);
+        let mut first = request(&mut pool, "a", 1);
+        let deadline = pool.next_queue_deadline().unwrap();
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+
+        let mut second = request(&mut pool, "a", 1);
+
+        pool.expire_queued_requests();
+        assert_eq!(pool.queue_depth("a"), 2);
+        assert_eq!(pool.next_queue_deadline(), Some(deadline));
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        pool.expire_queued_requests();
+
+        assert_eq!(pool.queue_depth("a"), 1);
+        assert!(matches!(
+            rejection(&mut first).downcast_ref::<WorkerError>(),
+            Some(WorkerError::RequestWaitTimedOut)
+        ));
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        pool.expire_queued_requests();
+
+        assert_eq!(pool.queue_depth("a"), 0);
+        assert!(pool.next_queue_deadline().is_none());
+        assert!(rejection(&mut second)
+            .downcast_ref::<WorkerError>()
+            .is_some());
+        assert_eq!(pool.metric_src.queued_requests(), 0);
+    }
+}
@@ -1,12 +1,14 @@
+use crate::event_sinks::{spawn_event_sinks, EventSinkOpts};
 use crate::inspector_server::Inspector;
+use crate::rt_worker::primary_worker::PrimaryWorkerRestartPolicy;
 use crate::rt_worker::worker_ctx::{
     create_events_worker, create_main_worker, create_user_worker_pool, TerminationToken,
 };
-use crate::rt_worker::worker_pool::WorkerPoolPolicy;
+use crate::rt_worker::worker_pool::{QueueFullBehavior, WorkerPoolPolicy};
 use crate::InspectorOption;
 use anyhow::{anyhow, bail, Context, Error};
 use deno_config::JsxImportSourceConfig;
-use event_worker::events::WorkerEventWithMetadata;
+use event_worker::channel::{event_channel, EventChannelOpts, EventSender};
 use futures_util::future::{poll_fn, BoxFuture};
 use futures_util::{FutureExt, Stream};
 use hyper::{server::conn::Http, service::Service, Body, Request, Response};
@@ -37,6 +39,8 @@ use tokio::time::{sleep, timeout};
 use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use tokio_rustls::rustls::ServerConfig;
 use tokio_rustls::TlsAcceptor;
+
+pub use event_worker::channel::EventDropPolicy;
 use tokio_util::sync::CancellationToken;
 use url::Url;
 
@@ -90,15 +94,17 @@ impl<S: Stream + Unpin> Stream for CancelOnDrop<S> {
 struct TerminationTokens {
     input: Option<TerminationToken>,
     event: Option<TerminationToken>,
+    sink: Option<TerminationToken>,
     pool: TerminationToken,
     main: TerminationToken,
 }
 
 impl TerminationTokens {
-    fn new(maybe_input: Option<TerminationToken>, with_event: bool) -> Self {
+    fn new(maybe_input: Option<TerminationToken>, with_event: bool, with_sink: bool) -> Self {
         Self {
             input: maybe_input,
             event: with_event.then(TerminationToken::new),
+            sink: with_sink.then(TerminationToken::new),
             pool: TerminationToken::new(),
             main: TerminationToken::new(),
         }
@@ -112,6 +118,12 @@ impl TerminationTokens {
         self.pool.cancel_and_wait().await;
         self.main.cancel_and_wait().await;
 
+        // NOTE: Event sinks are terminated last so that the events emitted
+        // while shutting down the other workers are written as well.
+        if let Some(token) = self.sink.as_ref() {
+            token.cancel_and_wait().await;
+        }
+
         if let Some(token) = self.input.as_ref() {
             assert!(token.inbound.is_cancelled());
 
@@ -249,6 +261,16 @@ pub struct ServerFlags {
     pub request_wait_timeout_ms: Option<u64>,
     pub request_idle_timeout_ms: Option<u64>,
     pub request_read_timeout_ms: Option<u64>,
+    pub request_queue_size: Option<usize>,
+    pub request_queue_full_behavior: Option<QueueFullBehavior>,
+    pub max_total_parallelism: Option<usize>,
+    pub circuit_breaker_threshold: Option<usize>,
+    pub circuit_breaker_cooldown_ms: Option<u64>,
+    pub primary_worker_restart_backoff_ms: Option<u64>,
+    pub primary_worker_restart_max_backoff_ms: Option<u64>,
+    pub event_channel_capacity: Option<usize>,
+    pub event_drop_policy: Option<EventDropPolicy>,
+    pub event_block_timeout_ms: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -350,29 +372,73 @@ impl Server {
         inspector: Option<Inspector>,
         jsx_specifier: Option<String>,
         jsx_module: Option<String>,
+        maybe_event_sink_opts: Option<EventSinkOpts>,
     ) -> Result<Self, Error> {
-        let mut worker_events_tx: Option<mpsc::UnboundedSender<WorkerEventWithMetadata>> = None;
         let maybe_events_entrypoint = entrypoints.events;
         let maybe_main_entrypoint = entrypoints.main;
-        let termination_tokens =
-            TerminationTokens::new(termination_token, maybe_events_service_path.is_some());
+        let maybe_event_sink_opts = maybe_event_sink_opts.filter(|it| !it.sinks.is_empty());
+        let termination_tokens = TerminationTokens::new(
+            termination_token,
+            maybe_events_service_path.is_some(),
+            maybe_event_sink_opts.is_some(),
+        );
+        let maybe_restart_policy = flags.primary_worker_restart_backoff_ms.and_then(|it| {
+            PrimaryWorkerRestartPolicy::new(
+                it,
+                flags.primary_worker_restart_max_backoff_ms.unwrap_or(30000),
+            )
+        });
+
+        let event_channel_opts = EventChannelOpts {
+            capacity: flags.event_channel_capacity,
+            drop_policy: flags.event_drop_policy.unwrap_or_default(),
+            block_timeout: Duration::from_millis(flags.event_block_timeout_ms.unwrap_or(100)),
+        };
+
+        let maybe_sink_channel = maybe_event_sink_opts
+            .is_some()
+            .then(|| event_channel(event_channel_opts));
+        let maybe_events_worker_channel = maybe_events_service_path
+            .is_some()
+            .then(|| event_channel(event_channel_opts));
+
+        // Every event is delivered to both the event sinks and the event
+        // worker, each through its own channel.
+        let worker_events_tx = {
+            let senders = maybe_sink_channel
+                .iter()
+                .chain(maybe_events_worker_channel.iter())
+                .map(|(tx, _)| tx.clone())
+                .collect::<Vec<_>>();
+
+            (!senders.is_empty()).then(|| EventSender::fanout(senders))
+        };
+
+        // Create Event Sinks
+        if let (Some(opts), Some((_, receiver))) = (maybe_event_sink_opts, maybe_sink_channel) {
+            spawn_event_sinks(opts, receiver, termination_tokens.sink.clone()).await?;
+        }
 
         // Create Event Worker
-        let event_worker_metric_src = if let Some(events_service_path) = maybe_events_service_path {
+        let event_worker_metric_src = if let (Some(events_service_path), Some((_, receiver))) =
+            (maybe_events_service_path, maybe_events_worker_channel)
+        {
             let events_path = Path::new(&events_service_path);
             let events_path_buf = events_path.to_path_buf();
 
-            let (ctx, sender) = create_events_worker(
+            let ctx = create_events_worker(
                 events_path_buf,
                 import_map_path.clone(),
                 flags.no_module_cache,
                 maybe_events_entrypoint,
                 maybe_decorator,
+                worker_events_tx.clone().unwrap(),
+                receiver,
                 Some(termination_tokens.event.clone().unwrap()),
+                maybe_restart_policy,
             )
             .await?;
 
-            worker_events_tx = Some(sender);
             Some(ctx.metric)
         } else {
             None
@@ -388,7 +454,7 @@ impl Server {
         // Create a user worker pool
         let (shared_metric_src, worker_pool_tx) = create_user_worker_pool(
             maybe_user_worker_policy.unwrap_or_default(),
-            worker_events_tx,
+            worker_events_tx.clone(),
             Some(termination_tokens.pool.clone()),
             static_patterns,
             inspector.clone(),
@@ -420,6 +486,8 @@ impl Server {
                 None
             },
             jsx_config,
+            maybe_restart_policy,
+            worker_events_tx,
         )
         .await?;
 
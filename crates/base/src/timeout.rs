@@ -327,6 +327,58 @@ impl<S> CancelOnWriteTimeout<S> {
     }
 }
 
+/// Ends a stream with an error once its deadline has passed.
+pub(crate) struct CancelAtDeadline<S> {
+    inner: S,
+    sleep: Pin<Box<Sleep>>,
+    err: Option<anyhow::Error>,
+}
+
+impl<S, T, E> futures_util::Stream for CancelAtDeadline<S>
+where
+    S: futures_util::Stream<Item = Result<T, E>> + Unpin,
+    E: Into<anyhow::Error>,
+{
+    type Item = Result<T, anyhow::Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let Some(err) = self.err.take() else {
+            return Poll::Ready(None);
+        };
+
+        if let Poll::Ready(()) = self.sleep.as_mut().poll(cx) {
+            return Poll::Ready(Some(Err(err)));
+        }
+
+        self.err = Some(err);
+
+        match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+            Some(v) => Poll::Ready(Some(v.map_err(Into::into))),
+            None => {
+                self.err = None;
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<S> CancelAtDeadline<S> {
+    pub(crate) fn new(inner: S, deadline: Instant, err: anyhow::Error) -> Self {
+        Self {
+            inner,
+            sleep: Box::pin(tokio::time::sleep_until(deadline)),
+            err: Some(err),
+        }
+    }
+}
+
 #[derive(EnumAsInner)]
 pub(crate) enum ReadTimeoutOp {
     UseTimeout {
@@ -0,0 +1,74 @@
+use anyhow::{Context, Error};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Returns the path of a new diagnostic file (such as a heap snapshot or a
+/// CPU profile) of the given worker.
+///
+/// Files are grouped in a directory per service and named after the time
+/// they were created, so the oldest ones can be removed to keep at most
+/// `max_count` of them with the same extension.
+pub fn new_output_path(
+    root: &Path,
+    service_path: &str,
+    key: Option<Uuid>,
+    ext: &str,
+    max_count: usize,
+) -> Result<PathBuf, Error> {
+    let dir = root.join(service_dir_name(service_path));
+
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("failed to create the directory: {}", dir.display()))?;
+
+    remove_old_files(&dir, ext, max_count);
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_millis())
+        .unwrap_or_default();
+
+    Ok(dir.join(format!("{}-{}.{}", timestamp, key.unwrap_or_default(), ext)))
+}
+
+fn service_dir_name(service_path: &str) -> String {
+    let name = service_path
+        .chars()
+        .map(|it| if it.is_ascii_alphanumeric() { it } else { '_' })
+        .collect::<String>();
+
+    match name.trim_matches('_') {
+        "" => "_".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// Makes room for a new file by removing the oldest ones.
+fn remove_old_files(dir: &Path, ext: &str, max_count: usize) {
+    if max_count == 0 {
+        return;
+    }
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut files = entries
+        .flatten()
+        .map(|it| it.path())
+        .filter(|it| it.extension().is_some_and(|it| it == ext))
+        .collect::<Vec<_>>();
+
+    if files.len() < max_count {
+        return;
+    }
+
+    let excess = files.len() + 1 - max_count;
+
+    files.sort();
+
+    for path in files.drain(..excess) {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -62,7 +62,7 @@ impl RequestScope {
     }
 
     pub async fn start_request(self) -> RequestScopeGuard {
-        if self.policy.is_per_request() {
+        if self.policy.is_request_scoped() {
             let fence = Arc::<Notify>::default();
 
             self.req_start_tx.send(fence.clone()).unwrap();
@@ -243,6 +243,7 @@ impl TestBedBuilder {
             }),
             static_patterns: vec![],
             maybe_jsx_import_source_config: None,
+            maybe_pool_policy: None,
         };
 
         let main_termination_token = TerminationToken::new();
@@ -0,0 +1,20 @@
+// Forwards the events each subscriber receives to the collector of the test.
+const COLLECTOR_URL = "http://127.0.0.1:8497";
+
+async function forward(subscriber: string, opts: { types?: string[], servicePaths?: string[] }) {
+  for await (const data of new globalThis.EventManager(opts)) {
+    await fetch(COLLECTOR_URL, {
+      method: "POST",
+      body: JSON.stringify({
+        subscriber,
+        event_type: data.event_type,
+        service_path: data.metadata.service_path,
+      }),
+    });
+  }
+}
+
+await Promise.all([
+  forward("logs", { types: ["Log"], servicePaths: ["./test_cases/log-events"] }),
+  forward("boots", { types: ["Boot"] }),
+]);
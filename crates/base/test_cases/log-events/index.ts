@@ -0,0 +1,5 @@
+Deno.serve(() => {
+  console.log("hello from log-events");
+  console.warn({ user: "alice", attempt: 1 });
+  return new Response("ok");
+});
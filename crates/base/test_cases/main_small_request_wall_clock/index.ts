@@ -0,0 +1,27 @@
+console.log('main function started');
+
+Deno.serve(async (req: Request) => {
+  const service_name = new URL(req.url).pathname.split("/")[1];
+
+  try {
+    const worker = await EdgeRuntime.userWorkers.create({
+      servicePath: `./test_cases/${service_name}`,
+      memoryLimitMb: 150,
+      workerTimeoutMs: 10 * 60 * 1000,
+      requestWallClockLimitMs: 500,
+      cpuTimeSoftLimitMs: 10 * 60 * 1000,
+      cpuTimeHardLimitMs: 10 * 60 * 1000,
+    });
+
+    // The body is read here, so that hitting the limit while it is streamed
+    // is reported the same way as while waiting for the headers.
+    const res = await worker.fetch(req);
+
+    return new Response(await res.arrayBuffer(), {
+      status: res.status,
+      headers: res.headers,
+    });
+  } catch (e) {
+    return Response.json({ msg: e.toString() }, { status: 500 });
+  }
+})
@@ -0,0 +1,30 @@
+console.log('main function started');
+
+Deno.serve(async (req: Request) => {
+  const url = new URL(req.url);
+  const { pathname } = url;
+  const service_name = pathname.split("/")[1];
+  const servicePath = `./test_cases/${service_name}`;
+
+  const dir = req.headers.get("x-cpu-profile-dir");
+  const sampleRate = Number(req.headers.get("x-cpu-profile-sample-rate") ?? 0);
+
+  const worker = await EdgeRuntime.userWorkers.create({
+    servicePath,
+    memoryLimitMb: 150,
+    workerTimeoutMs: 10 * 60 * 1000,
+    cpuTimeSoftLimitMs: 10 * 60 * 1000,
+    cpuTimeHardLimitMs: 10 * 60 * 1000,
+    forceCreate: true,
+    cpuProfile: { dir, sampleRate },
+  });
+
+  // Profiles taken on demand are only taken when requests aren't sampled.
+  const profiling = sampleRate > 0
+    ? Promise.resolve(null)
+    : EdgeRuntime.userWorkers.takeCpuProfile(worker.key, 200);
+
+  await (await worker.fetch(req)).text();
+
+  return Response.json({ path: await profiling });
+})
@@ -0,0 +1,29 @@
+console.log('main function started');
+
+Deno.serve(async (req: Request) => {
+  const url = new URL(req.url);
+  const { pathname } = url;
+  const service_name = pathname.split("/")[1];
+  const servicePath = `./test_cases/${service_name}`;
+
+  const envVarsObj = Deno.env.toObject();
+  const envVars = Object.keys(envVarsObj).map(k => [k, envVarsObj[k]]);
+
+  const worker = await EdgeRuntime.userWorkers.create({
+    servicePath,
+    memoryLimitMb: 150,
+    workerTimeoutMs: 10 * 60 * 1000,
+    cpuTimeSoftLimitMs: 10 * 60 * 1000,
+    cpuTimeHardLimitMs: 10 * 60 * 1000,
+    noModuleCache: false,
+    importMapPath: null,
+    envVars,
+  });
+
+  return await worker.fetch(req, {
+    context: {
+      tenantId: "acme",
+      claims: { sub: "alice", roles: ["admin"] },
+    },
+  });
+})
@@ -0,0 +1,44 @@
+console.log('main function started');
+
+Deno.serve(async (req: Request) => {
+  const url = new URL(req.url);
+  const { pathname } = url;
+  const service_name = pathname.split("/")[1];
+  const servicePath = `./test_cases/${service_name}`;
+
+  const envVarsObj = Deno.env.toObject();
+  const envVars = Object.keys(envVarsObj).map(k => [k, envVarsObj[k]]);
+  envVars.push(["CALLER_SECRET", "shh"]);
+
+  try {
+    const worker = await EdgeRuntime.userWorkers.create({
+      servicePath,
+      memoryLimitMb: 150,
+      workerTimeoutMs: 10 * 60 * 1000,
+      cpuTimeSoftLimitMs: 10 * 60 * 1000,
+      cpuTimeHardLimitMs: 10 * 60 * 1000,
+      noModuleCache: false,
+      importMapPath: null,
+      envVars,
+      bindings: {
+        greeter: {
+          servicePath: "./test_cases/service-binding-callee",
+          memoryLimitMb: 150,
+          cpuTimeSoftLimitMs: 10 * 60 * 1000,
+          cpuTimeHardLimitMs: 10 * 60 * 1000,
+          envVars: [["GREETING", "Hi"]],
+        },
+      },
+    });
+
+    return await worker.fetch(req);
+  } catch (e) {
+    console.error(e);
+
+    const error = { msg: e.toString() }
+    return new Response(
+      JSON.stringify(error),
+      { status: 500, headers: { "Content-Type": "application/json" } },
+    );
+  }
+})
@@ -0,0 +1,18 @@
+Deno.serve(async (req: Request) => {
+  const { pathname } = new URL(req.url);
+  const servicePath = `./test_cases/${pathname.split("/")[1]}`;
+
+  const worker = await EdgeRuntime.userWorkers.create({
+    servicePath,
+    memoryLimitMb: 150,
+    workerTimeoutMs: 10 * 60 * 1000,
+    cpuTimeSoftLimitMs: 10 * 60 * 1000,
+    cpuTimeHardLimitMs: 10 * 60 * 1000,
+  });
+
+  const res = await worker.fetch(req);
+  const body = await res.text();
+  const usage = await EdgeRuntime.userWorkers.getUsage(res);
+
+  return Response.json({ body, usage });
+});
@@ -0,0 +1,45 @@
+console.log('main function started');
+
+const BACKGROUND_BUDGET_MS = 1000;
+
+const sleep = (ms: number) => new Promise(r => setTimeout(r, ms));
+
+Deno.serve(async (req: Request) => {
+  const url = new URL(req.url);
+  const { pathname } = url;
+  const service_name = pathname.split("/")[1];
+  const servicePath = `./test_cases/${service_name}`;
+
+  const worker = await EdgeRuntime.userWorkers.create({
+    servicePath,
+    memoryLimitMb: 150,
+    workerTimeoutMs: 10 * 60 * 1000,
+    cpuTimeSoftLimitMs: 10 * 60 * 1000,
+    cpuTimeHardLimitMs: 10 * 60 * 1000,
+    backgroundBudgetMs: BACKGROUND_BUDGET_MS,
+    noModuleCache: false,
+    importMapPath: null,
+    envVars: [],
+  });
+
+  const body = await (await worker.fetch(req)).text();
+  const respondedAt = Date.now();
+
+  await sleep(BACKGROUND_BUDGET_MS / 4);
+
+  const aliveWhilePending = (await EdgeRuntime.userWorkers.getStats(worker.key)) !== null;
+
+  while (
+    (await EdgeRuntime.userWorkers.getStats(worker.key)) !== null &&
+    Date.now() - respondedAt < 10 * 1000
+  ) {
+    await sleep(50);
+  }
+
+  return Response.json({
+    body,
+    aliveWhilePending,
+    terminated: (await EdgeRuntime.userWorkers.getStats(worker.key)) === null,
+    terminatedAfterMs: Date.now() - respondedAt,
+  });
+})
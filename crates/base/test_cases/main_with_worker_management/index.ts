@@ -0,0 +1,40 @@
+console.log('main function started');
+
+Deno.serve(async (req: Request) => {
+  const url = new URL(req.url);
+  const { pathname } = url;
+  const service_name = pathname.split("/")[1];
+  const servicePath = `./test_cases/${service_name}`;
+
+  const envVarsObj = Deno.env.toObject();
+  const envVars = Object.keys(envVarsObj).map(k => [k, envVarsObj[k]]);
+
+  const worker = await EdgeRuntime.userWorkers.create({
+    servicePath,
+    memoryLimitMb: 150,
+    workerTimeoutMs: 10 * 60 * 1000,
+    cpuTimeSoftLimitMs: 10 * 60 * 1000,
+    cpuTimeHardLimitMs: 10 * 60 * 1000,
+    noModuleCache: false,
+    importMapPath: null,
+    envVars,
+  });
+
+  await (await worker.fetch(req)).text();
+
+  const listed = await EdgeRuntime.userWorkers.list();
+  const stats = await EdgeRuntime.userWorkers.getStats(worker.key);
+  const retired = await EdgeRuntime.userWorkers.retire(worker.key);
+  const afterRetire = await EdgeRuntime.userWorkers.getStats(worker.key);
+  const terminated = await EdgeRuntime.userWorkers.terminate(worker.key);
+  const unknown = await EdgeRuntime.userWorkers.terminate(crypto.randomUUID());
+
+  return Response.json({
+    listed: listed.some(it => it.key === worker.key && it.servicePath === servicePath),
+    stats,
+    retired,
+    isRetired: afterRetire?.isRetired ?? false,
+    terminated,
+    unknown,
+  });
+})
@@ -0,0 +1,16 @@
+Deno.serve((req: Request) => {
+  const context = EdgeRuntime.getRequestContext(req);
+  let mutated = true;
+
+  try {
+    context.claims.sub = "mallory";
+  } catch {
+    mutated = false;
+  }
+
+  return Response.json({
+    context,
+    mutated: mutated && context.claims.sub === "mallory",
+    headers: Array.from(req.headers.keys()),
+  });
+});
@@ -0,0 +1,8 @@
+Deno.serve(async (req: Request) => {
+  const { name } = await req.json();
+
+  return Response.json({
+    message: `${Deno.env.get("GREETING")} ${name}!`,
+    callerSecret: Deno.env.get("CALLER_SECRET") ?? null,
+  });
+});
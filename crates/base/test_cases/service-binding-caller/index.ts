@@ -0,0 +1,13 @@
+Deno.serve(async () => {
+  const res = await EdgeRuntime.bindings.greeter.fetch("http://localhost/greeter", {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ name: "bar" }),
+  });
+
+  return Response.json({
+    bindings: Object.keys(EdgeRuntime.bindings),
+    status: res.status,
+    body: await res.json(),
+  });
+});
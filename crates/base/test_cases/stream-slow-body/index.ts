@@ -0,0 +1,25 @@
+Deno.serve(() => {
+  const encoder = new TextEncoder();
+  let timer: number;
+
+  const body = new ReadableStream({
+    start(controller) {
+      let count = 0;
+
+      timer = setInterval(() => {
+        controller.enqueue(encoder.encode(`chunk ${count}\n`));
+
+        if (++count === 20) {
+          clearInterval(timer);
+          controller.close();
+        }
+      }, 100);
+    },
+
+    cancel() {
+      clearInterval(timer);
+    },
+  });
+
+  return new Response(body);
+});
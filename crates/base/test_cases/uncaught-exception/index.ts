@@ -0,0 +1,7 @@
+type Payload = { reason: string };
+
+function fail(payload: Payload): never {
+  throw new TypeError(payload.reason);
+}
+
+setTimeout(() => fail({ reason: "uncaught from a timer" }));
@@ -0,0 +1,6 @@
+Deno.serve(() => {
+  // Outlives the background budget given by the main worker.
+  EdgeRuntime.waitUntil(new Promise(r => setTimeout(r, 60 * 1000)));
+
+  return new Response("ok");
+});
@@ -14,12 +14,14 @@ use std::{
 use anyhow::Context;
 use async_tungstenite::WebSocketStream;
 use base::{
+    event_sinks::EventSinkOpts,
     integration_test, integration_test_listen_fut, integration_test_with_server_flag,
     rt_worker::worker_ctx::{create_user_worker_pool, create_worker, TerminationToken},
     server::{ServerEvent, ServerFlags, ServerHealth, Tls},
     DecoratorType,
 };
 use deno_core::serde_json;
+use event_worker::events::BootFailureKind;
 use futures_util::{future::BoxFuture, Future, FutureExt, SinkExt, StreamExt};
 use http::{Method, Request, Response as HttpResponse, StatusCode};
 use http_utils::utils::get_upgrade_type;
@@ -34,6 +36,7 @@ use sb_core::SharedMetricSource;
 use sb_workers::context::{
     MainWorkerRuntimeOpts, WorkerContextInitOpts, WorkerRequestMsg, WorkerRuntimeOpts,
 };
+use sb_workers::errors::WorkerError;
 use serde::Deserialize;
 use serial_test::serial;
 use tokio::{
@@ -204,6 +207,7 @@ async fn test_not_trigger_pku_sigsegv_due_to_jit_compilation_non_cli() {
         }),
         static_patterns: vec![],
         maybe_jsx_import_source_config: None,
+        maybe_pool_policy: None,
     };
 
     let ctx = create_worker((opts, main_termination_token.clone()), None, None)
@@ -363,6 +367,7 @@ async fn test_main_worker_boot_error() {
         }),
         static_patterns: vec![],
         maybe_jsx_import_source_config: None,
+        maybe_pool_policy: None,
     };
 
     let result = create_worker((opts, main_termination_token.clone()), None, None).await;
@@ -489,6 +494,7 @@ async fn test_main_worker_user_worker_mod_evaluate_exception() {
         }),
         static_patterns: vec![],
         maybe_jsx_import_source_config: None,
+        maybe_pool_policy: None,
     };
 
     let ctx = create_worker((opts, main_termination_token.clone()), None, None)
@@ -868,15 +874,23 @@ async fn test_worker_boot_invalid_imports() {
         conf: WorkerRuntimeOpts::UserWorker(test_user_runtime_opts()),
         static_patterns: vec![],
         maybe_jsx_import_source_config: None,
+        maybe_pool_policy: None,
     };
 
     let result = create_test_user_worker(opts).await;
 
     assert!(result.is_err());
-    assert!(result
-        .unwrap_err()
-        .to_string()
-        .starts_with("worker boot error"));
+
+    let err = result.unwrap_err();
+
+    assert!(err.to_string().starts_with("worker boot error"));
+
+    let Some(WorkerError::BootFailed(failure)) = err.downcast_ref::<WorkerError>() else {
+        panic!("expected a boot failure: {}", err);
+    };
+
+    assert_eq!(failure.kind, BootFailureKind::Transpile);
+    assert_eq!(failure.line, Some(3));
 }
 
 #[tokio::test]
@@ -1007,6 +1021,325 @@ async fn req_failure_case_wall_clock_reached() {
     tb.exit(Duration::from_secs(TESTBED_DEADLINE_SEC)).await;
 }
 
+#[tokio::test]
+#[serial]
+async fn req_failure_case_request_wall_clock_reached() {
+    let tb = TestBedBuilder::new("./test_cases/main_small_request_wall_clock")
+        .with_per_worker_policy(100000)
+        .build()
+        .await;
+
+    let mut res = tb
+        .request(|| {
+            Request::builder()
+                .uri("/sleep-5000ms")
+                .method("GET")
+                .body(Body::empty())
+                .context("can't make request")
+        })
+        .await
+        .unwrap();
+
+    let buf = to_bytes(res.body_mut()).await.unwrap();
+
+    assert_eq!(
+        buf,
+        "{\"msg\":\"WorkerRequestTimedOut: request has exceeded its wall clock limit\"}"
+    );
+
+    tb.exit(Duration::from_secs(TESTBED_DEADLINE_SEC)).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn req_failure_case_request_wall_clock_reached_while_streaming_body() {
+    let tb = TestBedBuilder::new("./test_cases/main_small_request_wall_clock")
+        .with_per_worker_policy(100000)
+        .build()
+        .await;
+
+    let mut res = tb
+        .request(|| {
+            Request::builder()
+                .uri("/stream-slow-body")
+                .method("GET")
+                .body(Body::empty())
+                .context("can't make request")
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(res.status().as_u16(), 500);
+
+    let buf = to_bytes(res.body_mut()).await.unwrap();
+    let body = serde_json::from_slice::<serde_json::Value>(&buf).unwrap();
+
+    assert!(body["msg"]
+        .as_str()
+        .is_some_and(|it| it.contains("request has exceeded its wall clock limit")));
+
+    tb.exit(Duration::from_secs(TESTBED_DEADLINE_SEC)).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_main_worker_manages_user_workers() {
+    let tb = TestBedBuilder::new("./test_cases/main_with_worker_management")
+        .with_per_worker_policy(100000)
+        .build()
+        .await;
+
+    let mut res = tb
+        .request(|| {
+            Request::builder()
+                .uri("/std_user_worker")
+                .method("OPTIONS")
+                .body(Body::empty())
+                .context("can't make request")
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(res.status().as_u16(), 200);
+
+    let buf = to_bytes(res.body_mut()).await.unwrap();
+    let body = serde_json::from_slice::<serde_json::Value>(&buf).unwrap();
+
+    assert_eq!(body["listed"], true);
+    assert_eq!(body["stats"]["servicePath"], "./test_cases/std_user_worker");
+    assert_eq!(body["stats"]["isRetired"], false);
+    assert_eq!(body["retired"], true);
+    assert_eq!(body["isRetired"], true);
+    assert_eq!(body["terminated"], true);
+    assert_eq!(body["unknown"], false);
+
+    tb.exit(Duration::from_secs(TESTBED_DEADLINE_SEC)).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_main_worker_reads_request_usage() {
+    let tb = TestBedBuilder::new("./test_cases/main_with_usage")
+        .with_per_worker_policy(100000)
+        .build()
+        .await;
+
+    let mut res = tb
+        .request(|| {
+            Request::builder()
+                .uri("/std_user_worker")
+                .method("POST")
+                .body(Body::from(r#"{"name":"bar"}"#))
+                .context("can't make request")
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(res.status().as_u16(), 200);
+
+    let buf = to_bytes(res.body_mut()).await.unwrap();
+    let body = serde_json::from_slice::<serde_json::Value>(&buf).unwrap();
+    let usage = &body["usage"];
+
+    assert!(body["body"]
+        .as_str()
+        .is_some_and(|it| it.contains("Hello bar from foo!")));
+    assert!(usage["cpuTimeMs"].as_f64().is_some_and(|it| it >= 0.0));
+    assert!(usage["wallTimeMs"].as_f64().is_some_and(|it| it > 0.0));
+    assert!(usage["peakHeapDeltaBytes"].is_u64());
+
+    tb.exit(Duration::from_secs(TESTBED_DEADLINE_SEC)).await;
+}
+
+async fn test_cpu_profile_written(sample_rate: f64) {
+    let dir = std::env::temp_dir().join(format!("cpu-profile-{}", uuid::Uuid::new_v4()));
+    let tb = TestBedBuilder::new("./test_cases/main_with_cpu_profile")
+        .with_per_worker_policy(100000)
+        .build()
+        .await;
+
+    let mut res = tb
+        .request(|| {
+            Request::builder()
+                .uri("/std_user_worker")
+                .method("OPTIONS")
+                .header("x-cpu-profile-dir", dir.to_string_lossy().as_ref())
+                .header("x-cpu-profile-sample-rate", sample_rate.to_string())
+                .body(Body::empty())
+                .context("can't make request")
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(res.status().as_u16(), 200);
+
+    let buf = to_bytes(res.body_mut()).await.unwrap();
+    let body = serde_json::from_slice::<serde_json::Value>(&buf).unwrap();
+
+    // NOTE: Sampled profiles are written once the response is done, so they
+    // may still be on their way.
+    let (path, profile) = timeout(Duration::from_secs(10), async {
+        loop {
+            let maybe_profile = std::fs::read_dir(&dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .flat_map(|it| std::fs::read_dir(it.path()).into_iter().flatten().flatten())
+                .map(|it| it.path())
+                .find_map(|path| {
+                    let profile =
+                        serde_json::from_slice::<serde_json::Value>(&std::fs::read(&path).ok()?)
+                            .ok()?;
+
+                    Some((path, profile))
+                });
+
+            if let Some(found) = maybe_profile {
+                break found;
+            }
+
+            sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("cpu profile was not written within 10 seconds");
+
+    if sample_rate == 0.0 {
+        assert_eq!(body["path"], path.to_string_lossy().as_ref());
+    } else {
+        assert_eq!(body["path"], serde_json::Value::Null);
+    }
+
+    assert_eq!(path.extension().unwrap(), "cpuprofile");
+    assert!(profile["nodes"].as_array().is_some_and(|it| !it.is_empty()));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    tb.exit(Duration::from_secs(TESTBED_DEADLINE_SEC)).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_cpu_profile_taken_on_demand() {
+    test_cpu_profile_written(0.0).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_cpu_profile_of_sampled_request() {
+    test_cpu_profile_written(1.0).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_user_worker_calls_bound_service() {
+    let tb = TestBedBuilder::new("./test_cases/main_with_service_bindings")
+        .with_per_worker_policy(100000)
+        .build()
+        .await;
+
+    let mut res = tb
+        .request(|| {
+            Request::builder()
+                .uri("/service-binding-caller")
+                .method("GET")
+                .body(Body::empty())
+                .context("can't make request")
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(res.status().as_u16(), 200);
+
+    let buf = to_bytes(res.body_mut()).await.unwrap();
+    let body = serde_json::from_slice::<serde_json::Value>(&buf).unwrap();
+
+    assert_eq!(body["bindings"], serde_json::json!(["greeter"]));
+    assert_eq!(body["status"], 200);
+    assert_eq!(body["body"]["message"], "Hi bar!");
+    assert_eq!(body["body"]["callerSecret"], serde_json::Value::Null);
+
+    tb.exit(Duration::from_secs(TESTBED_DEADLINE_SEC)).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_request_context_is_passed_to_user_worker() {
+    let tb = TestBedBuilder::new("./test_cases/main_with_request_context")
+        .with_per_worker_policy(100000)
+        .build()
+        .await;
+
+    let mut res = tb
+        .request(|| {
+            Request::builder()
+                .uri("/request-context")
+                .method("GET")
+                .body(Body::empty())
+                .context("can't make request")
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(res.status().as_u16(), 200);
+
+    let buf = to_bytes(res.body_mut()).await.unwrap();
+    let body = serde_json::from_slice::<serde_json::Value>(&buf).unwrap();
+
+    assert_eq!(
+        body["context"],
+        serde_json::json!({
+            "tenantId": "acme",
+            "claims": { "sub": "alice", "roles": ["admin"] },
+        })
+    );
+
+    assert_eq!(body["mutated"], false);
+    assert!(!body["headers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|it| it.as_str().unwrap().contains("context")));
+
+    tb.exit(Duration::from_secs(TESTBED_DEADLINE_SEC)).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_oneshot_worker_waits_until_background_budget() {
+    let tb = TestBedBuilder::new("./test_cases/main_with_wait_until")
+        .with_oneshot_policy(100000)
+        .build()
+        .await;
+
+    let mut res = tb
+        .request(|| {
+            Request::builder()
+                .uri("/wait-until")
+                .method("GET")
+                .body(Body::empty())
+                .context("can't make request")
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(res.status().as_u16(), 200);
+
+    let buf = to_bytes(res.body_mut()).await.unwrap();
+    let body = serde_json::from_slice::<serde_json::Value>(&buf).unwrap();
+
+    assert_eq!(body["body"], "ok");
+
+    // The worker outlives its response while the `waitUntil` promise is
+    // pending, but no longer than the background budget (1000ms) allows.
+    assert_eq!(body["aliveWhilePending"], true);
+    assert_eq!(body["terminated"], true);
+    assert!(body["terminatedAfterMs"]
+        .as_u64()
+        .is_some_and(|it| (900..5000).contains(&it)));
+
+    tb.exit(Duration::from_secs(TESTBED_DEADLINE_SEC)).await;
+}
+
 #[tokio::test]
 #[serial]
 async fn req_failture_case_memory_limit_1() {
@@ -2099,6 +2432,260 @@ async fn test_should_not_hang_when_forced_redirection_for_specifiers() {
     }
 }
 
+#[tokio::test]
+#[serial]
+async fn test_event_sink_http_batch_post() {
+    let token = TerminationToken::new();
+    let (batch_tx, mut batch_rx) = mpsc::unbounded_channel::<Vec<serde_json::Value>>();
+    let collector_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8499);
+
+    tokio::spawn(
+        hyper::Server::bind(&collector_addr).serve(hyper::service::make_service_fn(move |_| {
+            let batch_tx = batch_tx.clone();
+
+            async move {
+                Ok::<_, hyper::Error>(hyper::service::service_fn(move |req: Request<Body>| {
+                    let batch_tx = batch_tx.clone();
+
+                    async move {
+                        let body = to_bytes(req.into_body()).await?;
+
+                        batch_tx
+                            .send(serde_json::from_slice(&body).unwrap())
+                            .unwrap();
+
+                        Ok::<_, hyper::Error>(HttpResponse::new(Body::empty()))
+                    }
+                }))
+            }
+        })),
+    );
+
+    let (health_tx, mut health_rx) = mpsc::channel(1);
+    let server_fut = base::commands::start_server(
+        "0.0.0.0",
+        NON_SECURE_PORT,
+        None,
+        String::from("./test_cases/main"),
+        None,
+        None,
+        None,
+        None,
+        ServerFlags::default(),
+        Some(health_tx),
+        base::server::WorkerEntrypoints {
+            main: None,
+            events: None,
+        },
+        Some(token.clone()),
+        vec![],
+        None,
+        None,
+        None,
+        Some(EventSinkOpts {
+            sinks: vec![format!("http://{}", collector_addr).parse().unwrap()],
+            event_types: Some(
+                ["Log", "RequestStart", "RequestEnd"]
+                    .map(String::from)
+                    .into(),
+            ),
+            flush_interval: Duration::from_millis(100),
+            ..Default::default()
+        }),
+    );
+
+    tokio::spawn(server_fut);
+
+    while !matches!(health_rx.recv().await, Some(ServerHealth::Listening(..))) {}
+
+    let resp = reqwest::Client::new()
+        .get(format!("http://localhost:{}/log-events", NON_SECURE_PORT))
+        .header("x-request-id", "log-events-request")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(resp.text().await.unwrap(), "ok");
+
+    let found = timeout(Duration::from_secs(10), async {
+        let mut found_msg = false;
+        let mut found_fields = false;
+        let mut found_start = false;
+        let mut found_end = false;
+
+        while let Some(batch) = batch_rx.recv().await {
+            for record in batch {
+                match record["event_type"].as_str() {
+                    Some("Log") => {}
+
+                    Some("RequestStart") => {
+                        assert_eq!(record["metadata"]["request_id"], "log-events-request");
+                        assert_eq!(record["event"]["method"], "GET");
+                        assert_eq!(record["event"]["path"], "/log-events");
+                        found_start = true;
+                        continue;
+                    }
+
+                    Some("RequestEnd") => {
+                        assert_eq!(record["metadata"]["request_id"], "log-events-request");
+                        assert_eq!(
+                            record["metadata"]["service_path"],
+                            "./test_cases/log-events"
+                        );
+                        assert_eq!(record["event"]["status"], 200);
+                        assert_eq!(record["event"]["bytes"], 2);
+                        found_end = true;
+                        continue;
+                    }
+
+                    _ => panic!("unexpected record: {}", record),
+                }
+
+                if record["event"]["msg"]
+                    .as_str()
+                    .is_some_and(|it| it.contains("hello from log-events"))
+                {
+                    assert_eq!(
+                        record["metadata"]["service_path"],
+                        "./test_cases/log-events"
+                    );
+                    assert_eq!(record["event"]["level"], "Info");
+                    assert!(record["event"]["source"]["file"]
+                        .as_str()
+                        .is_some_and(|it| it.ends_with("index.ts")));
+                    assert_eq!(record["event"]["source"]["line"], 2);
+                    found_msg = true;
+                }
+
+                if record["event"]["fields"]["user"] == "alice" {
+                    assert_eq!(record["event"]["level"], "Warning");
+                    assert_eq!(record["event"]["fields"]["attempt"], 1);
+                    found_fields = true;
+                }
+            }
+
+            if found_msg && found_fields && found_start && found_end {
+                break;
+            }
+        }
+
+        found_msg && found_fields && found_start && found_end
+    })
+    .await;
+
+    assert_eq!(found.ok(), Some(true));
+
+    if timeout(Duration::from_secs(10), token.cancel_and_wait())
+        .await
+        .is_err()
+    {
+        panic!("failed to terminate server within 10 seconds");
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_event_manager_subscribers_with_different_filters() {
+    let token = TerminationToken::new();
+    let (record_tx, mut record_rx) = mpsc::unbounded_channel::<serde_json::Value>();
+    let collector_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8497);
+
+    tokio::spawn(
+        hyper::Server::bind(&collector_addr).serve(hyper::service::make_service_fn(move |_| {
+            let record_tx = record_tx.clone();
+
+            async move {
+                Ok::<_, hyper::Error>(hyper::service::service_fn(move |req: Request<Body>| {
+                    let record_tx = record_tx.clone();
+
+                    async move {
+                        let body = to_bytes(req.into_body()).await?;
+
+                        record_tx
+                            .send(serde_json::from_slice(&body).unwrap())
+                            .unwrap();
+
+                        Ok::<_, hyper::Error>(HttpResponse::new(Body::empty()))
+                    }
+                }))
+            }
+        })),
+    );
+
+    let (health_tx, mut health_rx) = mpsc::channel(1);
+    let server_fut = base::commands::start_server(
+        "0.0.0.0",
+        NON_SECURE_PORT,
+        None,
+        String::from("./test_cases/main"),
+        Some(String::from("./test_cases/event-manager-filters")),
+        None,
+        None,
+        None,
+        ServerFlags::default(),
+        Some(health_tx),
+        base::server::WorkerEntrypoints {
+            main: None,
+            events: None,
+        },
+        Some(token.clone()),
+        vec![],
+        None,
+        None,
+        None,
+        None,
+    );
+
+    tokio::spawn(server_fut);
+
+    while !matches!(health_rx.recv().await, Some(ServerHealth::Listening(..))) {}
+
+    let resp = reqwest::get(format!("http://localhost:{}/log-events", NON_SECURE_PORT))
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status().as_u16(), 200);
+
+    let found = timeout(Duration::from_secs(10), async {
+        let mut found_log = false;
+        let mut found_boot = false;
+
+        while let Some(record) = record_rx.recv().await {
+            match record["subscriber"].as_str() {
+                Some("logs") => {
+                    assert_eq!(record["event_type"], "Log");
+                    assert_eq!(record["service_path"], "./test_cases/log-events");
+                    found_log = true;
+                }
+
+                Some("boots") => {
+                    assert_eq!(record["event_type"], "Boot");
+                    found_boot = true;
+                }
+
+                _ => panic!("unexpected record: {}", record),
+            }
+
+            if found_log && found_boot {
+                break;
+            }
+        }
+
+        found_log && found_boot
+    })
+    .await;
+
+    assert_eq!(found.ok(), Some(true));
+
+    if timeout(Duration::from_secs(10), token.cancel_and_wait())
+        .await
+        .is_err()
+    {
+        panic!("failed to terminate server within 10 seconds");
+    }
+}
+
 trait AsyncReadWrite: AsyncRead + AsyncWrite + Send + Unpin {}
 
 impl<T> AsyncReadWrite for T where T: AsyncRead + AsyncWrite + Send + Unpin {}
@@ -37,4 +37,5 @@ impl From<&'_ v8::HeapStatistics> for WorkerHeapStatistics {
 pub struct MemCheckState {
     pub current: WorkerHeapStatistics,
     pub exceeded: bool,
+    pub soft_limit_exceeded: bool,
 }
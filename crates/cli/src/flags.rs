@@ -83,6 +83,11 @@ fn get_start_command() -> Command {
                 .default_value("false")
                 .value_parser(FalseyValueParser::new()),
         )
+        .arg(
+            arg!(--"eszip-cache-dir" <DIR>)
+                .help("Directory to persist the eszips built for user workers (kept in memory only by default)")
+                .value_parser(value_parser!(PathBuf)),
+        )
         .arg(arg!(--"import-map" <Path>).help("Path to import map file"))
         .arg(arg!(--"event-worker" <Path>).help("Path to event worker directory"))
         .arg(arg!(--"main-entrypoint" <Path>).help("Path to entrypoint in main service (only for eszips)"))
@@ -135,6 +140,107 @@ fn get_start_command() -> Command {
                     value_parser!(u32).range(1..9999).map(|it| -> usize { it as usize }),
                 ),
         )
+        .arg(
+            arg!(--"max-total-parallelism" <COUNT>)
+                .help("Maximum count of workers that can exist in the worker pool simultaneously across all services (unlimited by default)")
+                .value_parser(
+                    value_parser!(u32).range(1..99999).map(|it| -> usize { it as usize }),
+                ),
+        )
+        .arg(
+            arg!(--"request-queue-size" <COUNT>)
+                .help("Maximum count of requests that can wait for a worker of a single service")
+                .default_value("1000")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(--"request-queue-full-behavior" <BEHAVIOR>)
+                .help("What to do with a new request when the queue of the service is full")
+                .default_value("reject")
+                .value_parser(["reject", "drop_oldest"]),
+        )
+        .arg(
+            arg!(--"circuit-breaker-threshold" <COUNT>)
+                .help("Count of consecutive boot failures or uncaught exceptions after which new workers of a service are refused for a while (disabled by default)")
+                .default_value("0")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(--"circuit-breaker-cooldown" <MILLISECONDS>)
+                .help("Time in milliseconds during which new workers of a crash looping service are refused")
+                .default_value("30000")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(--"primary-worker-restart-backoff" <MILLISECONDS>)
+                .help("Initial delay in milliseconds before a crashed main or event worker is restarted, doubled on each consecutive failure (0 disables restarts)")
+                .default_value("100")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(--"primary-worker-restart-max-backoff" <MILLISECONDS>)
+                .help("Maximum delay in milliseconds before a crashed main or event worker is restarted")
+                .default_value("30000")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(--"event-channel-capacity" <COUNT>)
+                .help("Maximum number of events waiting to be accepted by the event worker")
+                .default_value("10000")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(--"event-drop-policy" <POLICY>)
+                .help("What to do with a new event when the event channel is full")
+                .default_value("drop_oldest")
+                .value_parser(["drop_oldest", "drop_newest", "block"]),
+        )
+        .arg(
+            arg!(--"event-block-timeout" <MILLISECONDS>)
+                .help("Maximum time in milliseconds a worker is blocked on a full event channel when the drop policy is `block`")
+                .default_value("100")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(--"event-sink" <SINK>)
+                .help("Writes events to `stdout`, `file:<path>`, `syslog`, `syslog:<addr>` or an `http(s)://` URL. Can be repeated, and used alongside or instead of an event worker")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--"event-sink-types" <TYPES>)
+                .help("Comma separated list of the event types written to the event sinks")
+                .value_delimiter(','),
+        )
+        .arg(
+            arg!(--"event-sink-batch-size" <COUNT>)
+                .help("Maximum number of events written to the event sinks at once")
+                .default_value("100")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(--"event-sink-flush-interval" <MILLISECONDS>)
+                .help("Interval in milliseconds at which pending events are written to the event sinks")
+                .default_value("1000")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(--"event-sink-max-retries" <COUNT>)
+                .help("How many times writing events to an event sink is retried before they are discarded")
+                .default_value("3")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(--"event-sink-file-max-size" <BYTES>)
+                .help("Size in bytes past which the file of a file event sink is rotated (0 disables rotation)")
+                .default_value("10485760")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(--"event-sink-file-max-count" <COUNT>)
+                .help("Number of rotated files kept by a file event sink")
+                .default_value("5")
+                .value_parser(value_parser!(usize)),
+        )
         .arg(
             arg!(--"request-wait-timeout" <MILLISECONDS>)
                 .help("Maximum time in milliseconds that can wait to establish a connection with a worker")
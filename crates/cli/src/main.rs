@@ -6,9 +6,11 @@ mod logger;
 
 use anyhow::{anyhow, bail, Error};
 use base::commands::start_server;
+use base::deno_runtime;
+use base::event_sinks::{EventSinkKind, EventSinkOpts};
 
-use base::rt_worker::worker_pool::{SupervisorPolicy, WorkerPoolPolicy};
-use base::server::{ServerFlags, Tls, WorkerEntrypoints};
+use base::rt_worker::worker_pool::{QueueFullBehavior, SupervisorPolicy, WorkerPoolPolicy};
+use base::server::{EventDropPolicy, ServerFlags, Tls, WorkerEntrypoints};
 use base::{DecoratorType, InspectorOption};
 use clap::ArgMatches;
 use deno_core::url::Url;
@@ -25,6 +27,7 @@ use std::io::Write;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 fn main() -> Result<(), anyhow::Error> {
     resolve_deno_runtime_env();
@@ -96,6 +99,10 @@ fn main() -> Result<(), anyhow::Error> {
                     .unwrap();
                 let import_map_path = sub_matches.get_one::<String>("import-map").cloned();
 
+                if let Some(dir) = sub_matches.get_one::<PathBuf>("eszip-cache-dir").cloned() {
+                    deno_runtime::MAYBE_ESZIP_CACHE_DIR.get_or_init(|| dir);
+                }
+
                 let no_module_cache = sub_matches
                     .get_one::<bool>("disable-module-cache")
                     .cloned()
@@ -143,6 +150,34 @@ fn main() -> Result<(), anyhow::Error> {
 
                 let maybe_max_parallelism =
                     sub_matches.get_one::<usize>("max-parallelism").cloned();
+                let maybe_max_total_parallelism = sub_matches
+                    .get_one::<usize>("max-total-parallelism")
+                    .cloned();
+                let maybe_request_queue_size =
+                    sub_matches.get_one::<usize>("request-queue-size").cloned();
+                let maybe_request_queue_full_behavior = sub_matches
+                    .get_one::<String>("request-queue-full-behavior")
+                    .map(|it| it.parse::<QueueFullBehavior>().unwrap());
+                let maybe_circuit_breaker_threshold = sub_matches
+                    .get_one::<usize>("circuit-breaker-threshold")
+                    .cloned();
+                let maybe_circuit_breaker_cooldown = sub_matches
+                    .get_one::<u64>("circuit-breaker-cooldown")
+                    .cloned();
+                let maybe_primary_worker_restart_backoff = sub_matches
+                    .get_one::<u64>("primary-worker-restart-backoff")
+                    .cloned();
+                let maybe_primary_worker_restart_max_backoff = sub_matches
+                    .get_one::<u64>("primary-worker-restart-max-backoff")
+                    .cloned();
+                let maybe_event_channel_capacity = sub_matches
+                    .get_one::<usize>("event-channel-capacity")
+                    .cloned();
+                let maybe_event_drop_policy = sub_matches
+                    .get_one::<String>("event-drop-policy")
+                    .map(|it| it.parse::<EventDropPolicy>().unwrap());
+                let maybe_event_block_timeout =
+                    sub_matches.get_one::<u64>("event-block-timeout").cloned();
                 let maybe_request_wait_timeout =
                     sub_matches.get_one::<u64>("request-wait-timeout").cloned();
                 let maybe_request_idle_timeout =
@@ -159,6 +194,42 @@ fn main() -> Result<(), anyhow::Error> {
                 let jsx_specifier = sub_matches.get_one::<String>("jsx-specifier").cloned();
                 let jsx_module = sub_matches.get_one::<String>("jsx-module").cloned();
 
+                let maybe_event_sink_opts =
+                    if let Some(val_ref) = sub_matches.get_many::<String>("event-sink") {
+                        Some(EventSinkOpts {
+                            sinks: val_ref
+                                .map(|it| it.parse::<EventSinkKind>())
+                                .collect::<Result<Vec<_>, _>>()?,
+                            event_types: sub_matches
+                                .get_many::<String>("event-sink-types")
+                                .map(|it| it.cloned().collect()),
+                            batch_size: sub_matches
+                                .get_one::<usize>("event-sink-batch-size")
+                                .cloned()
+                                .unwrap(),
+                            flush_interval: Duration::from_millis(
+                                sub_matches
+                                    .get_one::<u64>("event-sink-flush-interval")
+                                    .cloned()
+                                    .unwrap(),
+                            ),
+                            max_retries: sub_matches
+                                .get_one::<usize>("event-sink-max-retries")
+                                .cloned()
+                                .unwrap(),
+                            file_max_size: sub_matches
+                                .get_one::<u64>("event-sink-file-max-size")
+                                .cloned()
+                                .unwrap(),
+                            file_max_count: sub_matches
+                                .get_one::<usize>("event-sink-file-max-count")
+                                .cloned()
+                                .unwrap(),
+                        })
+                    } else {
+                        None
+                    };
+
                 let static_patterns: Vec<String> =
                     static_patterns.into_iter().map(|s| s.to_string()).collect();
 
@@ -185,6 +256,16 @@ fn main() -> Result<(), anyhow::Error> {
                     request_wait_timeout_ms: maybe_request_wait_timeout,
                     request_idle_timeout_ms: maybe_request_idle_timeout,
                     request_read_timeout_ms: maybe_request_read_timeout,
+                    request_queue_size: maybe_request_queue_size,
+                    request_queue_full_behavior: maybe_request_queue_full_behavior,
+                    max_total_parallelism: maybe_max_total_parallelism,
+                    circuit_breaker_threshold: maybe_circuit_breaker_threshold,
+                    circuit_breaker_cooldown_ms: maybe_circuit_breaker_cooldown,
+                    primary_worker_restart_backoff_ms: maybe_primary_worker_restart_backoff,
+                    primary_worker_restart_max_backoff_ms: maybe_primary_worker_restart_max_backoff,
+                    event_channel_capacity: maybe_event_channel_capacity,
+                    event_drop_policy: maybe_event_drop_policy,
+                    event_block_timeout_ms: maybe_event_block_timeout,
                 };
 
                 start_server(
@@ -230,6 +311,7 @@ fn main() -> Result<(), anyhow::Error> {
                     maybe_inspector_option,
                     jsx_specifier,
                     jsx_module,
+                    maybe_event_sink_opts,
                 )
                 .await?;
             }
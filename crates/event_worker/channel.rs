@@ -0,0 +1,622 @@
+use crate::events::{EventMetadata, EventsDroppedEvent, WorkerEventWithMetadata, WorkerEvents};
+use anyhow::{anyhow, bail, Error};
+use log::warn;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// What a producer does with a new event when the event channel is already
+/// full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EventDropPolicy {
+    /// Drops the event that has been waiting the longest and queues the new
+    /// one instead.
+    #[default]
+    DropOldest,
+    /// Drops the new event.
+    DropNewest,
+    /// Blocks the producer until there is room in the channel, dropping the
+    /// new event if none is made within the block timeout.
+    ///
+    /// Only producers sending with [`EventSender::send_blocking`] are
+    /// blocked. The others drop the new event right away.
+    Block,
+}
+
+impl FromStr for EventDropPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop_oldest" => Ok(Self::DropOldest),
+            "drop_newest" => Ok(Self::DropNewest),
+            "block" => Ok(Self::Block),
+            _ => Err(anyhow!("unknown event drop policy: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EventChannelOpts {
+    /// Maximum number of events waiting to be accepted by the events worker.
+    /// `None` leaves the channel unbounded.
+    pub capacity: Option<usize>,
+    pub drop_policy: EventDropPolicy,
+    /// How long a producer is blocked with [`EventDropPolicy::Block`].
+    pub block_timeout: Duration,
+}
+
+/// Number of events of a type that were dropped for a service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DroppedEvents {
+    pub service_path: Option<String>,
+    pub event_type: &'static str,
+    pub count: u64,
+}
+
+type DropKey = (Option<String>, &'static str);
+
+#[derive(Default)]
+struct ChannelState {
+    queue: VecDeque<WorkerEventWithMetadata>,
+    closed: bool,
+    dropped: HashMap<DropKey, u64>,
+    /// Drops that have not been reported to the events worker yet.
+    unreported: HashMap<DropKey, u64>,
+}
+
+impl ChannelState {
+    fn record_drop(&mut self, ev: &WorkerEventWithMetadata) {
+        let key = (ev.metadata.service_path.clone(), ev.event.event_type());
+        let unreported = self.unreported.entry(key.clone()).or_default();
+
+        if *unreported == 0 {
+            warn!(
+                "event channel is full, dropping {} events (service: {})",
+                key.1,
+                key.0.as_deref().unwrap_or("-")
+            );
+        }
+
+        *unreported += 1;
+        *self.dropped.entry(key).or_default() += 1;
+    }
+
+    fn next_event(&mut self) -> Option<WorkerEventWithMetadata> {
+        let key = self.unreported.keys().next().cloned();
+
+        if let Some(key) = key {
+            let count = self.unreported.remove(&key).unwrap_or_default();
+            let (service_path, event_type) = key;
+
+            return Some(WorkerEventWithMetadata {
+                event: WorkerEvents::EventsDropped(EventsDroppedEvent {
+                    event_type: event_type.to_string(),
+                    count,
+                }),
+                metadata: EventMetadata {
+                    service_path,
+                    ..Default::default()
+                },
+            });
+        }
+
+        self.queue.pop_front()
+    }
+}
+
+struct Channel {
+    opts: EventChannelOpts,
+    state: Mutex<ChannelState>,
+    not_full: Condvar,
+    not_empty: Notify,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+}
+
+/// Creates the channel through which workers send their events to the events
+/// worker.
+///
+/// Once the channel is full, new events are handled according to the drop
+/// policy. Dropped events are counted per service path and event type, and
+/// reported to the receiver with an `EventsDropped` event ahead of the
+/// events still in the channel.
+pub fn event_channel(opts: EventChannelOpts) -> (EventSender, EventReceiver) {
+    let channel = Arc::new(Channel {
+        opts,
+        state: Mutex::default(),
+        not_full: Condvar::new(),
+        not_empty: Notify::new(),
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+    });
+
+    (EventSender(vec![channel.clone()]), EventReceiver(channel))
+}
+
+impl Channel {
+    fn send(&self, ev: WorkerEventWithMetadata, block: bool) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.closed {
+            bail!("events worker receiver has been closed");
+        }
+
+        if let Some(capacity) = self.opts.capacity {
+            if state.queue.len() >= capacity {
+                match self.opts.drop_policy {
+                    EventDropPolicy::DropOldest => {
+                        if let Some(oldest) = state.queue.pop_front() {
+                            state.record_drop(&oldest);
+                        }
+                    }
+
+                    EventDropPolicy::DropNewest => {
+                        state.record_drop(&ev);
+                        return Ok(());
+                    }
+
+                    EventDropPolicy::Block if !block => {
+                        state.record_drop(&ev);
+                        return Ok(());
+                    }
+
+                    EventDropPolicy::Block => {
+                        state = self
+                            .not_full
+                            .wait_timeout_while(state, self.opts.block_timeout, |it| {
+                                !it.closed && it.queue.len() >= capacity
+                            })
+                            .unwrap()
+                            .0;
+
+                        if state.closed {
+                            bail!("events worker receiver has been closed");
+                        }
+
+                        if state.queue.len() >= capacity {
+                            state.record_drop(&ev);
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
+
+        state.queue.push_back(ev);
+        drop(state);
+        self.not_empty.notify_one();
+
+        Ok(())
+    }
+}
+
+/// The sending end of one or more event channels.
+pub struct EventSender(Vec<Arc<Channel>>);
+
+impl EventSender {
+    /// Combines senders so that every event is delivered to each of their
+    /// channels.
+    pub fn fanout(senders: impl IntoIterator<Item = EventSender>) -> Self {
+        Self(
+            senders
+                .into_iter()
+                .flat_map(|it| it.0.clone())
+                .inspect(|it| {
+                    it.senders.fetch_add(1, Ordering::AcqRel);
+                })
+                .collect(),
+        )
+    }
+
+    /// Sends an event to each channel without ever blocking, so it is safe to
+    /// call from async tasks. An error is only returned once every receiver
+    /// has been dropped.
+    pub fn send(&self, ev: WorkerEventWithMetadata) -> Result<(), Error> {
+        self.send_inner(ev, false)
+    }
+
+    /// Like [`EventSender::send`], except that with [`EventDropPolicy::Block`]
+    /// it blocks the calling thread for up to the block timeout while a
+    /// channel is full. Only meant to be called from the thread of an
+    /// isolate.
+    pub fn send_blocking(&self, ev: WorkerEventWithMetadata) -> Result<(), Error> {
+        self.send_inner(ev, true)
+    }
+
+    fn send_inner(&self, ev: WorkerEventWithMetadata, block: bool) -> Result<(), Error> {
+        let Some((last, rest)) = self.0.split_last() else {
+            bail!("events worker receiver has been closed");
+        };
+
+        let mut delivered = false;
+
+        for channel in rest {
+            delivered |= channel.send(ev.clone(), block).is_ok();
+        }
+
+        delivered |= last.send(ev, block).is_ok();
+
+        if !delivered {
+            bail!("events worker receiver has been closed");
+        }
+
+        Ok(())
+    }
+
+    /// Returns a handle to read the drop counters of the channels with,
+    /// which doesn't keep them open.
+    pub fn drop_counters(&self) -> EventDropCounters {
+        EventDropCounters(self.0.clone())
+    }
+}
+
+/// Reads the number of events dropped by one or more event channels.
+#[derive(Clone)]
+pub struct EventDropCounters(Vec<Arc<Channel>>);
+
+impl EventDropCounters {
+    /// Returns the number of dropped events per service path and event type
+    /// since the channels were created.
+    pub fn dropped_events(&self) -> Vec<DroppedEvents> {
+        let mut dropped = HashMap::<DropKey, u64>::new();
+
+        for channel in &self.0 {
+            let state = channel.state.lock().unwrap();
+
+            for (key, count) in &state.dropped {
+                *dropped.entry(key.clone()).or_default() += count;
+            }
+        }
+
+        dropped
+            .into_iter()
+            .map(|((service_path, event_type), count)| DroppedEvents {
+                service_path,
+                event_type,
+                count,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for EventDropCounters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventDropCounters").finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for EventSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|it| &it.opts))
+            .finish()
+    }
+}
+
+impl Clone for EventSender {
+    fn clone(&self) -> Self {
+        for channel in &self.0 {
+            channel.senders.fetch_add(1, Ordering::AcqRel);
+        }
+
+        Self(self.0.clone())
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        for channel in &self.0 {
+            if channel.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+                channel.not_empty.notify_waiters();
+            }
+        }
+    }
+}
+
+/// The receiving end of the event channel.
+///
+/// It can be cloned so that successive incarnations of the events worker
+/// keep accepting events from the same channel.
+pub struct EventReceiver(Arc<Channel>);
+
+impl EventReceiver {
+    /// Receives the next event, or `None` once every sender has been dropped
+    /// and the channel has been drained.
+    pub async fn recv(&self) -> Option<WorkerEventWithMetadata> {
+        let channel = &*self.0;
+
+        loop {
+            let notified = channel.not_empty.notified();
+
+            {
+                let mut state = channel.state.lock().unwrap();
+
+                if let Some(ev) = state.next_event() {
+                    drop(state);
+                    channel.not_full.notify_one();
+                    return Some(ev);
+                }
+
+                if channel.senders.load(Ordering::Acquire) == 0 {
+                    return None;
+                }
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Receives the next event if there is one in the channel.
+    pub fn try_recv(&self) -> Option<WorkerEventWithMetadata> {
+        let ev = self.0.state.lock().unwrap().next_event();
+
+        if ev.is_some() {
+            self.0.not_full.notify_one();
+        }
+
+        ev
+    }
+}
+
+impl fmt::Debug for EventReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventReceiver")
+            .field("opts", &self.0.opts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Clone for EventReceiver {
+    fn clone(&self) -> Self {
+        self.0.receivers.fetch_add(1, Ordering::AcqRel);
+        Self(self.0.clone())
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        if self.0.receivers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            let mut state = self.0.state.lock().unwrap();
+
+            state.closed = true;
+            state.queue.clear();
+            drop(state);
+
+            self.0.not_full.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::{LogEvent, LogLevel};
+    use std::time::Instant;
+
+    fn log(service_path: &str, msg: &str) -> WorkerEventWithMetadata {
+        WorkerEventWithMetadata {
+            event: WorkerEvents::Log(LogEvent {
+                msg: msg.to_string(),
+                level: LogLevel::Info,
+                timestamp: 0,
+                source: None,
+                fields: None,
+            }),
+            metadata: EventMetadata {
+                service_path: Some(service_path.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn bounded(capacity: usize, drop_policy: EventDropPolicy) -> (EventSender, EventReceiver) {
+        event_channel(EventChannelOpts {
+            capacity: Some(capacity),
+            drop_policy,
+            block_timeout: Duration::from_millis(100),
+        })
+    }
+
+    fn recv_msg(rx: &EventReceiver) -> Option<String> {
+        match rx.try_recv()?.event {
+            WorkerEvents::Log(ev) => Some(ev.msg),
+            ev => panic!("unexpected event: {:?}", ev),
+        }
+    }
+
+    fn recv_dropped(rx: &EventReceiver) -> (Option<String>, EventsDroppedEvent) {
+        let ev = rx.try_recv().expect("no event in the channel");
+
+        match ev.event {
+            WorkerEvents::EventsDropped(dropped) => (ev.metadata.service_path, dropped),
+            ev => panic!("unexpected event: {:?}", ev),
+        }
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let (tx, rx) = bounded(2, EventDropPolicy::DropOldest);
+
+        for msg in ["a", "b", "c", "d"] {
+            tx.send(log("foo", msg)).unwrap();
+        }
+
+        let (service_path, dropped) = recv_dropped(&rx);
+
+        assert_eq!(service_path.as_deref(), Some("foo"));
+        assert_eq!(dropped.event_type, "Log");
+        assert_eq!(dropped.count, 2);
+        assert_eq!(recv_msg(&rx).as_deref(), Some("c"));
+        assert_eq!(recv_msg(&rx).as_deref(), Some("d"));
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[test]
+    fn test_drop_newest() {
+        let (tx, rx) = bounded(2, EventDropPolicy::DropNewest);
+
+        for msg in ["a", "b", "c"] {
+            tx.send(log("foo", msg)).unwrap();
+        }
+
+        assert_eq!(recv_dropped(&rx).1.count, 1);
+        assert_eq!(recv_msg(&rx).as_deref(), Some("a"));
+        assert_eq!(recv_msg(&rx).as_deref(), Some("b"));
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[test]
+    fn test_block_drops_after_timeout() {
+        let (tx, rx) = bounded(1, EventDropPolicy::Block);
+
+        tx.send_blocking(log("foo", "a")).unwrap();
+
+        let started_at = Instant::now();
+
+        tx.send_blocking(log("foo", "b")).unwrap();
+
+        assert!(started_at.elapsed() >= Duration::from_millis(100));
+        assert_eq!(recv_dropped(&rx).1.count, 1);
+        assert_eq!(recv_msg(&rx).as_deref(), Some("a"));
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[test]
+    fn test_block_resumes_once_there_is_room() {
+        let (tx, rx) = bounded(1, EventDropPolicy::Block);
+
+        tx.send_blocking(log("foo", "a")).unwrap();
+
+        let handle = std::thread::spawn(move || tx.send_blocking(log("foo", "b")));
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(recv_msg(&rx).as_deref(), Some("a"));
+
+        handle.join().unwrap().unwrap();
+        assert_eq!(recv_msg(&rx).as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_block_does_not_block_async_senders() {
+        let (tx, rx) = bounded(1, EventDropPolicy::Block);
+
+        tx.send(log("foo", "a")).unwrap();
+
+        let started_at = Instant::now();
+
+        tx.send(log("foo", "b")).unwrap();
+
+        assert!(started_at.elapsed() < Duration::from_millis(100));
+        assert_eq!(recv_dropped(&rx).1.count, 1);
+        assert_eq!(recv_msg(&rx).as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn test_drops_are_reported_ahead_of_queued_events() {
+        let (tx, rx) = bounded(1, EventDropPolicy::DropNewest);
+
+        tx.send(log("foo", "a")).unwrap();
+        tx.send(log("foo", "b")).unwrap();
+        tx.send(log("bar", "c")).unwrap();
+
+        let mut reported = [recv_dropped(&rx), recv_dropped(&rx)]
+            .map(|(service_path, dropped)| (service_path.unwrap(), dropped.count));
+
+        reported.sort();
+
+        assert_eq!(reported, [("bar".to_string(), 1), ("foo".to_string(), 1)]);
+        assert_eq!(recv_msg(&rx).as_deref(), Some("a"));
+
+        // Drops are only reported once.
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[test]
+    fn test_drop_counters() {
+        let (tx, rx) = bounded(1, EventDropPolicy::DropNewest);
+        let counters = tx.drop_counters();
+
+        for (service_path, msg) in [("foo", "a"), ("foo", "b"), ("foo", "c"), ("bar", "d")] {
+            tx.send(log(service_path, msg)).unwrap();
+        }
+
+        // Reporting the drops to the receiver doesn't reset the counters.
+        while rx.try_recv().is_some() {}
+
+        let mut dropped = counters.dropped_events();
+
+        dropped.sort_by(|a, b| a.service_path.cmp(&b.service_path));
+
+        assert_eq!(
+            dropped,
+            vec![
+                DroppedEvents {
+                    service_path: Some("bar".to_string()),
+                    event_type: "Log",
+                    count: 1,
+                },
+                DroppedEvents {
+                    service_path: Some("foo".to_string()),
+                    event_type: "Log",
+                    count: 2,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recv_ends_once_senders_are_dropped() {
+        let (tx, rx) = event_channel(EventChannelOpts::default());
+        let counters = tx.drop_counters();
+        let tx2 = tx.clone();
+
+        tx.send(log("foo", "a")).unwrap();
+        drop(tx);
+        tx2.send(log("foo", "b")).unwrap();
+        drop(tx2);
+
+        // The drop counters don't keep the channel open.
+        drop(counters);
+
+        assert!(rx.recv().await.is_some());
+        assert!(rx.recv().await.is_some());
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[test]
+    fn test_send_fails_once_receivers_are_dropped() {
+        let (tx, rx) = event_channel(EventChannelOpts::default());
+        let rx2 = rx.clone();
+
+        drop(rx);
+        tx.send(log("foo", "a")).unwrap();
+
+        drop(rx2);
+        assert!(tx.send(log("foo", "b")).is_err());
+    }
+
+    #[test]
+    fn test_fanout_delivers_while_a_receiver_is_open() {
+        let (tx1, rx1) = event_channel(EventChannelOpts::default());
+        let (tx2, rx2) = event_channel(EventChannelOpts::default());
+        let tx = EventSender::fanout([tx1, tx2]);
+
+        tx.send(log("foo", "a")).unwrap();
+        assert_eq!(recv_msg(&rx1).as_deref(), Some("a"));
+        assert_eq!(recv_msg(&rx2).as_deref(), Some("a"));
+
+        drop(rx1);
+        tx.send(log("foo", "b")).unwrap();
+        assert_eq!(recv_msg(&rx2).as_deref(), Some("b"));
+
+        drop(rx2);
+        assert!(tx.send(log("foo", "c")).is_err());
+    }
+}
@@ -1,12 +1,29 @@
 import { primordials, core } from "ext:core/mod.js";
 const { SymbolAsyncIterator } = primordials;
 
-const { op_event_accept } = core.ops;
+const { op_event_subscribe, op_event_accept } = core.ops;
 
 class SupabaseEventListener {
+	#rid;
+
+	/**
+	 * @param {{ types?: string[], servicePaths?: string[] }} [opts] Only events
+	 * of these types, emitted by workers of these service paths, are received.
+	 * Each instance receives its own copy of the events it subscribed to.
+	 */
+	constructor(opts = {}) {
+		const { types = null, servicePaths = null } = opts ?? {};
+
+		this.#rid = op_event_subscribe({ types, servicePaths });
+	}
+
+	close() {
+		core.tryClose(this.#rid);
+	}
+
 	async nextEvent() {
 		try {
-			const reqEvt = await op_event_accept();
+			const reqEvt = await op_event_accept(this.#rid);
 			const done = reqEvt === 'Done';
 
 			let value = undefined;
@@ -35,6 +52,11 @@ class SupabaseEventListener {
 			async next() {
 				return await scopedClass.nextEvent();
 			},
+
+			async return() {
+				scopedClass.close();
+				return { value: undefined, done: true };
+			},
 		};
 	}
 }
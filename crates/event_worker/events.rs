@@ -1,17 +1,57 @@
+use anyhow::{anyhow, Error};
 use base_mem_check::MemCheckState;
+use deno_core::error::{JsError, JsStackFrame};
+use deno_core::serde_json::{Map, Value};
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use uuid::Uuid;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BootEvent {
     pub boot_time: usize,
 }
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BootFailureEvent {
     pub msg: String,
+    #[serde(default)]
+    pub kind: BootFailureKind,
+    /// The module that could not be resolved or loaded.
+    #[serde(default)]
+    pub specifier: Option<String>,
+    /// The module importing `specifier`.
+    #[serde(default)]
+    pub referrer: Option<String>,
+    #[serde(default)]
+    pub line: Option<u32>,
+    #[serde(default)]
+    pub column: Option<u32>,
+}
+
+impl BootFailureEvent {
+    pub fn new(kind: BootFailureKind, msg: String) -> Self {
+        Self {
+            msg,
+            kind,
+            specifier: None,
+            referrer: None,
+            line: None,
+            column: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BootFailureKind {
+    ModuleResolution,
+    NpmResolution,
+    Transpile,
+    TopLevelException,
+    Timeout,
+    #[default]
+    Unknown,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WorkerMemoryUsed {
     pub total: usize,
     pub heap: usize,
@@ -19,16 +59,17 @@ pub struct WorkerMemoryUsed {
     pub mem_check_captured: MemCheckState,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum ShutdownReason {
     WallClockTime,
     CPUTime,
     Memory,
+    MemorySoftLimit,
     EarlyDrop,
     TerminationRequested,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ShutdownEvent {
     pub reason: ShutdownReason,
     pub cpu_time_used: usize,
@@ -39,20 +80,113 @@ pub struct ShutdownEvent {
 pub struct UncaughtExceptionEvent {
     pub exception: String,
     pub cpu_time_used: usize,
+    /// Class of the error, such as `TypeError`.
+    #[serde(default)]
+    pub class: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Stack frames of the error, mapped back to their original locations.
+    #[serde(default)]
+    pub frames: Vec<StackFrame>,
+}
+
+impl UncaughtExceptionEvent {
+    pub fn new(err: &Error, cpu_time_used: usize) -> Self {
+        let maybe_js_error = err.chain().find_map(|it| it.downcast_ref::<JsError>());
+
+        Self {
+            exception: err.to_string(),
+            cpu_time_used,
+            class: maybe_js_error.and_then(|it| it.name.clone()),
+            message: maybe_js_error.and_then(|it| it.message.clone()),
+            frames: maybe_js_error
+                .map(|it| it.frames.iter().map(StackFrame::from).collect())
+                .unwrap_or_default(),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StackFrame {
+    pub function_name: Option<String>,
+    pub file_name: Option<String>,
+    pub line_number: Option<i64>,
+    pub column_number: Option<i64>,
+}
+
+impl From<&JsStackFrame> for StackFrame {
+    fn from(value: &JsStackFrame) -> Self {
+        Self {
+            function_name: value.function_name.clone(),
+            file_name: value.file_name.clone(),
+            line_number: value.line_number,
+            column_number: value.column_number,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EventLoopCompletedEvent {
     pub cpu_time_used: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CircuitBreakerEvent {
+    pub failures: usize,
+    pub msg: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RequestStartEvent {
+    pub method: String,
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RequestEndEvent {
+    pub method: String,
+    pub path: String,
+    pub status: Option<u16>,
+    pub duration_ms: f64,
+    pub bytes: u64,
+    pub cpu_time_ms: f64,
+    pub peak_heap_delta_bytes: usize,
+    pub msg: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PrimaryWorkerRestartEvent {
+    pub attempt: usize,
+    pub backoff_ms: u64,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventsDroppedEvent {
+    pub event_type: String,
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LogEvent {
     pub msg: String,
     pub level: LogLevel,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub source: Option<LogSource>,
+    /// Set when a plain object is the sole argument of the console call.
+    pub fields: Option<Map<String, Value>>,
+}
+
+/// Where the console method was called from.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LogSource {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LogLevel {
     Debug,
     Info,
@@ -60,7 +194,42 @@ pub enum LogLevel {
     Error,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl LogLevel {
+    /// Maps the level passed by `deno_console` to its print function.
+    pub fn from_console_level(level: u32) -> Self {
+        match level {
+            0 => Self::Debug,
+            1 => Self::Info,
+            2 => Self::Warning,
+            _ => Self::Error,
+        }
+    }
+
+    pub fn to_console_level(self) -> u32 {
+        match self {
+            Self::Debug => 0,
+            Self::Info => 1,
+            Self::Warning => 2,
+            Self::Error => 3,
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warn" | "warning" => Ok(Self::Warning),
+            "error" => Ok(Self::Error),
+            _ => Err(anyhow!("unknown log level: {}", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum WorkerEvents {
     Boot(BootEvent),
     BootFailure(BootFailureEvent),
@@ -68,9 +237,32 @@ pub enum WorkerEvents {
     Shutdown(ShutdownEvent),
     EventLoopCompleted(EventLoopCompletedEvent),
     Log(LogEvent),
+    CircuitBreakerOpen(CircuitBreakerEvent),
+    CircuitBreakerClose(CircuitBreakerEvent),
+    PrimaryWorkerRestart(PrimaryWorkerRestartEvent),
+    RequestStart(RequestStartEvent),
+    RequestEnd(RequestEndEvent),
+    EventsDropped(EventsDroppedEvent),
 }
 
 impl WorkerEvents {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            Self::Boot(_) => "Boot",
+            Self::BootFailure(_) => "BootFailure",
+            Self::UncaughtException(_) => "UncaughtException",
+            Self::Shutdown(_) => "Shutdown",
+            Self::EventLoopCompleted(_) => "EventLoopCompleted",
+            Self::Log(_) => "Log",
+            Self::CircuitBreakerOpen(_) => "CircuitBreakerOpen",
+            Self::CircuitBreakerClose(_) => "CircuitBreakerClose",
+            Self::PrimaryWorkerRestart(_) => "PrimaryWorkerRestart",
+            Self::RequestStart(_) => "RequestStart",
+            Self::RequestEnd(_) => "RequestEnd",
+            Self::EventsDropped(_) => "EventsDropped",
+        }
+    }
+
     pub fn with_cpu_time_used(mut self, cpu_time_used_ms: usize) -> Self {
         match &mut self {
             Self::UncaughtException(UncaughtExceptionEvent { cpu_time_used, .. })
@@ -89,9 +281,10 @@ impl WorkerEvents {
 pub struct EventMetadata {
     pub service_path: Option<String>,
     pub execution_id: Option<Uuid>,
+    pub request_id: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WorkerEventWithMetadata {
     pub event: WorkerEvents,
     pub metadata: EventMetadata,
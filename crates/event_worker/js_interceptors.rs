@@ -1,35 +1,175 @@
-use crate::events::{EventMetadata, LogEvent, LogLevel, WorkerEvents};
+use crate::channel::EventSender;
+use crate::events::{EventMetadata, LogEvent, LogLevel, LogSource, WorkerEvents};
 use crate::WorkerEventWithMetadata;
 use deno_core::error::AnyError;
 use deno_core::op2;
+use deno_core::serde_json::{Map, Value};
 use deno_core::OpState;
 use log::error;
-use tokio::sync::mpsc;
+use serde::Deserialize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[op2(fast)]
+const LOG_QUOTA_WINDOW: Duration = Duration::from_secs(1);
+
+/// Messages logged by a user worker below this level are discarded.
+#[derive(Debug, Clone, Copy)]
+pub struct MinLogLevel(pub LogLevel);
+
+/// Limits how many messages and bytes a user worker may log per second.
+///
+/// Messages past either limit are dropped. How many were dropped is reported
+/// by a single log event when the worker logs again after the window is over,
+/// or when the worker is dropped, whichever comes first.
+#[derive(Debug)]
+pub struct LogQuota {
+    max_messages: u64,
+    max_bytes: u64,
+    window_start: Instant,
+    messages: u64,
+    bytes: u64,
+    suppressed: u64,
+    reporter: Option<(EventSender, EventMetadata)>,
+}
+
+impl LogQuota {
+    /// A limit of zero disables it. Without a `reporter`, suppressed
+    /// messages are reported to the runtime log.
+    pub fn new(
+        max_messages: u64,
+        max_bytes: u64,
+        reporter: Option<(EventSender, EventMetadata)>,
+    ) -> Self {
+        Self {
+            max_messages,
+            max_bytes,
+            window_start: Instant::now(),
+            messages: 0,
+            bytes: 0,
+            suppressed: 0,
+            reporter,
+        }
+    }
+
+    /// Returns whether a message of `len` bytes may be logged.
+    fn acquire(&mut self, len: usize) -> bool {
+        self.acquire_at(len, Instant::now())
+    }
+
+    fn acquire_at(&mut self, len: usize, now: Instant) -> bool {
+        if now.saturating_duration_since(self.window_start) >= LOG_QUOTA_WINDOW {
+            self.report_suppressed();
+            self.window_start = now;
+            self.messages = 0;
+            self.bytes = 0;
+        }
+
+        let len = len as u64;
+
+        if (self.max_messages > 0 && self.messages >= self.max_messages)
+            || (self.max_bytes > 0 && self.bytes + len > self.max_bytes)
+        {
+            self.suppressed += 1;
+            return false;
+        }
+
+        self.messages += 1;
+        self.bytes += len;
+
+        true
+    }
+
+    fn report_suppressed(&mut self) {
+        if self.suppressed == 0 {
+            return;
+        }
+
+        let suppressed = std::mem::take(&mut self.suppressed);
+        let msg = format!(
+            "{} log messages were suppressed because the log quota of the worker was exceeded",
+            suppressed
+        );
+
+        let Some((tx, metadata)) = self.reporter.as_ref() else {
+            error!("[{:?}] {}", LogLevel::Warning, msg);
+            return;
+        };
+
+        let mut fields = Map::new();
+
+        fields.insert("suppressed".to_string(), Value::from(suppressed));
+
+        let _ = tx.send(WorkerEventWithMetadata {
+            event: WorkerEvents::Log(LogEvent {
+                msg,
+                level: LogLevel::Warning,
+                timestamp: now_ms(),
+                source: None,
+                fields: Some(fields),
+            }),
+            metadata: metadata.clone(),
+        });
+    }
+}
+
+impl Drop for LogQuota {
+    fn drop(&mut self) {
+        self.report_suppressed();
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[derive(Deserialize, Default, Debug)]
+pub struct LogOptions {
+    source: Option<LogSource>,
+    fields: Option<Map<String, Value>>,
+}
+
+#[op2]
 fn op_user_worker_log(
     state: &mut OpState,
     #[string] msg: &str,
-    is_err: bool,
+    #[smi] level: u32,
+    #[serde] opts: Option<LogOptions>,
 ) -> Result<(), AnyError> {
-    let maybe_tx = state.try_borrow::<mpsc::UnboundedSender<WorkerEventWithMetadata>>();
-    let mut level = LogLevel::Info;
-    if is_err {
-        level = LogLevel::Error;
+    let level = LogLevel::from_console_level(level);
+
+    if let Some(MinLogLevel(min_level)) = state.try_borrow::<MinLogLevel>() {
+        if level < *min_level {
+            return Ok(());
+        }
     }
 
+    if let Some(quota) = state.try_borrow_mut::<LogQuota>() {
+        if !quota.acquire(msg.len()) {
+            return Ok(());
+        }
+    }
+
+    let maybe_tx = state.try_borrow::<EventSender>();
+
     if let Some(tx) = maybe_tx {
+        let LogOptions { source, fields } = opts.unwrap_or_default();
         let event_metadata = state
             .try_borrow::<EventMetadata>()
             .unwrap_or(&EventMetadata::default())
             .clone();
 
         let metadata = EventMetadata { ..event_metadata };
+        let timestamp = now_ms();
 
-        tx.send(WorkerEventWithMetadata {
+        tx.send_blocking(WorkerEventWithMetadata {
             event: WorkerEvents::Log(LogEvent {
                 msg: msg.to_string(),
                 level,
+                timestamp,
+                source,
+                fields,
             }),
             metadata,
         })?;
@@ -40,4 +180,92 @@ fn op_user_worker_log(
     Ok(())
 }
 
-deno_core::extension!(sb_events_js_interceptors, ops = [op_user_worker_log,],);
+/// Returns the console level below which messages are discarded, so they
+/// don't need to be formatted at all.
+#[op2(fast)]
+#[smi]
+fn op_user_worker_log_level(state: &mut OpState) -> u32 {
+    state
+        .try_borrow::<MinLogLevel>()
+        .map(|MinLogLevel(level)| level.to_console_level())
+        .unwrap_or_default()
+}
+
+deno_core::extension!(
+    sb_events_js_interceptors,
+    ops = [op_user_worker_log, op_user_worker_log_level],
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::channel::{event_channel, EventChannelOpts, EventReceiver};
+
+    fn quota(max_messages: u64, max_bytes: u64) -> (LogQuota, EventReceiver) {
+        let (tx, rx) = event_channel(EventChannelOpts::default());
+
+        (
+            LogQuota::new(
+                max_messages,
+                max_bytes,
+                Some((tx, EventMetadata::default())),
+            ),
+            rx,
+        )
+    }
+
+    fn recv_suppressed(rx: &EventReceiver) -> Option<u64> {
+        match rx.try_recv()?.event {
+            WorkerEvents::Log(LogEvent {
+                fields: Some(fields),
+                ..
+            }) => fields.get("suppressed").and_then(Value::as_u64),
+            ev => panic!("unexpected event: {:?}", ev),
+        }
+    }
+
+    #[test]
+    fn test_log_quota_limits_messages() {
+        let (mut quota, rx) = quota(2, 0);
+        let now = quota.window_start;
+
+        assert!(quota.acquire_at(1000, now));
+        assert!(quota.acquire_at(1000, now));
+        assert!(!quota.acquire_at(1, now));
+        assert!(!quota.acquire_at(1, now));
+        assert_eq!(quota.suppressed, 2);
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[test]
+    fn test_log_quota_limits_bytes() {
+        let (mut quota, _rx) = quota(0, 10);
+        let now = quota.window_start;
+
+        assert!(quota.acquire_at(6, now));
+        assert!(!quota.acquire_at(5, now));
+        assert!(quota.acquire_at(4, now));
+        assert!(!quota.acquire_at(1, now));
+        assert_eq!(quota.suppressed, 2);
+    }
+
+    #[test]
+    fn test_log_quota_resets_after_window() {
+        let (mut quota, rx) = quota(1, 0);
+        let now = quota.window_start;
+
+        assert!(quota.acquire_at(1, now));
+        assert!(!quota.acquire_at(1, now + LOG_QUOTA_WINDOW / 2));
+        assert!(!quota.acquire_at(1, now + LOG_QUOTA_WINDOW / 2));
+
+        // the first message of the next window reports the suppressed ones
+        assert!(quota.acquire_at(1, now + LOG_QUOTA_WINDOW));
+        assert_eq!(recv_suppressed(&rx), Some(2));
+        assert!(!quota.acquire_at(1, now + LOG_QUOTA_WINDOW));
+
+        drop(quota);
+
+        assert_eq!(recv_suppressed(&rx), Some(1));
+        assert!(rx.try_recv().is_none());
+    }
+}
@@ -1,39 +1,101 @@
+use crate::channel::EventReceiver;
 use crate::events::{RawEvent, WorkerEventWithMetadata};
+use crate::subscription::{EventFilter, EventSubscription, EventSubscriptions};
 use anyhow::{bail, Error};
 use deno_core::op2;
-use deno_core::OpState;
+use deno_core::{CancelFuture, OpState, RcRef, ResourceId};
 use std::cell::RefCell;
 use std::rc::Rc;
-use tokio::sync::mpsc;
 
+pub mod channel;
 pub mod events;
 pub mod js_interceptors;
+pub mod subscription;
+
+#[op2]
+#[smi]
+fn op_event_subscribe(
+    state: &mut OpState,
+    #[serde] filter: Option<EventFilter>,
+) -> Result<ResourceId, Error> {
+    if !state.has::<EventReceiver>() {
+        bail!("events worker receiver not available")
+    }
+
+    let subscription = Rc::new(EventSubscription::new(filter.unwrap_or_default()));
+
+    if !state.has::<EventSubscriptions>() {
+        state.put(EventSubscriptions::default());
+    }
+
+    state.borrow_mut::<EventSubscriptions>().add(&subscription);
+
+    Ok(state.resource_table.add_rc(subscription))
+}
 
 #[op2(async)]
 #[serde]
-async fn op_event_accept(state: Rc<RefCell<OpState>>) -> Result<RawEvent, Error> {
-    let rx = {
-        let mut op_state = state.borrow_mut();
-        op_state.try_take::<mpsc::UnboundedReceiver<WorkerEventWithMetadata>>()
+async fn op_event_accept(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+) -> Result<RawEvent, Error> {
+    let (rx, subscription) = {
+        let op_state = state.borrow();
+        let rx = op_state.try_borrow::<EventReceiver>().cloned();
+        let subscription = op_state.resource_table.get::<EventSubscription>(rid)?;
+
+        (rx, subscription)
     };
-    if rx.is_none() {
+
+    let Some(rx) = rx else {
         bail!("events worker receiver not available")
-    }
-    let mut rx = rx.unwrap();
+    };
+
+    let cancel = RcRef::map(&subscription, |it| &it.cancel);
+
+    loop {
+        if let Some(event) = subscription.pop() {
+            return Ok(RawEvent::Event(Box::new(event)));
+        }
 
-    let data = rx.recv().await;
+        // NOTE: Whichever subscriber is waiting pulls the next event from
+        // the channel and hands it to every subscriber that wants it.
+        let done = async {
+            tokio::select! {
+                _ = subscription.notify.notified() => false,
+                maybe_event = rx.recv() => match maybe_event {
+                    Some(event) => {
+                        dispatch(&state, event);
+                        false
+                    }
+
+                    None => true,
+                },
+            }
+        }
+        .or_cancel(cancel.clone())
+        .await
+        .unwrap_or(true);
+
+        if done {
+            return Ok(match subscription.pop() {
+                Some(event) => RawEvent::Event(Box::new(event)),
+                None => RawEvent::Done,
+            });
+        }
+    }
+}
 
+fn dispatch(state: &Rc<RefCell<OpState>>, event: WorkerEventWithMetadata) {
     let mut op_state = state.borrow_mut();
-    op_state.put::<mpsc::UnboundedReceiver<WorkerEventWithMetadata>>(rx);
 
-    match data {
-        Some(event) => Ok(RawEvent::Event(Box::new(event))),
-        None => Ok(RawEvent::Done),
+    if let Some(subscriptions) = op_state.try_borrow_mut::<EventSubscriptions>() {
+        subscriptions.dispatch(event);
     }
 }
 
 deno_core::extension!(
     sb_user_event_worker,
-    ops = [op_event_accept],
+    ops = [op_event_subscribe, op_event_accept],
     esm = ["event_worker.js"]
 );
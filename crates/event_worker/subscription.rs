@@ -0,0 +1,259 @@
+use crate::events::WorkerEventWithMetadata;
+use deno_core::{CancelHandle, Resource};
+use log::warn;
+use serde::Deserialize;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashSet, VecDeque};
+use std::rc::{Rc, Weak};
+use tokio::sync::Notify;
+
+/// How many events may be waiting for a subscriber before the oldest ones
+/// are dropped.
+const SUBSCRIPTION_QUEUE_CAPACITY: usize = 10_000;
+
+/// Which events a subscriber of the events worker receives. A `None` field
+/// matches every event.
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EventFilter {
+    pub types: Option<HashSet<String>>,
+    pub service_paths: Option<HashSet<String>>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, ev: &WorkerEventWithMetadata) -> bool {
+        if let Some(types) = self.types.as_ref() {
+            if !types.contains(ev.event.event_type()) {
+                return false;
+            }
+        }
+
+        if let Some(service_paths) = self.service_paths.as_ref() {
+            let Some(service_path) = ev.metadata.service_path.as_ref() else {
+                return false;
+            };
+
+            if !service_paths.contains(service_path) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// A subscriber of the events worker, backed by an `EventManager` instance.
+///
+/// Events are pulled from the channel by whichever subscriber is waiting and
+/// then queued for each subscriber whose filter matches them.
+pub struct EventSubscription {
+    pub filter: EventFilter,
+    queue: RefCell<VecDeque<WorkerEventWithMetadata>>,
+    dropped: Cell<u64>,
+    pub notify: Notify,
+    pub cancel: CancelHandle,
+}
+
+impl EventSubscription {
+    pub fn new(filter: EventFilter) -> Self {
+        Self {
+            filter,
+            queue: RefCell::default(),
+            dropped: Cell::new(0),
+            notify: Notify::new(),
+            cancel: CancelHandle::new(),
+        }
+    }
+
+    pub fn pop(&self) -> Option<WorkerEventWithMetadata> {
+        self.queue.borrow_mut().pop_front()
+    }
+
+    fn push(&self, ev: WorkerEventWithMetadata) {
+        let mut queue = self.queue.borrow_mut();
+
+        if queue.len() >= SUBSCRIPTION_QUEUE_CAPACITY {
+            queue.pop_front();
+
+            if self.dropped.replace(self.dropped.get() + 1) == 0 {
+                warn!("event subscriber is falling behind, dropping its oldest events");
+            }
+        }
+
+        queue.push_back(ev);
+        self.notify.notify_one();
+    }
+}
+
+impl Resource for EventSubscription {
+    fn name(&self) -> std::borrow::Cow<str> {
+        "eventSubscription".into()
+    }
+
+    fn close(self: Rc<Self>) {
+        self.cancel.cancel();
+    }
+}
+
+/// Every subscription registered in the events worker.
+#[derive(Default)]
+pub struct EventSubscriptions(Vec<Weak<EventSubscription>>);
+
+impl EventSubscriptions {
+    pub fn add(&mut self, subscription: &Rc<EventSubscription>) {
+        self.0.push(Rc::downgrade(subscription));
+    }
+
+    /// Queues the event for each live subscription whose filter matches it.
+    /// If none does, it is discarded without ever being serialized.
+    pub fn dispatch(&mut self, ev: WorkerEventWithMetadata) {
+        self.0.retain(|it| it.strong_count() > 0);
+
+        let mut matching = self
+            .0
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|it| it.filter.matches(&ev))
+            .collect::<Vec<_>>();
+
+        let Some(last) = matching.pop() else {
+            return;
+        };
+
+        for subscription in matching {
+            subscription.push(ev.clone());
+        }
+
+        last.push(ev);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::{EventMetadata, EventsDroppedEvent, LogEvent, LogLevel, WorkerEvents};
+
+    fn log(service_path: Option<&str>, msg: &str) -> WorkerEventWithMetadata {
+        WorkerEventWithMetadata {
+            event: WorkerEvents::Log(LogEvent {
+                msg: msg.to_string(),
+                level: LogLevel::Info,
+                timestamp: 0,
+                source: None,
+                fields: None,
+            }),
+            metadata: EventMetadata {
+                service_path: service_path.map(String::from),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn dropped(service_path: &str) -> WorkerEventWithMetadata {
+        WorkerEventWithMetadata {
+            event: WorkerEvents::EventsDropped(EventsDroppedEvent {
+                event_type: "Log".to_string(),
+                count: 1,
+            }),
+            metadata: EventMetadata {
+                service_path: Some(service_path.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn filter(types: Option<&[&str]>, service_paths: Option<&[&str]>) -> EventFilter {
+        let to_set = |it: &[&str]| it.iter().map(|it| it.to_string()).collect();
+
+        EventFilter {
+            types: types.map(to_set),
+            service_paths: service_paths.map(to_set),
+        }
+    }
+
+    fn drain(subscription: &EventSubscription) -> Vec<String> {
+        std::iter::from_fn(|| subscription.pop())
+            .map(|ev| match ev.event {
+                WorkerEvents::Log(ev) => ev.msg,
+                ev => ev.event_type().to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let ev = log(Some("foo"), "a");
+
+        assert!(EventFilter::default().matches(&ev));
+        assert!(filter(Some(&["Log"]), None).matches(&ev));
+        assert!(!filter(Some(&["Boot"]), None).matches(&ev));
+        assert!(filter(None, Some(&["foo", "bar"])).matches(&ev));
+        assert!(!filter(None, Some(&["bar"])).matches(&ev));
+        assert!(filter(Some(&["Log"]), Some(&["foo"])).matches(&ev));
+        assert!(!filter(Some(&["Log"]), Some(&["bar"])).matches(&ev));
+        assert!(!filter(Some(&["Boot"]), Some(&["foo"])).matches(&ev));
+
+        // an event without a service path only matches if service paths are
+        // not filtered
+        assert!(filter(Some(&["Log"]), None).matches(&log(None, "b")));
+        assert!(!filter(None, Some(&["foo"])).matches(&log(None, "b")));
+    }
+
+    #[test]
+    fn test_dispatch_fans_out_to_matching_subscriptions() {
+        let mut subscriptions = EventSubscriptions::default();
+        let all = Rc::new(EventSubscription::new(EventFilter::default()));
+        let logs = Rc::new(EventSubscription::new(filter(Some(&["Log"]), None)));
+        let bar = Rc::new(EventSubscription::new(filter(None, Some(&["bar"]))));
+
+        subscriptions.add(&all);
+        subscriptions.add(&logs);
+        subscriptions.add(&bar);
+
+        subscriptions.dispatch(log(Some("foo"), "a"));
+        subscriptions.dispatch(dropped("bar"));
+        subscriptions.dispatch(log(Some("bar"), "b"));
+
+        assert_eq!(drain(&all), ["a", "EventsDropped", "b"]);
+        assert_eq!(drain(&logs), ["a", "b"]);
+        assert_eq!(drain(&bar), ["EventsDropped", "b"]);
+    }
+
+    #[test]
+    fn test_dispatch_forgets_dropped_subscriptions() {
+        let mut subscriptions = EventSubscriptions::default();
+        let alive = Rc::new(EventSubscription::new(EventFilter::default()));
+        let dead = Rc::new(EventSubscription::new(EventFilter::default()));
+
+        subscriptions.add(&alive);
+        subscriptions.add(&dead);
+
+        drop(dead);
+        subscriptions.dispatch(log(Some("foo"), "a"));
+
+        assert_eq!(subscriptions.0.len(), 1);
+        assert_eq!(drain(&alive), ["a"]);
+    }
+
+    #[test]
+    fn test_full_subscription_drops_oldest_events() {
+        let mut subscriptions = EventSubscriptions::default();
+        let subscription = Rc::new(EventSubscription::new(EventFilter::default()));
+
+        subscriptions.add(&subscription);
+
+        for i in 0..SUBSCRIPTION_QUEUE_CAPACITY + 2 {
+            subscriptions.dispatch(log(Some("foo"), &i.to_string()));
+        }
+
+        let events = drain(&subscription);
+
+        assert_eq!(subscription.dropped.get(), 2);
+        assert_eq!(events.len(), SUBSCRIPTION_QUEUE_CAPACITY);
+        assert_eq!(events.first().map(String::as_str), Some("2"));
+        assert_eq!(
+            events.last(),
+            Some(&(SUBSCRIPTION_QUEUE_CAPACITY + 1).to_string())
+        );
+    }
+}
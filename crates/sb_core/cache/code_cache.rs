@@ -0,0 +1,197 @@
+use crate::cache::common::FastInsecureHasher;
+use deno_core::error::AnyError;
+use deno_webstorage::rusqlite::params;
+
+use super::cache_db::CacheDB;
+use super::cache_db::CacheDBConfiguration;
+use super::cache_db::CacheFailure;
+
+pub static CODE_CACHE_DB: CacheDBConfiguration = CacheDBConfiguration {
+    table_initializer: "CREATE TABLE IF NOT EXISTS codecache (
+      specifier TEXT PRIMARY KEY,
+      source_hash TEXT NOT NULL,
+      data BLOB NOT NULL
+    );",
+    on_version_change: "DELETE FROM codecache;",
+    preheat_queries: &[],
+    on_failure: CacheFailure::Blackhole,
+};
+
+/// Default maximum number of bytes of code cache data kept in the database.
+const MAX_CODE_CACHE_BYTES: i64 = 256 * 1024 * 1024;
+
+/// Stores the V8 code cache produced for modules, keyed by the module
+/// specifier and the hash of the module source.
+///
+/// Once the data stored exceeds its maximum size, the entries written the
+/// longest ago are evicted.
+#[derive(Clone)]
+pub struct CodeCache {
+    inner: CodeCacheInner,
+}
+
+impl CodeCache {
+    pub fn new(db: CacheDB) -> Self {
+        Self::with_max_bytes(db, MAX_CODE_CACHE_BYTES)
+    }
+
+    pub fn with_max_bytes(db: CacheDB, max_bytes: i64) -> Self {
+        Self {
+            inner: CodeCacheInner::new(db, max_bytes),
+        }
+    }
+
+    pub fn compute_source_hash(bytes: &[u8]) -> u64 {
+        FastInsecureHasher::new().write(bytes).finish()
+    }
+
+    fn ensure_ok<T: Default>(res: Result<T, AnyError>) -> T {
+        match res {
+            Ok(x) => x,
+            Err(err) => {
+                // NOTE: A broken code cache must never fail the module
+                // loading; V8 just compiles the module from scratch.
+                log::debug!("Error using code cache: {:#}", err);
+                T::default()
+            }
+        }
+    }
+
+    pub fn get_sync(&self, specifier: &str, source_hash: u64) -> Option<Vec<u8>> {
+        Self::ensure_ok(self.inner.get_sync(specifier, source_hash))
+    }
+
+    pub fn set_sync(&self, specifier: &str, source_hash: u64, data: &[u8]) {
+        Self::ensure_ok(self.inner.set_sync(specifier, source_hash, data));
+    }
+}
+
+#[derive(Clone)]
+struct CodeCacheInner {
+    conn: CacheDB,
+    max_bytes: i64,
+}
+
+impl CodeCacheInner {
+    pub fn new(conn: CacheDB, max_bytes: i64) -> Self {
+        Self { conn, max_bytes }
+    }
+
+    pub fn get_sync(&self, specifier: &str, source_hash: u64) -> Result<Option<Vec<u8>>, AnyError> {
+        let query = "
+      SELECT
+        data
+      FROM
+        codecache
+      WHERE
+        specifier=?1
+        AND source_hash=?2
+      LIMIT 1";
+        let res =
+            self.conn
+                .query_row(query, params![specifier, &source_hash.to_string()], |row| {
+                    let data: Vec<u8> = row.get(0)?;
+                    Ok(data)
+                })?;
+        Ok(res)
+    }
+
+    pub fn set_sync(&self, specifier: &str, source_hash: u64, data: &[u8]) -> Result<(), AnyError> {
+        if data.len() as i64 > self.max_bytes {
+            // NOTE: It would evict every other entry only to be evicted
+            // itself.
+            return Ok(());
+        }
+
+        let sql = "
+      INSERT OR REPLACE INTO
+        codecache (specifier, source_hash, data)
+      VALUES
+        (?1, ?2, ?3)";
+        self.conn
+            .execute(sql, params![specifier, &source_hash.to_string(), data])?;
+        self.evict()
+    }
+
+    /// Deletes the entries written the longest ago until the remaining ones
+    /// fit in the maximum size. Replacing an entry gives it a new rowid, so
+    /// the rowid order is the write order.
+    fn evict(&self) -> Result<(), AnyError> {
+        let sql = "
+      DELETE FROM
+        codecache
+      WHERE
+        rowid IN (
+          SELECT
+            rowid
+          FROM (
+            SELECT
+              rowid,
+              SUM(LENGTH(data)) OVER (ORDER BY rowid DESC) AS total
+            FROM
+              codecache
+          )
+          WHERE
+            total > ?1
+        )";
+        self.conn.execute(sql, params![self.max_bytes])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn code_cache(max_bytes: i64) -> CodeCache {
+        CodeCache::with_max_bytes(CacheDB::in_memory(&CODE_CACHE_DB, "1.0.0"), max_bytes)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let cache = code_cache(MAX_CODE_CACHE_BYTES);
+
+        assert_eq!(cache.get_sync("file:///a.js", 1), None);
+
+        cache.set_sync("file:///a.js", 1, &[1, 2, 3]);
+
+        assert_eq!(cache.get_sync("file:///a.js", 1), Some(vec![1, 2, 3]));
+        assert_eq!(cache.get_sync("file:///b.js", 1), None);
+    }
+
+    #[test]
+    fn test_invalidated_by_source_hash() {
+        let cache = code_cache(MAX_CODE_CACHE_BYTES);
+
+        cache.set_sync("file:///a.js", 1, &[1, 2, 3]);
+
+        assert_eq!(cache.get_sync("file:///a.js", 2), None);
+
+        cache.set_sync("file:///a.js", 2, &[4, 5]);
+
+        assert_eq!(cache.get_sync("file:///a.js", 1), None);
+        assert_eq!(cache.get_sync("file:///a.js", 2), Some(vec![4, 5]));
+    }
+
+    #[test]
+    fn test_evicts_oldest_entries_over_max_bytes() {
+        let cache = code_cache(10);
+
+        cache.set_sync("file:///a.js", 1, &[0; 4]);
+        cache.set_sync("file:///b.js", 1, &[0; 4]);
+
+        // rewriting an entry makes it the most recent one
+        cache.set_sync("file:///a.js", 1, &[1; 4]);
+        cache.set_sync("file:///c.js", 1, &[0; 4]);
+
+        assert_eq!(cache.get_sync("file:///b.js", 1), None);
+        assert_eq!(cache.get_sync("file:///a.js", 1), Some(vec![1; 4]));
+        assert_eq!(cache.get_sync("file:///c.js", 1), Some(vec![0; 4]));
+
+        // an entry bigger than the cache itself is not kept
+        cache.set_sync("file:///d.js", 1, &[0; 11]);
+
+        assert_eq!(cache.get_sync("file:///d.js", 1), None);
+        assert_eq!(cache.get_sync("file:///c.js", 1), Some(vec![0; 4]));
+    }
+}
@@ -1,6 +1,7 @@
 pub mod cache_db;
 pub mod caches;
 pub mod check;
+pub mod code_cache;
 pub mod common;
 pub mod deno_dir;
 pub mod disk_cache;
@@ -1,3 +1,4 @@
+use deno_core::serde_json::Value;
 use deno_core::Resource;
 use tokio_util::sync::CancellationToken;
 
@@ -15,5 +16,10 @@ impl ConnWatcher {
     }
 }
 
+/// A JSON value the main worker attaches to a request sent to a user worker.
+/// It travels alongside the connection of the request, never as a header.
+#[derive(Debug, Clone)]
+pub struct RequestContext(pub Value);
+
 #[derive(Clone)]
 pub struct DenoRuntimeDropToken(pub CancellationToken);
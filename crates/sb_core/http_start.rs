@@ -5,6 +5,7 @@ use deno_core::error::bad_resource;
 use deno_core::error::bad_resource_id;
 use deno_core::error::AnyError;
 use deno_core::op2;
+use deno_core::serde_json::Value;
 use deno_core::OpState;
 use deno_core::ResourceId;
 use deno_http::http_create_conn_resource;
@@ -19,7 +20,7 @@ use crate::net::TokioDuplexResource;
 fn op_http_start(
     state: &mut OpState,
     #[smi] stream_rid: ResourceId,
-) -> Result<(ResourceId, ResourceId), AnyError> {
+) -> Result<(ResourceId, ResourceId, Option<Value>), AnyError> {
     if let Ok(resource_rc) = state.resource_table.take::<TokioDuplexResource>(stream_rid) {
         // This connection might be used somewhere else. If it's the case, we cannot proceed with the
         // process of starting a HTTP server on top of this connection, so we just return a bad
@@ -27,7 +28,7 @@ fn op_http_start(
         let resource = Rc::try_unwrap(resource_rc)
             .map_err(|_| bad_resource("Duplex stream is currently in use"))?;
 
-        let (id, stream) = resource.into_inner();
+        let (id, stream, context) = resource.into_inner();
         let token = state
             .borrow_mut::<HashMap<usize, CancellationToken>>()
             .remove(&id);
@@ -43,7 +44,7 @@ fn op_http_start(
 
         let conn_watcher = state.resource_table.add(ConnWatcher(token));
 
-        return Ok((conn, conn_watcher));
+        return Ok((conn, conn_watcher, context.map(|it| it.0)));
     }
 
     Err(bad_resource_id())
@@ -46,6 +46,8 @@ import * as performance from 'ext:deno_web/15_performance.js';
 import * as messagePort from 'ext:deno_web/13_message_port.js';
 import { SupabaseEventListener } from 'ext:sb_user_event_worker/event_worker.js';
 import * as MainWorker from 'ext:sb_core_main_js/js/main_worker.js';
+import { getServiceBindings } from 'ext:sb_user_workers/user_workers.js';
+import { getRequestContext } from 'ext:sb_core_main_js/js/http.js';
 import * as DenoWebCompression from 'ext:deno_web/14_compression.js';
 import * as DenoWSStream from 'ext:deno_websocket/02_websocketstream.js';
 import * as eventSource from 'ext:deno_fetch/27_eventsource.js';
@@ -59,19 +61,136 @@ let globalThis_;
 const ops = core.ops;
 const {
 	Error,
+	ErrorCaptureStackTrace,
 	ArrayPrototypePop,
 	ArrayPrototypeShift,
 	ObjectAssign,
 	ObjectKeys,
 	ObjectDefineProperty,
 	ObjectDefineProperties,
+	ObjectGetPrototypeOf,
+	ObjectPrototype,
 	ObjectSetPrototypeOf,
+	PromisePrototypeThen,
+	PromiseResolve,
+	RegExpPrototypeExec,
 	SafeSet,
 	StringPrototypeIncludes,
 	StringPrototypeSplit,
 	StringPrototypeTrim
 } = primordials;
 
+// Keeps the worker alive until the given promise settles, as long as the
+// background budget of the worker allows.
+function waitUntil(maybePromise) {
+	ops.op_wait_until_enter();
+
+	PromisePrototypeThen(
+		PromiseResolve(maybePromise),
+		() => ops.op_wait_until_leave(),
+		(err) => {
+			ops.op_wait_until_leave();
+			globalThis.console.error('waitUntil promise rejected:', err);
+		},
+	);
+}
+
+// Levels passed by `deno_console` to the print function of a console.
+const CONSOLE_LOG_LEVELS = {
+	debug: 0,
+	log: 1,
+	info: 1,
+	warn: 2,
+	error: 3,
+};
+
+const CALL_SITE_PATTERN = /\(?([^\s()]+):(\d+):(\d+)\)?$/;
+
+function isPlainObject(value) {
+	if (value === null || typeof value !== 'object') {
+		return false;
+	}
+
+	const proto = ObjectGetPrototypeOf(value);
+	return proto === ObjectPrototype || proto === null;
+}
+
+function getCallSite(callee) {
+	const holder = {};
+	const stackTraceLimit = Error.stackTraceLimit;
+
+	Error.stackTraceLimit = 1;
+	ErrorCaptureStackTrace(holder, callee);
+	Error.stackTraceLimit = stackTraceLimit;
+
+	const frame = StringPrototypeSplit(holder.stack ?? '', '\n')[1];
+	const match = frame ? RegExpPrototypeExec(CALL_SITE_PATTERN, StringPrototypeTrim(frame)) : null;
+
+	if (!match) {
+		return null;
+	}
+
+	return {
+		file: match[1],
+		line: +match[2],
+		column: +match[3],
+	};
+}
+
+// Creates the console of a user worker, which sends what is printed to the
+// events worker along with its level, call site and, if a plain object is
+// the sole argument, its fields.
+function createUserWorkerConsole() {
+	let minLevel = null;
+	let pendingOptions = null;
+
+	const userConsole = new console.Console((msg, level) => {
+		const options = pendingOptions;
+
+		pendingOptions = null;
+
+		try {
+			return ops.op_user_worker_log(msg, level, options);
+		} catch (err) {
+			if (options?.fields) {
+				// NOTE: Fields that can't be serialized are dropped rather
+				// than the message itself.
+				return ops.op_user_worker_log(msg, level, { ...options, fields: null });
+			}
+
+			throw err;
+		}
+	});
+
+	for (const name of ObjectKeys(CONSOLE_LOG_LEVELS)) {
+		const level = CONSOLE_LOG_LEVELS[name];
+		const method = userConsole[name];
+
+		const patchedMethod = function (...args) {
+			minLevel ??= ops.op_user_worker_log_level();
+
+			if (level < minLevel) {
+				return;
+			}
+
+			pendingOptions = {
+				source: getCallSite(patchedMethod),
+				fields: args.length === 1 && isPlainObject(args[0]) ? args[0] : null,
+			};
+
+			try {
+				return method.apply(this, args);
+			} finally {
+				pendingOptions = null;
+			}
+		};
+
+		userConsole[name] = patchedMethod;
+	}
+
+	return userConsole;
+}
+
 let image;
 function ImageNonEnumerable(getter) {
 	let valueIsSet = false;
@@ -522,13 +641,22 @@ globalThis.bootstrapSBEdge = (opts, extraCtx) => {
 	if (isUserWorker) {
 		delete globalThis.EdgeRuntime;
 
+		ObjectDefineProperty(globalThis, 'EdgeRuntime', {
+			get() {
+				return {
+					waitUntil,
+					getRequestContext,
+					get bindings() {
+						return getServiceBindings();
+					},
+				};
+			},
+			configurable: true,
+		});
+
 		// override console
 		ObjectDefineProperties(globalThis, {
-			console: nonEnumerable(
-				new console.Console((msg, level) => {
-					return ops.op_user_worker_log(msg, level > 1);
-				}),
-			),
+			console: nonEnumerable(createUserWorkerConsole()),
 		});
 	
 		const apiNames = ObjectKeys(PATCH_DENO_API_LIST);
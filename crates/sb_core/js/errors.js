@@ -27,6 +27,9 @@ const buildDomErrorClass = (name) => class extends DOMException {
 const InvalidWorkerResponse = buildErrorClass("InvalidWorkerResponse");
 const InvalidWorkerCreation = buildErrorClass("InvalidWorkerCreation");
 const WorkerRequestCancelled = buildErrorClass("WorkerRequestCancelled");
+const WorkerRequestTimedOut = buildErrorClass("WorkerRequestTimedOut");
+const WorkerHeapSnapshotFailed = buildErrorClass("WorkerHeapSnapshotFailed");
+const WorkerCpuProfileFailed = buildErrorClass("WorkerCpuProfileFailed");
 const NotFound = buildErrorClass("NotFound");
 const PermissionDenied = buildErrorClass("PermissionDenied");
 const ConnectionRefused = buildErrorClass("ConnectionRefused");
@@ -57,6 +60,9 @@ function registerErrors() {
     core.registerErrorClass("InvalidWorkerResponse", InvalidWorkerResponse);
     core.registerErrorClass("InvalidWorkerCreation", InvalidWorkerCreation);
     core.registerErrorClass("WorkerRequestCancelled", WorkerRequestCancelled);
+    core.registerErrorClass("WorkerRequestTimedOut", WorkerRequestTimedOut);
+    core.registerErrorClass("WorkerHeapSnapshotFailed", WorkerHeapSnapshotFailed);
+    core.registerErrorClass("WorkerCpuProfileFailed", WorkerCpuProfileFailed);
     core.registerErrorClass("NotFound", NotFound);
     core.registerErrorClass("PermissionDenied", PermissionDenied);
     core.registerErrorClass("ConnectionRefused", ConnectionRefused);
@@ -9,7 +9,7 @@ import { upgradeWebSocket } from "ext:deno_http/02_websocket.ts";
 const ops = core.ops;
 
 const { internalRidSymbol } = core;
-const { ObjectPrototypeIsPrototypeOf } = primordials;
+const { ObjectFreeze, ObjectPrototypeIsPrototypeOf, ObjectValues } = primordials;
 
 const HttpConnPrototypeNextRequest = HttpConn.prototype.nextRequest;
 const HttpConnPrototypeClose = HttpConn.prototype.close;
@@ -53,7 +53,8 @@ function internalServerError() {
 function serveHttp(conn) {
 	let closed = false;
 
-	const [connRid, watcherRid] = ops.op_http_start(conn[internalRidSymbol]);
+	const [connRid, watcherRid, context] = ops.op_http_start(conn[internalRidSymbol]);
+	const requestContext = deepFreeze(context);
 	const httpConn = new HttpConn(connRid, conn.remoteAddr, conn.localAddr);
 	
 	httpConn.nextRequest = async () => {
@@ -65,7 +66,8 @@ function serveHttp(conn) {
 
 		nextRequest.request[kSupabaseTag] = {
 			watcherRid,
-			streamRid: nextRequest.streamRid
+			streamRid: nextRequest.streamRid,
+			context: requestContext,
 		};
 
 		return nextRequest;
@@ -226,6 +228,26 @@ function getSupabaseTag(request) {
 	return request[kSupabaseTag];
 }
 
+function deepFreeze(value) {
+	if (value !== null && typeof value === "object") {
+		for (const item of ObjectValues(value)) {
+			deepFreeze(item);
+		}
+
+		ObjectFreeze(value);
+	}
+
+	return value;
+}
+
+/**
+ * Returns the context the main worker has passed along with `request`, or
+ * `null` if there is none. It can't be modified.
+ */
+function getRequestContext(request) {
+	return request?.[kSupabaseTag]?.context ?? null;
+}
+
 function applySupabaseTag(src, dest) {
 	if (
 		!ObjectPrototypeIsPrototypeOf(RequestPrototype, src) 
@@ -245,5 +267,6 @@ export {
 	serveHttp,
 	getSupabaseTag,
 	applySupabaseTag,
+	getRequestContext,
 	upgradeWebSocket
 };
@@ -9,11 +9,12 @@ use deno_core::v8;
 use deno_core::OpState;
 use deno_core::{op2, JsRuntime};
 use enum_as_inner::EnumAsInner;
+use event_worker::channel::{DroppedEvents, EventDropCounters};
 use futures::task::AtomicWaker;
 use futures::FutureExt;
 use log::error;
 use serde::Serialize;
-use tokio::sync::oneshot;
+use tokio::sync::{oneshot, Notify};
 
 mod upgrade;
 
@@ -41,6 +42,43 @@ impl From<Arc<AtomicWaker>> for MemCheckWaker {
     }
 }
 
+/// Tracks the promises passed to `EdgeRuntime.waitUntil` that have not
+/// settled yet.
+#[derive(Debug, Default, Clone)]
+pub struct WaitUntilTracker {
+    pending: Arc<AtomicUsize>,
+    notify: Arc<Notify>,
+}
+
+impl WaitUntilTracker {
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Acquire)
+    }
+
+    fn enter(&self) {
+        self.pending.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn leave(&self) {
+        if self.pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.notify.notify_waiters();
+        }
+    }
+
+    /// Resolves once every tracked promise has settled.
+    pub async fn settled(&self) {
+        loop {
+            let notified = self.notify.notified();
+
+            if self.pending() == 0 {
+                return;
+            }
+
+            notified.await;
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct SharedMetricSource {
     active_user_workers: Arc<AtomicUsize>,
@@ -48,9 +86,23 @@ pub struct SharedMetricSource {
     received_requests: Arc<AtomicUsize>,
     handled_requests: Arc<AtomicUsize>,
     active_io: Arc<AtomicUsize>,
+    queued_requests: Arc<AtomicUsize>,
+    dequeued_requests: Arc<AtomicUsize>,
+    rejected_requests: Arc<AtomicUsize>,
+    request_queue_wait_time_ms: Arc<AtomicUsize>,
+    event_drop_counters: Option<EventDropCounters>,
 }
 
 impl SharedMetricSource {
+    /// Reports the events dropped by the event channels along with the other
+    /// metrics.
+    pub fn with_event_drop_counters(self, counters: Option<EventDropCounters>) -> Self {
+        Self {
+            event_drop_counters: counters,
+            ..self
+        }
+    }
+
     pub fn active_io(&self) -> usize {
         self.active_io.load(Ordering::Relaxed)
     }
@@ -91,12 +143,45 @@ impl SharedMetricSource {
         self.active_io.fetch_sub(1, Ordering::Relaxed);
     }
 
+    pub fn queued_requests(&self) -> usize {
+        self.queued_requests.load(Ordering::Relaxed)
+    }
+
+    pub fn incl_queued_requests(&self) {
+        self.queued_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a request that left the queue to be served, along with the
+    /// time it spent waiting.
+    pub fn incl_dequeued_requests(&self, wait_time_ms: usize) {
+        self.queued_requests.fetch_sub(1, Ordering::Relaxed);
+        self.dequeued_requests.fetch_add(1, Ordering::Relaxed);
+        self.request_queue_wait_time_ms
+            .fetch_add(wait_time_ms, Ordering::Relaxed);
+    }
+
+    /// Records a request that left the queue without being served, either
+    /// because it was evicted, timed out or its receiver went away.
+    pub fn incl_rejected_queued_requests(&self) {
+        self.queued_requests.fetch_sub(1, Ordering::Relaxed);
+        self.rejected_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a request that was rejected before it could enter the queue.
+    pub fn incl_rejected_requests(&self) {
+        self.rejected_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
     pub fn reset(&self) {
         self.active_user_workers.store(0, Ordering::Relaxed);
         self.retired_user_workers.store(0, Ordering::Relaxed);
         self.received_requests.store(0, Ordering::Relaxed);
         self.handled_requests.store(0, Ordering::Relaxed);
         self.active_io.store(0, Ordering::Relaxed);
+        self.queued_requests.store(0, Ordering::Relaxed);
+        self.dequeued_requests.store(0, Ordering::Relaxed);
+        self.rejected_requests.store(0, Ordering::Relaxed);
+        self.request_queue_wait_time_ms.store(0, Ordering::Relaxed);
     }
 }
 
@@ -230,6 +315,11 @@ struct RuntimeSharedStatistics {
     retired_user_workers_count: usize,
     received_requests_count: usize,
     handled_requests_count: usize,
+    queued_requests_count: usize,
+    dequeued_requests_count: usize,
+    rejected_requests_count: usize,
+    request_queue_wait_time_ms: usize,
+    dropped_events: Vec<DroppedEvents>,
 }
 
 impl RuntimeSharedStatistics {
@@ -239,6 +329,15 @@ impl RuntimeSharedStatistics {
             retired_user_workers_count: src.retired_user_workers.load(Ordering::Relaxed),
             received_requests_count: src.received_requests.load(Ordering::Relaxed),
             handled_requests_count: src.handled_requests.load(Ordering::Relaxed),
+            queued_requests_count: src.queued_requests.load(Ordering::Relaxed),
+            dequeued_requests_count: src.dequeued_requests.load(Ordering::Relaxed),
+            rejected_requests_count: src.rejected_requests.load(Ordering::Relaxed),
+            request_queue_wait_time_ms: src.request_queue_wait_time_ms.load(Ordering::Relaxed),
+            dropped_events: src
+                .event_drop_counters
+                .as_ref()
+                .map(EventDropCounters::dropped_events)
+                .unwrap_or_default(),
         }
     }
 }
@@ -293,6 +392,20 @@ fn op_schedule_mem_check(state: &mut OpState) -> Result<(), AnyError> {
     Ok(())
 }
 
+#[op2(fast)]
+fn op_wait_until_enter(state: &mut OpState) {
+    if let Some(tracker) = state.try_borrow::<WaitUntilTracker>() {
+        tracker.enter();
+    }
+}
+
+#[op2(fast)]
+fn op_wait_until_leave(state: &mut OpState) {
+    if let Some(tracker) = state.try_borrow::<WaitUntilTracker>() {
+        tracker.leave();
+    }
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct MemoryUsage {
@@ -358,6 +471,8 @@ deno_core::extension!(
         op_set_exit_code,
         op_runtime_metrics,
         op_schedule_mem_check,
+        op_wait_until_enter,
+        op_wait_until_leave,
         op_runtime_memory_usage,
         op_set_raw,
         op_bootstrap_unstable_args
@@ -376,3 +491,50 @@ deno_core::extension!(
         "js/01_http.js"
     ]
 );
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::time::timeout;
+
+    use super::WaitUntilTracker;
+
+    #[tokio::test]
+    async fn test_wait_until_settled_without_pending_promises() {
+        let tracker = WaitUntilTracker::default();
+
+        assert_eq!(tracker.pending(), 0);
+        assert!(timeout(Duration::from_millis(100), tracker.settled())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_settled_after_every_leave() {
+        let tracker = WaitUntilTracker::default();
+
+        tracker.enter();
+        tracker.enter();
+
+        assert_eq!(tracker.pending(), 2);
+
+        let settled = tokio::spawn({
+            let tracker = tracker.clone();
+            async move { tracker.settled().await }
+        });
+
+        tracker.leave();
+
+        assert_eq!(tracker.pending(), 1);
+        assert!(timeout(Duration::from_millis(100), tracker.settled())
+            .await
+            .is_err());
+        assert!(!settled.is_finished());
+
+        tracker.leave();
+
+        assert_eq!(tracker.pending(), 0);
+        assert!(timeout(Duration::from_secs(1), settled).await.is_ok());
+    }
+}
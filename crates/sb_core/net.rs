@@ -20,27 +20,37 @@ use tokio_util::sync::CancellationToken;
 use tracing::span;
 use tracing::Level;
 
-use crate::conn_sync::DenoRuntimeDropToken;
+use crate::conn_sync::{DenoRuntimeDropToken, RequestContext};
+
+/// A connection sent to a worker, along with the token that is cancelled
+/// when it is closed and the context of its request.
+pub type DuplexStreamEntry = (
+    io::DuplexStream,
+    Option<CancellationToken>,
+    Option<RequestContext>,
+);
 
 pub struct TokioDuplexResource {
     id: usize,
     rw: AsyncRefCell<io::DuplexStream>,
+    context: Option<RequestContext>,
     cancel_handle: CancelHandle,
 }
 
 impl TokioDuplexResource {
-    pub fn new(rw: io::DuplexStream) -> Self {
+    pub fn new(rw: io::DuplexStream, context: Option<RequestContext>) -> Self {
         static COUNTER: AtomicUsize = AtomicUsize::new(0);
 
         Self {
             id: COUNTER.fetch_add(1, Ordering::SeqCst),
             rw: rw.into(),
+            context,
             cancel_handle: CancelHandle::default(),
         }
     }
 
-    pub fn into_inner(self) -> (usize, io::DuplexStream) {
-        (self.id, self.rw.into_inner())
+    pub fn into_inner(self) -> (usize, io::DuplexStream, Option<RequestContext>) {
+        (self.id, self.rw.into_inner(), self.context)
     }
 
     pub fn cancel_read_ops(&self) {
@@ -103,9 +113,7 @@ pub async fn op_net_accept(
         let mut op_state = state.borrow_mut();
 
         (
-            op_state
-                .try_take::<mpsc::UnboundedReceiver<(io::DuplexStream, Option<CancellationToken>)>>(
-                ),
+            op_state.try_take::<mpsc::UnboundedReceiver<DuplexStreamEntry>>(),
             op_state
                 .try_borrow::<DenoRuntimeDropToken>()
                 .cloned()
@@ -122,17 +130,15 @@ pub async fn op_net_accept(
         let state = state.clone();
         move |value| {
             let mut op_state = state.borrow_mut();
-            op_state.put::<mpsc::UnboundedReceiver<(io::DuplexStream, Option<CancellationToken>)>>(
-                value,
-            );
+            op_state.put::<mpsc::UnboundedReceiver<DuplexStreamEntry>>(value);
         }
     });
 
-    let Some((stream, conn_token)) = rx.recv().await else {
+    let Some((stream, conn_token, context)) = rx.recv().await else {
         return Err(bad_resource("duplex stream channel is closed"));
     };
 
-    let resource = TokioDuplexResource::new(stream);
+    let resource = TokioDuplexResource::new(stream, context);
     let id = resource.id;
 
     // since the op state was dropped before,
@@ -0,0 +1,292 @@
+use crate::DecoratorType;
+use deno_config::JsxImportSourceConfig;
+use deno_core::serde_json;
+use deno_core::url::Url;
+use eszip::EszipV2;
+use glob::glob;
+use log::{debug, error};
+use sb_core::cache::common::FastInsecureHasher;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// bump this version to invalidate the entries stored on disk
+const ESZIP_CACHE_VERSION: &str = "v1";
+
+/// Everything that affects how the eszip of a service is built.
+#[derive(Debug, Clone)]
+pub struct EszipCacheKey {
+    pub service_path: PathBuf,
+    pub maybe_entrypoint: Option<String>,
+    pub maybe_import_map_path: Option<String>,
+    pub maybe_decorator: Option<DecoratorType>,
+    pub maybe_jsx_import_source_config: Option<JsxImportSourceConfig>,
+    pub static_patterns: Vec<String>,
+    pub allow_remote_modules: bool,
+}
+
+impl EszipCacheKey {
+    fn hash(&self) -> u64 {
+        let mut hasher = FastInsecureHasher::new();
+
+        hasher
+            .write_str(ESZIP_CACHE_VERSION)
+            .write_hashable(&self.service_path)
+            .write_hashable(&self.maybe_entrypoint)
+            .write_hashable(&self.maybe_import_map_path)
+            .write_hashable(self.maybe_decorator.map(|it| it as u8))
+            .write_hashable(&self.static_patterns)
+            .write_hashable(self.allow_remote_modules);
+
+        if let Some(config) = self.maybe_jsx_import_source_config.as_ref() {
+            hasher
+                .write_hashable(&config.default_specifier)
+                .write_hashable(&config.default_types_specifier)
+                .write_str(&config.module)
+                .write_str(config.base_url.as_str());
+        }
+
+        hasher.finish()
+    }
+
+    /// Local files whose contents are not covered by the eszip modules, such
+    /// as the import map and the files matched by the static patterns.
+    fn extra_sources(&self) -> Vec<PathBuf> {
+        let mut sources = vec![];
+
+        if let Some(path) = self.maybe_import_map_path.as_ref() {
+            if !path.starts_with("data:") {
+                sources.push(PathBuf::from(path));
+            }
+        }
+
+        for pattern in &self.static_patterns {
+            let Ok(paths) = glob(pattern) else {
+                continue;
+            };
+
+            sources.extend(paths.flatten().filter(|it| it.is_file()));
+        }
+
+        sources
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EszipCacheEntry {
+    sources: Vec<PathBuf>,
+    source_hash: u64,
+    #[serde(skip)]
+    bytes: Option<Arc<[u8]>>,
+}
+
+/// A cache of built eszips, so that workers created again for the same
+/// service don't have to walk the module graph and emit its sources again.
+///
+/// Entries are keyed by [`EszipCacheKey`] and validated against a hash of the
+/// contents of the local source files that went into the eszip. Remote
+/// modules are not validated; they follow the module cache instead.
+pub struct EszipCache {
+    entries: Mutex<HashMap<u64, EszipCacheEntry>>,
+    maybe_disk_dir: Option<PathBuf>,
+}
+
+impl EszipCache {
+    pub fn new(maybe_disk_dir: Option<PathBuf>) -> Self {
+        Self {
+            entries: Mutex::default(),
+            maybe_disk_dir,
+        }
+    }
+
+    /// Returns the bytes of the cached eszip if the sources it was built from
+    /// have not changed since.
+    pub fn get(&self, key: &EszipCacheKey) -> Option<Arc<[u8]>> {
+        let key_hash = key.hash();
+        let maybe_entry = self.entries.lock().unwrap().get(&key_hash).cloned();
+        let entry = match maybe_entry {
+            Some(entry) => entry,
+            None => self.read_from_disk(key_hash)?,
+        };
+
+        if hash_sources(&entry.sources) != Some(entry.source_hash) {
+            debug!("eszip cache is stale: {}", key.service_path.display());
+            self.remove(key_hash);
+            return None;
+        }
+
+        entry.bytes
+    }
+
+    /// Stores the given eszip and returns its bytes.
+    pub fn insert(&self, key: &EszipCacheKey, eszip: EszipV2) -> Arc<[u8]> {
+        let mut sources = eszip
+            .specifiers()
+            .iter()
+            .filter(|it| it.starts_with("file:"))
+            .filter_map(|it| Url::parse(it).ok()?.to_file_path().ok())
+            .collect::<Vec<_>>();
+
+        sources.extend(key.extra_sources());
+        sources.sort();
+        sources.dedup();
+
+        let bytes: Arc<[u8]> = eszip.into_bytes().into();
+        let Some(source_hash) = hash_sources(&sources) else {
+            return bytes;
+        };
+
+        let key_hash = key.hash();
+        let entry = EszipCacheEntry {
+            sources,
+            source_hash,
+            bytes: Some(bytes.clone()),
+        };
+
+        self.write_to_disk(key_hash, &entry);
+        self.entries.lock().unwrap().insert(key_hash, entry);
+
+        bytes
+    }
+
+    fn remove(&self, key_hash: u64) {
+        self.entries.lock().unwrap().remove(&key_hash);
+
+        if let Some(dir) = self.maybe_disk_dir.as_ref() {
+            let (eszip_path, meta_path) = disk_paths(dir, key_hash);
+            let _ = fs::remove_file(eszip_path);
+            let _ = fs::remove_file(meta_path);
+        }
+    }
+
+    fn read_from_disk(&self, key_hash: u64) -> Option<EszipCacheEntry> {
+        let (eszip_path, meta_path) = disk_paths(self.maybe_disk_dir.as_ref()?, key_hash);
+        let mut entry =
+            serde_json::from_slice::<EszipCacheEntry>(&fs::read(meta_path).ok()?).ok()?;
+
+        entry.bytes = Some(fs::read(eszip_path).ok()?.into());
+        self.entries.lock().unwrap().insert(key_hash, entry.clone());
+
+        Some(entry)
+    }
+
+    fn write_to_disk(&self, key_hash: u64, entry: &EszipCacheEntry) {
+        let (Some(dir), Some(bytes)) = (self.maybe_disk_dir.as_ref(), entry.bytes.as_ref()) else {
+            return;
+        };
+
+        let (eszip_path, meta_path) = disk_paths(dir, key_hash);
+        let result = fs::create_dir_all(dir)
+            .and_then(|_| fs::write(eszip_path, bytes))
+            .and_then(|_| fs::write(meta_path, serde_json::to_vec(entry).unwrap_or_default()));
+
+        if let Err(err) = result {
+            error!("failed to write eszip cache: {}", err);
+        }
+    }
+}
+
+fn disk_paths(dir: &Path, key_hash: u64) -> (PathBuf, PathBuf) {
+    (
+        dir.join(format!("{:016x}.eszip", key_hash)),
+        dir.join(format!("{:016x}.json", key_hash)),
+    )
+}
+
+fn hash_sources(sources: &[PathBuf]) -> Option<u64> {
+    let mut hasher = FastInsecureHasher::new();
+
+    for path in sources {
+        let content = fs::read(path).ok()?;
+
+        hasher.write_hashable(path).write(&content);
+    }
+
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EszipCache, EszipCacheKey};
+    use eszip::EszipV2;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sb_eszip_cache_{}_{}", name, std::process::id()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn key(import_map_path: &str) -> EszipCacheKey {
+        EszipCacheKey {
+            service_path: PathBuf::from("./test_cases/main"),
+            maybe_entrypoint: None,
+            maybe_import_map_path: Some(import_map_path.to_string()),
+            maybe_decorator: None,
+            maybe_jsx_import_source_config: None,
+            static_patterns: vec![],
+            allow_remote_modules: true,
+        }
+    }
+
+    #[test]
+    fn test_eszip_cache_invalidated_by_source_change() {
+        let dir = temp_dir("invalidate");
+        let import_map_path = dir.join("import_map.json");
+
+        std::fs::write(&import_map_path, r#"{"imports":{}}"#).unwrap();
+
+        let cache = EszipCache::new(None);
+        let key = key(import_map_path.to_str().unwrap());
+
+        assert!(cache.get(&key).is_none());
+
+        let bytes = cache.insert(&key, EszipV2::default());
+
+        assert_eq!(cache.get(&key).as_deref(), Some(&*bytes));
+
+        std::fs::write(&import_map_path, r#"{"imports":{"a":"./a.ts"}}"#).unwrap();
+
+        assert!(cache.get(&key).is_none());
+    }
+
+    #[test]
+    fn test_eszip_cache_reads_from_disk() {
+        let dir = temp_dir("disk");
+        let import_map_path = dir.join("import_map.json");
+
+        std::fs::write(&import_map_path, r#"{"imports":{}}"#).unwrap();
+
+        let key = key(import_map_path.to_str().unwrap());
+        let bytes = EszipCache::new(Some(dir.join("cache"))).insert(&key, EszipV2::default());
+
+        let cache = EszipCache::new(Some(dir.join("cache")));
+
+        assert_eq!(cache.get(&key).as_deref(), Some(&*bytes));
+    }
+
+    #[test]
+    fn test_eszip_cache_keyed_by_remote_module_access() {
+        let dir = temp_dir("remote");
+        let import_map_path = dir.join("import_map.json");
+
+        std::fs::write(&import_map_path, r#"{"imports":{}}"#).unwrap();
+
+        let cache = EszipCache::new(None);
+        let key = key(import_map_path.to_str().unwrap());
+
+        cache.insert(&key, EszipV2::default());
+
+        let key = EszipCacheKey {
+            allow_remote_modules: false,
+            ..key
+        };
+
+        assert!(cache.get(&key).is_none());
+    }
+}
@@ -21,6 +21,7 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 pub mod emitter;
+pub mod eszip_cache;
 pub mod graph_fs;
 pub mod graph_resolver;
 pub mod graph_util;
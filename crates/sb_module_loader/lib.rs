@@ -1,4 +1,4 @@
-use deno_core::{FastString, ModuleLoader};
+use deno_core::{FastString, ModuleLoader, SourceMapGetter};
 use deno_npm::resolution::ValidSerializedNpmResolutionSnapshot;
 use sb_fs::virtual_fs::FileBackedVfs;
 use sb_fs::EszipStaticFiles;
@@ -15,6 +15,7 @@ pub mod util;
 pub struct RuntimeProviders {
     pub npm_resolver: Arc<dyn NpmResolver>,
     pub module_loader: Rc<dyn ModuleLoader>,
+    pub source_map_getter: Rc<dyn SourceMapGetter>,
     pub vfs: Arc<FileBackedVfs>,
     pub module_code: Option<FastString>,
     pub static_files: EszipStaticFiles,
@@ -2,7 +2,9 @@ use crate::metadata::Metadata;
 use crate::node::cjs_code_anaylzer::CliCjsCodeAnalyzer;
 use crate::node::cli_node_resolver::CliNodeResolver;
 use crate::node::node_module_loader::{CjsResolutionStore, NpmModuleLoader};
-use crate::standalone::standalone_module_loader::{EmbeddedModuleLoader, SharedModuleLoaderState};
+use crate::standalone::standalone_module_loader::{
+    EmbeddedModuleLoader, EszipSourceMapGetter, SharedModuleLoaderState,
+};
 use crate::RuntimeProviders;
 use anyhow::Context;
 use deno_core::error::AnyError;
@@ -12,6 +14,7 @@ use deno_tls::rustls::RootCertStore;
 use deno_tls::RootCertStoreProvider;
 use import_map::{parse_from_json, ImportMap};
 use sb_core::cache::caches::Caches;
+use sb_core::cache::code_cache::CodeCache;
 use sb_core::cache::deno_dir::DenoDirProvider;
 use sb_core::cache::node::NodeAnalysisCache;
 use sb_core::cache::CacheSetting;
@@ -155,6 +158,7 @@ pub async fn create_module_loader_for_eszip(
     let cjs_resolutions = Arc::new(CjsResolutionStore::default());
     let cache_db = Caches::new(deno_dir_provider.clone());
     let node_analysis_cache = NodeAnalysisCache::new(cache_db.node_analysis_db());
+    let code_cache = Arc::new(CodeCache::new(cache_db.code_cache_db()));
     let cjs_esm_code_analyzer = CliCjsCodeAnalyzer::new(node_analysis_cache, fs.clone());
     let node_code_translator = Arc::new(NodeCodeTranslator::new(
         cjs_esm_code_analyzer,
@@ -186,6 +190,7 @@ pub async fn create_module_loader_for_eszip(
                 fs.clone(),
                 cli_node_resolver,
             )),
+            maybe_code_cache: Some(code_cache),
         }),
     };
 
@@ -194,6 +199,7 @@ pub async fn create_module_loader_for_eszip(
             shared: module_loader_factory.shared.clone(),
             include_source_map,
         }),
+        source_map_getter: Rc::new(EszipSourceMapGetter(module_loader_factory.shared.clone())),
         npm_resolver: npm_resolver.into_npm_resolver(),
         vfs,
         module_code: code_fs,
@@ -9,10 +9,14 @@ use deno_core::error::AnyError;
 use deno_core::futures::FutureExt;
 use deno_core::ModuleType;
 use deno_core::ResolutionKind;
-use deno_core::{ModuleLoader, ModuleSourceCode};
+use deno_core::{ModuleLoader, ModuleSourceCode, SourceCodeCacheInfo, SourceMapGetter};
 use deno_core::{ModuleSpecifier, RequestedModuleType};
 use deno_semver::npm::NpmPackageReqReference;
 use eszip::deno_graph;
+use sb_core::cache::code_cache::CodeCache;
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 use tracing::instrument;
 
@@ -25,6 +29,31 @@ pub struct SharedModuleLoaderState {
     pub(crate) mapped_specifier_resolver: MappedSpecifierResolver,
     pub(crate) npm_module_loader: Arc<NpmModuleLoader>,
     pub(crate) node_resolver: Arc<CliNodeResolver>,
+    pub(crate) maybe_code_cache: Option<Arc<CodeCache>>,
+}
+
+/// Looks up the source maps retained in the eszip, so that the locations in
+/// stack traces point to the original sources.
+pub struct EszipSourceMapGetter(pub(crate) Arc<SharedModuleLoaderState>);
+
+impl SourceMapGetter for EszipSourceMapGetter {
+    fn get_source_map(&self, file_name: &str) -> Option<Vec<u8>> {
+        let module = self.0.eszip.get_module(file_name)?;
+
+        // NOTE: The eszip has been fully loaded by the time any code runs, so
+        // the source map is already available.
+        let source_map = module.source_map().now_or_never()??;
+
+        if source_map.is_empty() {
+            return None;
+        }
+
+        Some(source_map.to_vec())
+    }
+
+    fn get_source_line(&self, _file_name: &str, _line_number: usize) -> Option<String> {
+        None
+    }
 }
 
 #[derive(Clone)]
@@ -160,6 +189,7 @@ impl ModuleLoader for EmbeddedModuleLoader {
         let original_specifier = original_specifier.clone();
         let found_specifier =
             ModuleSpecifier::parse(&module.specifier).expect("invalid url in eszip");
+        let maybe_code_cache = self.shared.maybe_code_cache.clone();
 
         deno_core::ModuleLoadResponse::Async(
             async move {
@@ -193,24 +223,53 @@ impl ModuleLoader for EmbeddedModuleLoader {
                     Arc::from(src)
                 };
 
+                let module_type = match module.kind {
+                    eszip::ModuleKind::JavaScript => ModuleType::JavaScript,
+                    eszip::ModuleKind::Json => ModuleType::Json,
+                    eszip::ModuleKind::Jsonc => {
+                        return Err(type_error("jsonc modules not supported"))
+                    }
+                    eszip::ModuleKind::OpaqueData => {
+                        unreachable!();
+                    }
+                };
+
+                let maybe_code_cache_info = match maybe_code_cache {
+                    Some(code_cache) if module_type == ModuleType::JavaScript => {
+                        let hash =
+                            CodeCache::compute_source_hash(maybe_code_with_source_map.as_bytes());
+                        let data = code_cache
+                            .get_sync(found_specifier.as_str(), hash)
+                            .map(Cow::Owned);
+
+                        Some(SourceCodeCacheInfo { hash, data })
+                    }
+
+                    _ => None,
+                };
+
                 Ok(deno_core::ModuleSource::new_with_redirect(
-                    match module.kind {
-                        eszip::ModuleKind::JavaScript => ModuleType::JavaScript,
-                        eszip::ModuleKind::Json => ModuleType::Json,
-                        eszip::ModuleKind::Jsonc => {
-                            return Err(type_error("jsonc modules not supported"))
-                        }
-                        eszip::ModuleKind::OpaqueData => {
-                            unreachable!();
-                        }
-                    },
+                    module_type,
                     ModuleSourceCode::String(maybe_code_with_source_map.into()),
                     &original_specifier,
                     &found_specifier,
-                    None,
+                    maybe_code_cache_info,
                 ))
             }
             .boxed_local(),
         )
     }
+
+    fn code_cache_ready(
+        &self,
+        module_specifier: ModuleSpecifier,
+        hash: u64,
+        code_cache: &[u8],
+    ) -> Pin<Box<dyn Future<Output = ()>>> {
+        if let Some(cache) = self.shared.maybe_code_cache.as_ref() {
+            cache.set_sync(module_specifier.as_str(), hash, code_cache);
+        }
+
+        std::future::ready(()).boxed_local()
+    }
 }
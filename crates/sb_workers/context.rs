@@ -1,14 +1,23 @@
-use anyhow::{anyhow, Error};
+use anyhow::{anyhow, bail, Error};
 use deno_config::JsxImportSourceConfig;
 use deno_core::FastString;
 use enum_as_inner::EnumAsInner;
-use event_worker::events::{UncaughtExceptionEvent, WorkerEventWithMetadata};
+use event_worker::channel::{EventReceiver, EventSender};
+use event_worker::events::{LogLevel, UncaughtExceptionEvent};
+use futures_util::future::BoxFuture;
+use futures_util::task::AtomicWaker;
 use hyper::{Body, Request, Response};
+use once_cell::sync::Lazy;
 use sb_core::util::sync::AtomicFlag;
 use sb_core::{MetricSource, SharedMetricSource};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::atomic::AtomicUsize;
-use std::{collections::HashMap, sync::Arc};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+use std::sync::Weak;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::unbounded_channel;
 use tokio::sync::{mpsc, oneshot, Mutex, Notify, OwnedSemaphorePermit};
 use tokio_util::sync::CancellationToken;
@@ -16,6 +25,154 @@ use uuid::Uuid;
 
 use sb_graph::{DecoratorType, EszipPayloadKind};
 
+#[derive(Debug, Clone, Copy, EnumAsInner)]
+pub enum SupervisorPolicy {
+    PerWorker,
+    PerRequest {
+        oneshot: bool,
+    },
+    /// A supervisor registered by the embedder under `name`. Requests are
+    /// routed to its workers as with `PerRequest` if `per_request` is set,
+    /// otherwise as with `PerWorker`.
+    Custom {
+        name: &'static str,
+        per_request: bool,
+    },
+}
+
+impl Default for SupervisorPolicy {
+    fn default() -> Self {
+        Self::PerWorker
+    }
+}
+
+static CUSTOM_SUPERVISOR_POLICIES: Lazy<RwLock<HashMap<&'static str, SupervisorPolicy>>> =
+    Lazy::new(RwLock::default);
+
+impl FromStr for SupervisorPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = s.strip_prefix("custom:") {
+            return Self::custom(name)
+                .ok_or_else(|| anyhow!("unknown custom supervisor: {}", name));
+        }
+
+        match s {
+            "per_worker" => Ok(Self::PerWorker),
+            "per_request" => Ok(Self::PerRequest { oneshot: false }),
+            "oneshot" => Ok(Self::PerRequest { oneshot: true }),
+            _ => Err(anyhow!("unknown supervisor policy: {}", s)),
+        }
+    }
+}
+
+impl SupervisorPolicy {
+    /// Makes the custom supervisor policy named `name` selectable with
+    /// `custom:<name>`, and returns it.
+    pub fn register_custom(name: &'static str, per_request: bool) -> Self {
+        let policy = Self::Custom { name, per_request };
+
+        CUSTOM_SUPERVISOR_POLICIES
+            .write()
+            .unwrap()
+            .insert(name, policy);
+
+        policy
+    }
+
+    /// Returns the custom supervisor policy registered under `name`, if any.
+    pub fn custom(name: &str) -> Option<Self> {
+        CUSTOM_SUPERVISOR_POLICIES
+            .read()
+            .unwrap()
+            .get(name)
+            .copied()
+    }
+
+    pub fn oneshot() -> Self {
+        Self::PerRequest { oneshot: true }
+    }
+
+    pub fn is_oneshot(&self) -> bool {
+        matches!(self, Self::PerRequest { oneshot: true })
+    }
+
+    /// Whether a worker handles only one request at a time under this policy.
+    pub fn is_request_scoped(&self) -> bool {
+        matches!(
+            self,
+            Self::PerRequest { .. }
+                | Self::Custom {
+                    per_request: true,
+                    ..
+                }
+        )
+    }
+}
+
+/// What the worker pool does with a new request when the queue of the target
+/// service is already full.
+#[derive(Debug, Clone, Copy, Default, EnumAsInner)]
+pub enum QueueFullBehavior {
+    /// Rejects the new request.
+    #[default]
+    Reject,
+    /// Evicts the request that has been waiting the longest and queues the
+    /// new one instead.
+    DropOldest,
+}
+
+impl FromStr for QueueFullBehavior {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(Self::Reject),
+            "drop_oldest" => Ok(Self::DropOldest),
+            _ => Err(anyhow!("unknown queue full behavior: {}", s)),
+        }
+    }
+}
+
+/// Pool policy values that a single service can override. Any field left as
+/// `None` falls back to the process-wide `WorkerPoolPolicy`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ServicePoolPolicy {
+    pub supervisor_policy: Option<SupervisorPolicy>,
+    pub max_parallelism: Option<usize>,
+    pub request_wait_timeout_ms: Option<u64>,
+    pub max_queue_size: Option<usize>,
+    pub queue_weight: Option<u32>,
+}
+
+/// Where and how the heap snapshots of a user worker are written.
+#[derive(Debug, Clone, Default)]
+pub struct HeapSnapshotOpts {
+    pub dir: PathBuf,
+    /// Whether a snapshot is written when the worker reaches its memory limit.
+    pub on_oom: bool,
+    /// Size past which a snapshot is discarded. Zero means no cap.
+    pub max_size_mb: u64,
+    /// Number of snapshots kept per service; the oldest ones are removed
+    /// first. Zero keeps all of them.
+    pub max_count: usize,
+}
+
+/// Where and when the CPU profiles of a user worker are taken.
+#[derive(Debug, Clone, Default)]
+pub struct CpuProfileOpts {
+    pub dir: PathBuf,
+    /// Fraction of the requests, between `0.0` and `1.0`, that are profiled
+    /// from their start until their response is done.
+    pub sample_rate: f64,
+    /// Sampling interval of the profiler. Zero uses the V8 default.
+    pub sampling_interval_us: u64,
+    /// Number of profiles kept per service; the oldest ones are removed
+    /// first. Zero keeps all of them.
+    pub max_count: usize,
+}
+
 #[derive(Debug, Clone)]
 pub enum WorkerExitStatus {
     Normal,
@@ -29,7 +186,7 @@ impl Default for WorkerExitStatus {
 }
 
 #[derive(Debug, Clone, Default)]
-pub struct WorkerExit(Arc<Mutex<WorkerExitStatus>>);
+pub struct WorkerExit(Arc<Mutex<WorkerExitStatus>>, CancellationToken);
 
 impl WorkerExit {
     pub async fn error(&self) -> Option<anyhow::Error> {
@@ -44,6 +201,16 @@ impl WorkerExit {
     pub async fn set(&self, exit_status: WorkerExitStatus) {
         *self.0.lock().await = exit_status;
     }
+
+    /// Marks the worker as having left its event loop, after its exit status
+    /// has been set.
+    pub fn notify_exited(&self) {
+        self.1.cancel();
+    }
+
+    pub async fn exited(&self) {
+        self.1.cancelled().await;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -52,7 +219,7 @@ pub struct UserWorkerRuntimeOpts {
     pub key: Option<Uuid>,
 
     pub pool_msg_tx: Option<mpsc::UnboundedSender<UserWorkerMsgs>>,
-    pub events_msg_tx: Option<mpsc::UnboundedSender<WorkerEventWithMetadata>>,
+    pub events_msg_tx: Option<EventSender>,
     pub cancel: Option<CancellationToken>,
 
     pub memory_limit_mb: u64,
@@ -63,6 +230,48 @@ pub struct UserWorkerRuntimeOpts {
     pub cpu_time_soft_limit_ms: u64,
     pub cpu_time_hard_limit_ms: u64,
 
+    /// Memory usage past which the worker is retired and a GC is triggered,
+    /// while `memory_limit_mb` still terminates it. Zero disables it.
+    pub memory_soft_limit_mb: u64,
+
+    /// How long a single request may wait for its response under the
+    /// `PerWorker` policy before it is cancelled. Zero disables it.
+    pub request_wall_clock_limit_ms: u64,
+    /// Number of requests exceeding `request_wall_clock_limit_ms` after which
+    /// the worker is retired. Zero never retires it.
+    pub max_request_wall_clock_violations: u32,
+
+    /// How long the worker may keep running after `beforeunload` has been
+    /// dispatched with the shutdown reason. Zero disables the notification
+    /// on termination.
+    pub shutdown_grace_period_ms: u64,
+
+    /// How long the worker may keep running after its last response to
+    /// settle the promises passed to `EdgeRuntime.waitUntil`.
+    pub background_budget_ms: u64,
+
+    /// Heap snapshots are only available if this is set.
+    pub heap_snapshot: Option<HeapSnapshotOpts>,
+    /// CPU profiles are only available if this is set.
+    pub cpu_profile: Option<CpuProfileOpts>,
+
+    /// Set by the pool to account for the usage of each request.
+    pub usage: Option<Arc<WorkerUsage>>,
+
+    /// Console messages below this level are discarded. `None` keeps all of
+    /// them.
+    pub log_level: Option<LogLevel>,
+    /// How many console messages the worker may log per second. Zero
+    /// disables the limit.
+    pub log_rate_limit: u64,
+    /// How many bytes of console messages the worker may log per second.
+    /// Zero disables the limit.
+    pub log_byte_limit: u64,
+
+    /// Services the worker may call directly through the worker pool, keyed
+    /// by the name they are exposed under in `EdgeRuntime.bindings`.
+    pub bindings: HashMap<String, ServiceBindingOpts>,
+
     pub force_create: bool,
     pub net_access_disabled: bool,
     pub custom_module_root: Option<String>,
@@ -77,6 +286,18 @@ impl Default for UserWorkerRuntimeOpts {
             low_memory_multiplier: 5,
             cpu_time_soft_limit_ms: 50,
             cpu_time_hard_limit_ms: 100,
+            memory_soft_limit_mb: 0,
+            request_wall_clock_limit_ms: 0,
+            max_request_wall_clock_violations: 0,
+            shutdown_grace_period_ms: 0,
+            background_budget_ms: 0,
+            heap_snapshot: None,
+            cpu_profile: None,
+            usage: None,
+            log_level: None,
+            log_rate_limit: 0,
+            log_byte_limit: 0,
+            bindings: HashMap::new(),
 
             force_create: false,
             key: None,
@@ -91,6 +312,129 @@ impl Default for UserWorkerRuntimeOpts {
     }
 }
 
+/// The options the workers of a service bound to a user worker are created
+/// with, as declared by the main worker along with the binding.
+#[derive(Debug, Clone)]
+pub struct ServiceBindingOpts {
+    pub service_path: PathBuf,
+    pub no_module_cache: bool,
+    pub import_map_path: Option<String>,
+    pub env_vars: HashMap<String, String>,
+    pub conf: UserWorkerRuntimeOpts,
+    pub maybe_decorator: Option<DecoratorType>,
+    pub maybe_jsx_import_source_config: Option<JsxImportSourceConfig>,
+    pub maybe_pool_policy: Option<ServicePoolPolicy>,
+}
+
+impl ServiceBindingOpts {
+    pub fn worker_options(&self) -> WorkerContextInitOpts {
+        WorkerContextInitOpts {
+            service_path: self.service_path.clone(),
+            no_module_cache: self.no_module_cache,
+            import_map_path: self.import_map_path.clone(),
+            env_vars: self.env_vars.clone(),
+            events_rx: None,
+            timing: None,
+            conf: WorkerRuntimeOpts::UserWorker(self.conf.clone()),
+            maybe_eszip: None,
+            maybe_module_code: None,
+            maybe_entrypoint: None,
+            maybe_decorator: self.maybe_decorator,
+            static_patterns: vec![],
+            maybe_jsx_import_source_config: self.maybe_jsx_import_source_config.clone(),
+            maybe_pool_policy: self.maybe_pool_policy,
+        }
+    }
+}
+
+impl TryFrom<WorkerContextInitOpts> for ServiceBindingOpts {
+    type Error = Error;
+
+    fn try_from(value: WorkerContextInitOpts) -> Result<Self, Self::Error> {
+        if value.maybe_eszip.is_some() || value.maybe_module_code.is_some() {
+            bail!("bound services must be loaded from their service path");
+        }
+
+        let WorkerRuntimeOpts::UserWorker(conf) = value.conf else {
+            bail!("bound services must run in user workers");
+        };
+
+        Ok(Self {
+            service_path: value.service_path,
+            no_module_cache: value.no_module_cache,
+            import_map_path: value.import_map_path,
+            env_vars: value.env_vars,
+            conf,
+            maybe_decorator: value.maybe_decorator,
+            maybe_jsx_import_source_config: value.maybe_jsx_import_source_config,
+            maybe_pool_policy: value.maybe_pool_policy,
+        })
+    }
+}
+
+/// The services bound to a user worker.
+///
+/// The workers of bound services are created with the options declared
+/// along with the binding only, so nothing of the worker they are bound to,
+/// such as its environment variables, is passed on to them.
+pub struct ServiceBindings {
+    pool_msg_tx: mpsc::UnboundedSender<UserWorkerMsgs>,
+    services: HashMap<String, ServiceBindingOpts>,
+    grants: HashMap<Uuid, usize>,
+}
+
+impl ServiceBindings {
+    /// Returns `None` if the worker has no bindings or isn't part of a pool.
+    pub fn new(conf: &UserWorkerRuntimeOpts) -> Option<Self> {
+        if conf.bindings.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            pool_msg_tx: conf.pool_msg_tx.clone()?,
+            services: conf.bindings.clone(),
+            grants: HashMap::new(),
+        })
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.services.keys().cloned().collect()
+    }
+
+    pub fn pool_msg_tx(&self) -> &mpsc::UnboundedSender<UserWorkerMsgs> {
+        &self.pool_msg_tx
+    }
+
+    /// Returns the options to create a worker of the service bound under
+    /// `name` with.
+    pub fn worker_options(&self, name: &str) -> Option<WorkerContextInitOpts> {
+        self.services
+            .get(name)
+            .map(ServiceBindingOpts::worker_options)
+    }
+
+    /// Allows a single request to be sent to a worker created for one of the
+    /// bound services.
+    pub fn grant(&mut self, key: Uuid) {
+        *self.grants.entry(key).or_default() += 1;
+    }
+
+    /// Consumes a grant for the worker `key`, returning whether there was
+    /// one.
+    pub fn take_grant(&mut self, key: &Uuid) -> bool {
+        let Some(count) = self.grants.get_mut(key) else {
+            return false;
+        };
+
+        *count -= 1;
+        if *count == 0 {
+            self.grants.remove(key);
+        }
+
+        true
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct UserWorkerProfile {
     pub worker_request_msg_tx: mpsc::UnboundedSender<WorkerRequestMsg>,
@@ -98,11 +442,47 @@ pub struct UserWorkerProfile {
         mpsc::UnboundedSender<Arc<Notify>>,
         mpsc::UnboundedSender<()>,
     ),
+    pub req_timeout_tx: mpsc::UnboundedSender<()>,
+    pub request_wall_clock_limit: Option<Duration>,
+    pub diagnostic_tx: WorkerDiagnosticSender,
+    pub cpu_profile_sample_rate: f64,
+    pub usage: Arc<WorkerUsage>,
     pub service_path: String,
     pub permit: Option<Arc<OwnedSemaphorePermit>>,
+    pub pool_permit: Option<Arc<OwnedSemaphorePermit>>,
     pub cancel: CancellationToken,
+    /// Cancelled to terminate the worker.
+    pub termination: CancellationToken,
     pub status: TimingStatus,
     pub exit: WorkerExit,
+    pub created_at: Instant,
+}
+
+impl UserWorkerProfile {
+    pub fn stats(&self, key: &Uuid) -> UserWorkerStats {
+        UserWorkerStats {
+            key: key.to_string(),
+            service_path: self.service_path.clone(),
+            age_ms: self.created_at.elapsed().as_millis() as u64,
+            demand: self.status.demand.load(Ordering::Acquire),
+            cpu_time_ms: self.usage.cpu_time_ms(),
+            heap_bytes: self.usage.heap_bytes(),
+            is_retired: self.status.is_retired.is_raised(),
+        }
+    }
+}
+
+/// A snapshot of a live user worker, as reported to the main worker.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UserWorkerStats {
+    pub key: String,
+    pub service_path: String,
+    pub age_ms: u64,
+    pub demand: usize,
+    pub cpu_time_ms: f64,
+    pub heap_bytes: usize,
+    pub is_retired: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -168,16 +548,19 @@ pub struct Timing {
         mpsc::UnboundedReceiver<Arc<Notify>>,
         mpsc::UnboundedReceiver<()>,
     ),
+    pub req_timeout_rx: mpsc::UnboundedReceiver<()>,
 }
 
 impl Default for Timing {
     fn default() -> Self {
         let (_, dumb_start_rx) = unbounded_channel::<Arc<Notify>>();
         let (_, dumb_end_rx) = unbounded_channel::<()>();
+        let (_, dumb_timeout_rx) = unbounded_channel::<()>();
 
         Self {
             status: TimingStatus::default(),
             req: (dumb_start_rx, dumb_end_rx),
+            req_timeout_rx: dumb_timeout_rx,
         }
     }
 }
@@ -188,7 +571,7 @@ pub struct WorkerContextInitOpts {
     pub no_module_cache: bool,
     pub import_map_path: Option<String>,
     pub env_vars: HashMap<String, String>,
-    pub events_rx: Option<mpsc::UnboundedReceiver<WorkerEventWithMetadata>>,
+    pub events_rx: Option<EventReceiver>,
     pub timing: Option<Timing>,
     pub conf: WorkerRuntimeOpts,
     pub maybe_eszip: Option<EszipPayloadKind>,
@@ -197,6 +580,7 @@ pub struct WorkerContextInitOpts {
     pub maybe_decorator: Option<DecoratorType>,
     pub static_patterns: Vec<String>,
     pub maybe_jsx_import_source_config: Option<JsxImportSourceConfig>,
+    pub maybe_pool_policy: Option<ServicePoolPolicy>,
 }
 
 #[derive(Debug)]
@@ -213,10 +597,165 @@ pub enum UserWorkerMsgs {
         Option<CancellationToken>,
     ),
     Idle(Uuid),
-    Shutdown(Uuid),
+    /// Reports an exited worker, along with whether it exited cleanly (i.e.
+    /// it was dropped early or its event loop completed).
+    Shutdown(Uuid, bool),
+    CreateFailed(String, String),
+    UncaughtException(Uuid, String),
+    TakeHeapSnapshot(Uuid, oneshot::Sender<Result<PathBuf, Error>>),
+    TakeCpuProfile(Uuid, Duration, oneshot::Sender<Result<PathBuf, Error>>),
+    ListWorkers(oneshot::Sender<Vec<UserWorkerStats>>),
+    GetWorkerStats(Uuid, oneshot::Sender<Option<UserWorkerStats>>),
+    /// Terminates the worker, resolving to whether it was found.
+    TerminateWorker(Uuid, oneshot::Sender<bool>),
+    /// Stops routing new requests to the worker, resolving to whether it was
+    /// found.
+    RetireWorker(Uuid, oneshot::Sender<bool>),
+}
+
+/// Requests that are served by the event loop of a running worker.
+pub enum WorkerDiagnosticMsg {
+    HeapSnapshot(oneshot::Sender<Result<PathBuf, Error>>),
+    /// Profiles the worker until the future resolves.
+    CpuProfile(
+        BoxFuture<'static, ()>,
+        oneshot::Sender<Result<PathBuf, Error>>,
+    ),
+}
+
+/// Sends requests to the event loop of a running worker, waking it up so they
+/// are served right away.
+#[derive(Debug, Clone)]
+pub struct WorkerDiagnosticSender {
+    tx: mpsc::UnboundedSender<WorkerDiagnosticMsg>,
+    waker: Arc<AtomicWaker>,
+}
+
+impl WorkerDiagnosticSender {
+    pub fn new(tx: mpsc::UnboundedSender<WorkerDiagnosticMsg>, waker: Arc<AtomicWaker>) -> Self {
+        Self { tx, waker }
+    }
+
+    pub fn send(
+        &self,
+        msg: WorkerDiagnosticMsg,
+    ) -> Result<(), mpsc::error::SendError<WorkerDiagnosticMsg>> {
+        self.tx.send(msg)?;
+        self.waker.wake();
+        Ok(())
+    }
+}
+
+/// Resource usage of a user worker, updated by its event loop and sampled
+/// around each request to account for it.
+#[derive(Debug, Default)]
+pub struct WorkerUsage {
+    cpu_time_ns: AtomicI64,
+    heap_bytes: AtomicUsize,
+    peak_trackers: std::sync::Mutex<Vec<PeakTracker>>,
+}
+
+/// The peak heap usage of a request in flight.
+#[derive(Debug)]
+struct PeakTracker {
+    peak_heap_bytes: Weak<AtomicUsize>,
+    request_id: Option<String>,
+}
+
+impl WorkerUsage {
+    pub fn record(&self, cpu_time_ns: i64, heap_bytes: usize) {
+        self.cpu_time_ns.store(cpu_time_ns, Ordering::Release);
+        self.heap_bytes.store(heap_bytes, Ordering::Release);
+        self.peak_trackers
+            .lock()
+            .unwrap()
+            .retain(|it| match it.peak_heap_bytes.upgrade() {
+                Some(peak) => {
+                    peak.fetch_max(heap_bytes, Ordering::AcqRel);
+                    true
+                }
+
+                None => false,
+            });
+    }
+
+    pub fn cpu_time_ms(&self) -> f64 {
+        self.cpu_time_ns.load(Ordering::Acquire).max(0) as f64 / 1_000_000.0
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        self.heap_bytes.load(Ordering::Acquire)
+    }
+
+    /// Returns the id of the oldest request still in flight, if any.
+    pub fn in_flight_request_id(&self) -> Option<String> {
+        self.peak_trackers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|it| it.peak_heap_bytes.strong_count() > 0)
+            .find_map(|it| it.request_id.clone())
+    }
+
+    pub fn track(self: &Arc<Self>, request_id: Option<String>) -> RequestUsageTracker {
+        let heap_bytes = self.heap_bytes.load(Ordering::Acquire);
+        let peak_heap_bytes = Arc::new(AtomicUsize::new(heap_bytes));
+
+        self.peak_trackers.lock().unwrap().push(PeakTracker {
+            peak_heap_bytes: Arc::downgrade(&peak_heap_bytes),
+            request_id,
+        });
+
+        RequestUsageTracker {
+            usage: self.clone(),
+            started_at: Instant::now(),
+            cpu_time_ns: self.cpu_time_ns.load(Ordering::Acquire),
+            heap_bytes,
+            peak_heap_bytes,
+        }
+    }
 }
 
-pub type SendRequestResult = (Response<Body>, mpsc::UnboundedSender<()>);
+/// Keeps track of the usage of a worker from the start of a request.
+///
+/// Requests served concurrently by the same worker are charged for the CPU
+/// time used while any of them was in flight.
+pub struct RequestUsageTracker {
+    usage: Arc<WorkerUsage>,
+    started_at: Instant,
+    cpu_time_ns: i64,
+    heap_bytes: usize,
+    peak_heap_bytes: Arc<AtomicUsize>,
+}
+
+impl RequestUsageTracker {
+    pub fn finish(self) -> RequestUsage {
+        let cpu_time_ns = self.usage.cpu_time_ns.load(Ordering::Acquire) - self.cpu_time_ns;
+        let peak_heap_bytes = self.peak_heap_bytes.load(Ordering::Acquire);
+
+        RequestUsage {
+            cpu_time_ms: cpu_time_ns.max(0) as f64 / 1_000_000.0,
+            wall_time_ms: self.started_at.elapsed().as_secs_f64() * 1000.0,
+            peak_heap_delta_bytes: peak_heap_bytes.saturating_sub(self.heap_bytes),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestUsage {
+    pub cpu_time_ms: f64,
+    pub wall_time_ms: f64,
+    pub peak_heap_delta_bytes: usize,
+}
+
+/// The response of a user worker, a sender to signal that its body is done
+/// with the number of bytes read from it, and its usage once it is done.
+pub type SendRequestResult = (
+    Response<Body>,
+    mpsc::UnboundedSender<u64>,
+    oneshot::Receiver<RequestUsage>,
+);
 
 #[derive(Debug)]
 pub struct CreateUserWorkerResult {
@@ -229,3 +768,66 @@ pub struct WorkerRequestMsg {
     pub res_tx: oneshot::Sender<Result<Response<Body>, hyper::Error>>,
     pub conn_token: Option<CancellationToken>,
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::WorkerUsage;
+
+    #[test]
+    fn test_request_usage_is_measured_from_its_start() {
+        let usage = Arc::new(WorkerUsage::default());
+
+        usage.record(1_000_000, 100);
+
+        let tracker = usage.track(Some("req-1".to_string()));
+
+        assert_eq!(usage.in_flight_request_id().as_deref(), Some("req-1"));
+
+        usage.record(4_000_000, 300);
+        usage.record(6_000_000, 200);
+
+        let req_usage = tracker.finish();
+
+        assert_eq!(req_usage.cpu_time_ms, 5.0);
+        assert_eq!(req_usage.peak_heap_delta_bytes, 200);
+        assert!(req_usage.wall_time_ms >= 0.0);
+        assert_eq!(usage.in_flight_request_id(), None);
+        assert_eq!(usage.cpu_time_ms(), 6.0);
+        assert_eq!(usage.heap_bytes(), 200);
+    }
+
+    #[test]
+    fn test_concurrent_requests_are_tracked_separately() {
+        let usage = Arc::new(WorkerUsage::default());
+        let first = usage.track(Some("req-1".to_string()));
+
+        usage.record(2_000_000, 500);
+
+        let second = usage.track(Some("req-2".to_string()));
+
+        usage.record(3_000_000, 400);
+
+        // the oldest request in flight is reported
+        assert_eq!(usage.in_flight_request_id().as_deref(), Some("req-1"));
+
+        let first = first.finish();
+
+        assert_eq!(usage.in_flight_request_id().as_deref(), Some("req-2"));
+
+        usage.record(5_000_000, 600);
+
+        let second = second.finish();
+
+        assert_eq!(first.cpu_time_ms, 3.0);
+        assert_eq!(first.peak_heap_delta_bytes, 500);
+        assert_eq!(second.cpu_time_ms, 3.0);
+        assert_eq!(second.peak_heap_delta_bytes, 100);
+
+        // finished requests are no longer tracked once usage is recorded
+        usage.record(5_000_000, 600);
+
+        assert!(usage.peak_trackers.lock().unwrap().is_empty());
+    }
+}
@@ -1,7 +1,14 @@
+use event_worker::events::BootFailureEvent;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum WorkerError {
     #[error("request has been cancelled by supervisor")]
     RequestCancelledBySupervisor,
+    #[error("request has exceeded its wall clock limit")]
+    RequestTimedOut,
+    #[error("worker boot error {}", .0.msg)]
+    BootFailed(BootFailureEvent),
+    #[error("worker did not respond in time")]
+    RequestWaitTimedOut,
 }
@@ -2,8 +2,9 @@ pub mod context;
 pub mod errors;
 
 use crate::context::{
-    CreateUserWorkerResult, UserWorkerMsgs, UserWorkerRuntimeOpts, WorkerContextInitOpts,
-    WorkerRuntimeOpts,
+    CpuProfileOpts, CreateUserWorkerResult, HeapSnapshotOpts, RequestUsage, ServiceBindingOpts,
+    ServiceBindings, ServicePoolPolicy, SupervisorPolicy, UserWorkerMsgs, UserWorkerRuntimeOpts,
+    UserWorkerStats, WorkerContextInitOpts, WorkerRuntimeOpts,
 };
 use anyhow::Error;
 use context::SendRequestResult;
@@ -11,6 +12,7 @@ use deno_config::JsxImportSourceConfig;
 use deno_core::error::{custom_error, type_error, AnyError};
 use deno_core::futures::stream::Peekable;
 use deno_core::futures::{FutureExt, Stream, StreamExt};
+use deno_core::serde_json::Value;
 use deno_core::{op2, ModuleSpecifier};
 use deno_core::{
     AsyncRefCell, AsyncResult, BufView, ByteString, CancelFuture, CancelHandle, CancelTryFuture,
@@ -18,21 +20,23 @@ use deno_core::{
 };
 use deno_http::{HttpRequestReader, HttpStreamReadResource};
 use errors::WorkerError;
+use event_worker::events::{BootFailureEvent, LogLevel};
 use http_utils::utils::get_upgrade_type;
 use hyper::body::HttpBody;
 use hyper::header::{HeaderName, HeaderValue, CONTENT_LENGTH};
 use hyper::upgrade::OnUpgrade;
 use hyper::{Body, Method, Request};
 use log::error;
-use sb_core::conn_sync::ConnWatcher;
+use sb_core::conn_sync::{ConnWatcher, RequestContext};
 use sb_graph::{DecoratorType, EszipPayloadKind};
 use serde::{Deserialize, Serialize};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::rc::Rc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 use tokio::sync::{mpsc, oneshot};
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
@@ -43,6 +47,15 @@ deno_core::extension!(
         op_user_worker_create,
         op_user_worker_fetch_build,
         op_user_worker_fetch_send,
+        op_user_worker_fetch_usage,
+        op_user_worker_take_heap_snapshot,
+        op_user_worker_take_cpu_profile,
+        op_user_worker_list,
+        op_user_worker_stats,
+        op_user_worker_terminate,
+        op_user_worker_retire,
+        op_user_worker_binding_names,
+        op_user_worker_binding_create,
     ],
     esm_entry_point = "ext:sb_user_workers/user_workers.js",
     esm = ["user_workers.js",]
@@ -56,6 +69,108 @@ pub struct JsxImportBaseConfig {
     base_url: String,
 }
 
+#[derive(Deserialize, Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UserWorkerPoolPolicyOptions {
+    supervisor_policy: Option<String>,
+    max_parallelism: Option<usize>,
+    request_wait_timeout_ms: Option<u64>,
+    max_queue_size: Option<usize>,
+    queue_weight: Option<u32>,
+}
+
+impl TryFrom<UserWorkerPoolPolicyOptions> for ServicePoolPolicy {
+    type Error = AnyError;
+
+    fn try_from(value: UserWorkerPoolPolicyOptions) -> Result<Self, Self::Error> {
+        let supervisor_policy = value
+            .supervisor_policy
+            .map(|it| it.parse::<SupervisorPolicy>())
+            .transpose()
+            .map_err(|err| type_error(err.to_string()))?;
+
+        if let Some(0) = value.max_parallelism {
+            return Err(type_error("maximum parallelism must be greater than zero"));
+        }
+
+        if let Some(0) = value.queue_weight {
+            return Err(type_error("queue weight must be greater than zero"));
+        }
+
+        Ok(Self {
+            supervisor_policy,
+            max_parallelism: value.max_parallelism,
+            request_wait_timeout_ms: value.request_wait_timeout_ms,
+            max_queue_size: value.max_queue_size,
+            queue_weight: value.queue_weight,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UserWorkerHeapSnapshotOptions {
+    dir: String,
+    #[serde(default)]
+    on_oom: bool,
+    #[serde(default)]
+    max_size_mb: u64,
+    #[serde(default)]
+    max_count: usize,
+}
+
+impl TryFrom<UserWorkerHeapSnapshotOptions> for HeapSnapshotOpts {
+    type Error = AnyError;
+
+    fn try_from(value: UserWorkerHeapSnapshotOptions) -> Result<Self, Self::Error> {
+        if value.dir.is_empty() {
+            return Err(type_error("heap snapshot directory must be defined"));
+        }
+
+        Ok(Self {
+            dir: PathBuf::from(value.dir),
+            on_oom: value.on_oom,
+            max_size_mb: value.max_size_mb,
+            max_count: value.max_count,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UserWorkerCpuProfileOptions {
+    dir: String,
+    #[serde(default)]
+    sample_rate: f64,
+    #[serde(default)]
+    sampling_interval_us: u64,
+    #[serde(default)]
+    max_count: usize,
+}
+
+impl TryFrom<UserWorkerCpuProfileOptions> for CpuProfileOpts {
+    type Error = AnyError;
+
+    fn try_from(value: UserWorkerCpuProfileOptions) -> Result<Self, Self::Error> {
+        if value.dir.is_empty() {
+            return Err(type_error("cpu profile directory must be defined"));
+        }
+
+        if !(0.0..=1.0).contains(&value.sample_rate) {
+            return Err(type_error(
+                "cpu profile sample rate must be between 0 and 1",
+            ));
+        }
+
+        Ok(Self {
+            dir: PathBuf::from(value.dir),
+            sample_rate: value.sample_rate,
+            sampling_interval_us: value.sampling_interval_us,
+            max_count: value.max_count,
+        })
+    }
+}
+
 #[derive(Deserialize, Serialize, Default, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct UserWorkerCreateOptions {
@@ -76,95 +191,178 @@ pub struct UserWorkerCreateOptions {
     worker_timeout_ms: u64,
     cpu_time_soft_limit_ms: u64,
     cpu_time_hard_limit_ms: u64,
+    memory_soft_limit_mb: u64,
+    request_wall_clock_limit_ms: u64,
+    max_request_wall_clock_violations: u32,
+    shutdown_grace_period_ms: u64,
+    background_budget_ms: u64,
+    heap_snapshot: Option<UserWorkerHeapSnapshotOptions>,
+    cpu_profile: Option<UserWorkerCpuProfileOptions>,
+    log_level: Option<String>,
+    log_rate_limit: u64,
+    log_byte_limit: u64,
+    bindings: HashMap<String, UserWorkerCreateOptions>,
 
     jsx_import_source_config: Option<JsxImportBaseConfig>,
     decorator_type: Option<DecoratorType>,
+    pool_policy: Option<UserWorkerPoolPolicyOptions>,
 }
 
-#[op2(async)]
-#[string]
-pub async fn op_user_worker_create(
-    state: Rc<RefCell<OpState>>,
-    #[serde] opts: UserWorkerCreateOptions,
-) -> Result<String, AnyError> {
-    let result_rx = {
-        let op_state = state.borrow();
-        let tx = op_state.borrow::<mpsc::UnboundedSender<UserWorkerMsgs>>();
-        let (result_tx, result_rx) = oneshot::channel::<Result<CreateUserWorkerResult, Error>>();
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum UserWorkerCreateOutcome {
+    Created(String),
+    /// Thrown as an `InvalidWorkerCreation` error carrying the details of the
+    /// failure.
+    BootFailed {
+        message: String,
+        failure: BootFailureEvent,
+    },
+}
 
-        let UserWorkerCreateOptions {
-            service_path,
-            no_module_cache,
-            import_map_path,
-            env_vars,
-            force_create,
-            net_access_disabled,
-            allow_remote_modules,
-            custom_module_root,
-            maybe_eszip,
-            maybe_entrypoint,
-            maybe_module_code,
+/// Converts the options passed to `UserWorker.create`, including those of
+/// the services bound to the worker, into the options it is created with.
+fn worker_options_from_create_options(
+    opts: UserWorkerCreateOptions,
+    op_state: &OpState,
+) -> Result<WorkerContextInitOpts, AnyError> {
+    let UserWorkerCreateOptions {
+        service_path,
+        no_module_cache,
+        import_map_path,
+        env_vars,
+        force_create,
+        net_access_disabled,
+        allow_remote_modules,
+        custom_module_root,
+        maybe_eszip,
+        maybe_entrypoint,
+        maybe_module_code,
+
+        memory_limit_mb,
+        low_memory_multiplier,
+        worker_timeout_ms,
+        cpu_time_soft_limit_ms,
+        cpu_time_hard_limit_ms,
+        memory_soft_limit_mb,
+        request_wall_clock_limit_ms,
+        max_request_wall_clock_violations,
+        shutdown_grace_period_ms,
+        background_budget_ms,
+        heap_snapshot,
+        cpu_profile,
+        log_level,
+        log_rate_limit,
+        log_byte_limit,
+        bindings,
+        jsx_import_source_config,
+        decorator_type: maybe_decorator,
+        pool_policy,
+    } = opts;
+
+    if memory_soft_limit_mb > 0 && memory_soft_limit_mb >= memory_limit_mb {
+        return Err(type_error(
+            "memory soft limit must be less than the memory limit",
+        ));
+    }
+
+    let maybe_pool_policy = pool_policy.map(ServicePoolPolicy::try_from).transpose()?;
+    let heap_snapshot = heap_snapshot.map(HeapSnapshotOpts::try_from).transpose()?;
+    let cpu_profile = cpu_profile.map(CpuProfileOpts::try_from).transpose()?;
+    let log_level = log_level
+        .map(|it| it.parse::<LogLevel>())
+        .transpose()
+        .map_err(|err| type_error(err.to_string()))?;
+
+    let mut env_vars_map = HashMap::new();
+    for (key, value) in env_vars {
+        env_vars_map.insert(key, value);
+    }
+
+    let bindings = bindings
+        .into_iter()
+        .map(|(name, opts)| {
+            let worker_options = worker_options_from_create_options(opts, op_state)?;
+            let binding_opts = ServiceBindingOpts::try_from(worker_options)
+                .map_err(|err| type_error(err.to_string()))?;
+
+            Ok((name, binding_opts))
+        })
+        .collect::<Result<HashMap<_, _>, AnyError>>()?;
+
+    let jsx_import_conf = {
+        if let Some(jsx_import_source_config) = jsx_import_source_config {
+            Some(JsxImportSourceConfig {
+                default_specifier: jsx_import_source_config.default_specifier,
+                default_types_specifier: None,
+                module: jsx_import_source_config.module,
+                base_url: {
+                    let main = op_state.borrow::<ModuleSpecifier>().to_string();
+                    deno_core::resolve_url_or_path(&main, std::env::current_dir()?.as_path())?
+                },
+            })
+        } else {
+            None
+        }
+    };
 
+    Ok(WorkerContextInitOpts {
+        service_path: PathBuf::from(service_path),
+        no_module_cache,
+        import_map_path,
+        env_vars: env_vars_map,
+        events_rx: None,
+        timing: None,
+        maybe_eszip: maybe_eszip.map(EszipPayloadKind::JsBufferKind),
+        maybe_entrypoint,
+        maybe_module_code: maybe_module_code.map(|v| v.into()),
+        maybe_decorator,
+        conf: WorkerRuntimeOpts::UserWorker(UserWorkerRuntimeOpts {
             memory_limit_mb,
             low_memory_multiplier,
             worker_timeout_ms,
             cpu_time_soft_limit_ms,
             cpu_time_hard_limit_ms,
-            jsx_import_source_config,
-            decorator_type: maybe_decorator,
-        } = opts;
-
-        let mut env_vars_map = HashMap::new();
-        for (key, value) in env_vars {
-            env_vars_map.insert(key, value);
-        }
+            memory_soft_limit_mb,
+            request_wall_clock_limit_ms,
+            max_request_wall_clock_violations,
+            shutdown_grace_period_ms,
+            background_budget_ms,
+            heap_snapshot,
+            cpu_profile,
+            usage: None,
+            log_level,
+            log_rate_limit,
+            log_byte_limit,
+            bindings,
+            force_create,
+            net_access_disabled,
+            allow_remote_modules,
+            custom_module_root,
+            key: None,
+            pool_msg_tx: None,
+            events_msg_tx: None,
+            cancel: None,
+            service_path: None,
+        }),
+        static_patterns: vec![],
+        maybe_jsx_import_source_config: jsx_import_conf,
+        maybe_pool_policy,
+    })
+}
 
-        let jsx_import_conf = {
-            if let Some(jsx_import_source_config) = jsx_import_source_config {
-                Some(JsxImportSourceConfig {
-                    default_specifier: jsx_import_source_config.default_specifier,
-                    default_types_specifier: None,
-                    module: jsx_import_source_config.module,
-                    base_url: {
-                        let main = op_state.borrow::<ModuleSpecifier>().to_string();
-                        deno_core::resolve_url_or_path(&main, std::env::current_dir()?.as_path())?
-                    },
-                })
-            } else {
-                None
-            }
-        };
+#[op2(async)]
+#[serde]
+pub async fn op_user_worker_create(
+    state: Rc<RefCell<OpState>>,
+    #[serde] opts: UserWorkerCreateOptions,
+) -> Result<UserWorkerCreateOutcome, AnyError> {
+    let result_rx = {
+        let op_state = state.borrow();
+        let tx = op_state.borrow::<mpsc::UnboundedSender<UserWorkerMsgs>>();
+        let (result_tx, result_rx) = oneshot::channel::<Result<CreateUserWorkerResult, Error>>();
 
-        let user_worker_options = WorkerContextInitOpts {
-            service_path: PathBuf::from(service_path),
-            no_module_cache,
-            import_map_path,
-            env_vars: env_vars_map,
-            events_rx: None,
-            timing: None,
-            maybe_eszip: maybe_eszip.map(EszipPayloadKind::JsBufferKind),
-            maybe_entrypoint,
-            maybe_module_code: maybe_module_code.map(|v| v.into()),
-            maybe_decorator,
-            conf: WorkerRuntimeOpts::UserWorker(UserWorkerRuntimeOpts {
-                memory_limit_mb,
-                low_memory_multiplier,
-                worker_timeout_ms,
-                cpu_time_soft_limit_ms,
-                cpu_time_hard_limit_ms,
-                force_create,
-                net_access_disabled,
-                allow_remote_modules,
-                custom_module_root,
-                key: None,
-                pool_msg_tx: None,
-                events_msg_tx: None,
-                cancel: None,
-                service_path: None,
-            }),
-            static_patterns: vec![],
-            maybe_jsx_import_source_config: jsx_import_conf,
-        };
+        let user_worker_options = worker_options_from_create_options(opts, &op_state)?;
 
         tx.send(UserWorkerMsgs::Create(user_worker_options, result_tx))?;
         result_rx
@@ -181,8 +379,213 @@ pub async fn op_user_worker_create(
     // channel returns a Result<T, E>, we need to unwrap it first;
     let result = result.unwrap();
     match result {
-        Err(e) => Err(custom_error("InvalidWorkerCreation", e.to_string())),
-        Ok(res) => Ok(res.key.to_string()),
+        Err(e) => {
+            let message = e.to_string();
+
+            // NOTE: A request that timed out in the queue of its service is
+            // not a boot failure, no worker has been booted for it.
+            match e.downcast_ref::<WorkerError>() {
+                Some(WorkerError::BootFailed(failure)) => Ok(UserWorkerCreateOutcome::BootFailed {
+                    message,
+                    failure: failure.clone(),
+                }),
+
+                _ => Err(custom_error("InvalidWorkerCreation", message)),
+            }
+        }
+
+        Ok(res) => Ok(UserWorkerCreateOutcome::Created(res.key.to_string())),
+    }
+}
+
+#[op2(async)]
+#[string]
+pub async fn op_user_worker_take_heap_snapshot(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+) -> Result<String, AnyError> {
+    let key_parsed = Uuid::try_parse(key.as_str())?;
+    let result_rx = {
+        let op_state = state.borrow();
+        let tx = op_state.borrow::<mpsc::UnboundedSender<UserWorkerMsgs>>();
+        let (result_tx, result_rx) = oneshot::channel::<Result<PathBuf, Error>>();
+
+        tx.send(UserWorkerMsgs::TakeHeapSnapshot(key_parsed, result_tx))?;
+        result_rx
+    };
+
+    match result_rx.await {
+        Ok(Ok(path)) => Ok(path.to_string_lossy().to_string()),
+        Ok(Err(err)) => Err(custom_error("WorkerHeapSnapshotFailed", err.to_string())),
+        Err(_) => Err(custom_error(
+            "WorkerHeapSnapshotFailed",
+            "user worker has exited before taking the heap snapshot",
+        )),
+    }
+}
+
+#[op2(async)]
+#[string]
+pub async fn op_user_worker_take_cpu_profile(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+    #[number] duration_ms: u64,
+) -> Result<String, AnyError> {
+    let key_parsed = Uuid::try_parse(key.as_str())?;
+    let result_rx = {
+        let op_state = state.borrow();
+        let tx = op_state.borrow::<mpsc::UnboundedSender<UserWorkerMsgs>>();
+        let (result_tx, result_rx) = oneshot::channel::<Result<PathBuf, Error>>();
+
+        tx.send(UserWorkerMsgs::TakeCpuProfile(
+            key_parsed,
+            Duration::from_millis(duration_ms),
+            result_tx,
+        ))?;
+
+        result_rx
+    };
+
+    match result_rx.await {
+        Ok(Ok(path)) => Ok(path.to_string_lossy().to_string()),
+        Ok(Err(err)) => Err(custom_error("WorkerCpuProfileFailed", err.to_string())),
+        Err(_) => Err(custom_error(
+            "WorkerCpuProfileFailed",
+            "user worker has exited before taking the cpu profile",
+        )),
+    }
+}
+
+/// Sends a message built with a oneshot sender to the worker pool and waits
+/// for its reply.
+async fn request_worker_pool<T>(
+    state: &Rc<RefCell<OpState>>,
+    msg: impl FnOnce(oneshot::Sender<T>) -> UserWorkerMsgs,
+) -> Result<T, AnyError> {
+    let result_rx = {
+        let op_state = state.borrow();
+        let tx = op_state.borrow::<mpsc::UnboundedSender<UserWorkerMsgs>>();
+        let (result_tx, result_rx) = oneshot::channel::<T>();
+
+        tx.send(msg(result_tx))?;
+        result_rx
+    };
+
+    result_rx
+        .await
+        .map_err(|_| anyhow::anyhow!("worker pool has dropped the request"))
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_user_worker_list(
+    state: Rc<RefCell<OpState>>,
+) -> Result<Vec<UserWorkerStats>, AnyError> {
+    request_worker_pool(&state, UserWorkerMsgs::ListWorkers).await
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_user_worker_stats(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+) -> Result<Option<UserWorkerStats>, AnyError> {
+    let key_parsed = Uuid::try_parse(key.as_str())?;
+
+    request_worker_pool(&state, |tx| UserWorkerMsgs::GetWorkerStats(key_parsed, tx)).await
+}
+
+#[op2(async)]
+pub async fn op_user_worker_terminate(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+) -> Result<bool, AnyError> {
+    let key_parsed = Uuid::try_parse(key.as_str())?;
+
+    request_worker_pool(&state, |tx| UserWorkerMsgs::TerminateWorker(key_parsed, tx)).await
+}
+
+#[op2(async)]
+pub async fn op_user_worker_retire(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+) -> Result<bool, AnyError> {
+    let key_parsed = Uuid::try_parse(key.as_str())?;
+
+    request_worker_pool(&state, |tx| UserWorkerMsgs::RetireWorker(key_parsed, tx)).await
+}
+
+/// Returns the sender of the worker pool to send a request to the worker
+/// `key` with. User workers may only send requests to the workers of the
+/// services bound to them, once for each worker handed out by
+/// `op_user_worker_binding_create`.
+fn worker_pool_tx(
+    state: &mut OpState,
+    key: &Uuid,
+) -> Result<mpsc::UnboundedSender<UserWorkerMsgs>, AnyError> {
+    if let Some(tx) = state.try_borrow::<mpsc::UnboundedSender<UserWorkerMsgs>>() {
+        return Ok(tx.clone());
+    }
+
+    match state.try_borrow_mut::<ServiceBindings>() {
+        Some(bindings) if bindings.take_grant(key) => Ok(bindings.pool_msg_tx().clone()),
+        _ => Err(custom_error(
+            "PermissionDenied",
+            "requests can only be sent to the services bound to the worker",
+        )),
+    }
+}
+
+#[op2]
+#[serde]
+pub fn op_user_worker_binding_names(state: &mut OpState) -> Vec<String> {
+    state
+        .try_borrow::<ServiceBindings>()
+        .map(ServiceBindings::names)
+        .unwrap_or_default()
+}
+
+/// Resolves to the key of a worker of the service bound under `name`, which
+/// is created if none can take the request.
+#[op2(async)]
+#[string]
+pub async fn op_user_worker_binding_create(
+    state: Rc<RefCell<OpState>>,
+    #[string] name: String,
+) -> Result<String, AnyError> {
+    let result_rx = {
+        let op_state = state.borrow();
+        let Some(bindings) = op_state.try_borrow::<ServiceBindings>() else {
+            return Err(type_error(format!("unknown service binding: {}", name)));
+        };
+
+        let Some(worker_options) = bindings.worker_options(&name) else {
+            return Err(type_error(format!("unknown service binding: {}", name)));
+        };
+
+        let (result_tx, result_rx) = oneshot::channel::<Result<CreateUserWorkerResult, Error>>();
+
+        bindings
+            .pool_msg_tx()
+            .send(UserWorkerMsgs::Create(worker_options, result_tx))?;
+
+        result_rx
+    };
+
+    match result_rx.await {
+        Ok(Ok(res)) => {
+            state
+                .borrow_mut()
+                .borrow_mut::<ServiceBindings>()
+                .grant(res.key);
+            Ok(res.key.to_string())
+        }
+
+        Ok(Err(err)) => Err(custom_error("InvalidWorkerCreation", err.to_string())),
+        Err(_) => Err(custom_error(
+            "InvalidWorkerCreation",
+            "failed to create worker",
+        )),
     }
 }
 
@@ -209,6 +612,7 @@ pub struct UserWorkerResponse {
     status_text: String,
     headers: Vec<(ByteString, ByteString)>,
     body_rid: ResourceId,
+    usage_rid: ResourceId,
     size: Option<u64>,
 }
 
@@ -278,12 +682,21 @@ impl Resource for UserWorkerRequestBodyResource {
     }
 }
 
+struct UserWorkerRequestUsageResource(RefCell<Option<oneshot::Receiver<RequestUsage>>>);
+
+impl Resource for UserWorkerRequestUsageResource {
+    fn name(&self) -> std::borrow::Cow<str> {
+        "userWorkerRequestUsage".into()
+    }
+}
+
 type BytesStream = Pin<Box<dyn Stream<Item = Result<bytes::Bytes, std::io::Error>> + Unpin>>;
 
 struct UserWorkerResponseBodyResource {
     reader: AsyncRefCell<Peekable<BytesStream>>,
     size: Option<u64>,
-    req_end_tx: mpsc::UnboundedSender<()>,
+    bytes_read: Cell<u64>,
+    req_end_tx: mpsc::UnboundedSender<u64>,
     cancel: CancelHandle,
     conn_token: Option<CancellationToken>,
 }
@@ -296,6 +709,7 @@ impl Resource for UserWorkerResponseBodyResource {
     fn read(self: Rc<Self>, limit: usize) -> AsyncResult<BufView> {
         Box::pin(async move {
             let reader = RcRef::map(&self, |r| &r.reader).borrow_mut().await;
+            let this = self.clone();
 
             let fut = async move {
                 let mut reader = Pin::new(reader);
@@ -304,6 +718,8 @@ impl Resource for UserWorkerResponseBodyResource {
                         Some(Ok(chunk)) if !chunk.is_empty() => {
                             let len = std::cmp::min(limit, chunk.len());
                             let chunk = chunk.split_to(len);
+
+                            this.bytes_read.set(this.bytes_read.get() + len as u64);
                             break Ok(chunk.into());
                         }
                         // This unwrap is safe because `peek_mut()` returned `Some`, and thus
@@ -329,7 +745,7 @@ impl Resource for UserWorkerResponseBodyResource {
     fn close(self: Rc<Self>) {
         self.cancel.cancel();
 
-        let _ = self.req_end_tx.send(());
+        let _ = self.req_end_tx.send(self.bytes_read.get());
         let Ok(this) = Rc::try_unwrap(self) else {
             return;
         };
@@ -403,15 +819,15 @@ pub async fn op_user_worker_fetch_send(
     #[string] key: String,
     #[smi] rid: ResourceId,
     #[smi] request_body_rid: Option<ResourceId>,
-    #[smi] stream_rid: ResourceId,
+    #[smi] stream_rid: Option<ResourceId>,
     #[smi] watcher_rid: Option<ResourceId>,
+    #[serde] context: Option<Value>,
 ) -> Result<UserWorkerResponse, AnyError> {
+    let key_parsed = Uuid::try_parse(key.as_str())?;
     let (tx, req) = {
         let (tx, mut req) = {
             let mut op_state = state.borrow_mut();
-            let tx = op_state
-                .borrow::<mpsc::UnboundedSender<UserWorkerMsgs>>()
-                .clone();
+            let tx = worker_pool_tx(&mut op_state, &key_parsed)?;
 
             let req = Rc::try_unwrap(
                 op_state
@@ -424,7 +840,8 @@ pub async fn op_user_worker_fetch_send(
             (tx, req)
         };
 
-        if get_upgrade_type(req.0.headers()).is_some() {
+        if let Some(stream_rid) = stream_rid.filter(|_| get_upgrade_type(req.0.headers()).is_some())
+        {
             let req_stream = state
                 .borrow_mut()
                 .resource_table
@@ -439,11 +856,14 @@ pub async fn op_user_worker_fetch_send(
             }
         }
 
+        if let Some(context) = context {
+            req.0.extensions_mut().insert(RequestContext(context));
+        }
+
         (tx, req)
     };
 
     let (result_tx, result_rx) = oneshot::channel::<Result<SendRequestResult, Error>>();
-    let key_parsed = Uuid::try_parse(key.as_str())?;
 
     let conn_token = watcher_rid
         .and_then(|it| {
@@ -488,8 +908,8 @@ pub async fn op_user_worker_fetch_send(
     });
 
     let res = result_rx.await?;
-    let (res, req_end_tx) = match res {
-        Ok((res, req_end_tx)) => (res, req_end_tx),
+    let (res, req_end_tx, usage_rx) = match res {
+        Ok((res, req_end_tx, usage_rx)) => (res, req_end_tx, usage_rx),
         Err(err) => {
             error!("user worker failed to respond: {}", err);
 
@@ -498,6 +918,10 @@ pub async fn op_user_worker_fetch_send(
                     return Err(custom_error("WorkerRequestCancelled", err.to_string()));
                 }
 
+                Some(err @ WorkerError::RequestTimedOut) => {
+                    return Err(custom_error("WorkerRequestTimedOut", err.to_string()));
+                }
+
                 None => {
                     return Err(custom_error("InvalidWorkerResponse", err.to_string()));
                 }
@@ -534,21 +958,47 @@ pub async fn op_user_worker_fetch_send(
         reader: AsyncRefCell::new(stream.peekable()),
         cancel: CancelHandle::default(),
         size,
+        bytes_read: Cell::default(),
         req_end_tx,
         conn_token,
     });
 
+    let usage_rid = op_state
+        .resource_table
+        .add(UserWorkerRequestUsageResource(RefCell::new(Some(usage_rx))));
+
     let response = UserWorkerResponse {
         status,
         status_text,
         headers,
         body_rid,
+        usage_rid,
         size,
     };
 
     Ok(response)
 }
 
+/// Resolves to the usage of a request once its response body is done, or to
+/// `None` if it could not be accounted for.
+#[op2(async)]
+#[serde]
+pub async fn op_user_worker_fetch_usage(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+) -> Result<Option<RequestUsage>, AnyError> {
+    let resource = state
+        .borrow_mut()
+        .resource_table
+        .take::<UserWorkerRequestUsageResource>(rid)?;
+
+    let Some(usage_rx) = resource.0.borrow_mut().take() else {
+        return Ok(None);
+    };
+
+    Ok(usage_rx.await.ok())
+}
+
 /// Wraps a [`mpsc::Receiver`] in a [`Stream`] that can be used as a Hyper [`Body`].
 pub struct BodyStream(pub mpsc::Receiver<Result<bytes::Bytes, Error>>);
 
@@ -1,19 +1,40 @@
 import { primordials, core } from "ext:core/mod.js";
 import { readableStreamForRid, writableStreamForRid } from "ext:deno_web/06_streams.js";
 import { getSupabaseTag } from "ext:sb_core_main_js/js/http.js";
+import { errors } from "ext:sb_core_main_js/js/errors.js";
 
 const ops = core.ops;
 
-const { TypeError } = primordials;
+const {
+	TypeError,
+	ObjectAssign,
+	ObjectFreeze,
+	ObjectFromEntries,
+	ObjectEntries,
+	ArrayPrototypeMap,
+	SafeWeakMap,
+	PromiseResolve,
+} = primordials;
 
 const {
 	op_user_worker_fetch_send,
+	op_user_worker_fetch_usage,
 	op_user_worker_create,
+	op_user_worker_take_heap_snapshot,
+	op_user_worker_take_cpu_profile,
+	op_user_worker_list,
+	op_user_worker_stats,
+	op_user_worker_terminate,
+	op_user_worker_retire,
+	op_user_worker_binding_names,
+	op_user_worker_binding_create,
 } = ops;
 
 const NO_SUPABASE_TAG_WARN_MSG = `Unable to find the supabase tag from the request instance.\n\
 Invoke \`EdgeRuntime.applySupabaseTag(origReq, newReq)\` if you have cloned the original request.`
 
+const REQUEST_USAGES = new SafeWeakMap();
+
 function nullBodyStatus(status) {
 	return status === 101 || status === 204 || status === 205 || status === 304;
 }
@@ -23,6 +44,155 @@ function redirectStatus(status) {
 		status === 307 || status === 308;
 }
 
+async function sendRequest(key, request, options, tag) {
+	const { method, url, headers, body, bodyUsed } = request;
+	const { signal, context } = options;
+
+	signal?.throwIfAborted();
+
+	const headersArray = Array.from(headers.entries());
+	const hasBody = !bodyUsed && !!body;
+
+	const userWorkerReq = {
+		method,
+		url,
+		hasBody,
+		headers: headersArray,
+	};
+
+	const { requestRid, requestBodyRid } = await ops.op_user_worker_fetch_build(
+		userWorkerReq,
+	);
+
+	// stream the request body
+	let requestBodyPromise = null;
+
+	if (hasBody) {
+		let writableStream = writableStreamForRid(requestBodyRid);
+		requestBodyPromise = body.pipeTo(writableStream, { signal });
+	}
+
+	const responsePromise = op_user_worker_fetch_send(
+		key,
+		requestRid,
+		requestBodyRid,
+		tag?.streamRid ?? null,
+		tag?.watcherRid ?? null,
+		context ?? null,
+	);
+
+	const [requestBodyPromiseResult, responsePromiseResult] = await Promise.allSettled([
+		requestBodyPromise,
+		responsePromise
+	]);
+
+	if (requestBodyPromiseResult.status === "rejected") {
+		// console.warn(requestBodyPromiseResult.reason);
+	}
+
+	if (responsePromiseResult.status === "rejected") {
+		throw responsePromiseResult.reason;
+	}
+
+	const result = responsePromiseResult.value;
+	const usagePromise = op_user_worker_fetch_usage(result.usageRid);
+
+	// the usage is only known once the response body is done, which
+	// shouldn't keep the event loop alive on its own
+	core.unrefOpPromise(usagePromise);
+	const response = {
+		headers: result.headers,
+		status: result.status,
+		statusText: result.statusText,
+		body: null,
+	};
+
+	// TODO: add a test
+	if (nullBodyStatus(result.status) || redirectStatus(result.status)) {
+		core.tryClose(result.bodyRid);
+	} else {
+		if (request.method === "HEAD" || request.method === "CONNECT") {
+			core.tryClose(result.bodyRid);
+		} else {
+			const stream = readableStreamForRid(result.bodyRid);
+
+			signal?.addEventListener("abort", () => {
+				core.tryClose(result.bodyRid);
+			});
+			
+			response.body = stream;
+		}
+	}
+
+	const res = new Response(response.body ? response.body : null, {
+		headers: response.headers,
+		status: response.status,
+		statusText: response.statusText,
+	});
+
+	REQUEST_USAGES.set(res, usagePromise);
+
+	return res;
+}
+
+/**
+ * Fills in the defaults of the options a user worker is created with, as well
+ * as those of the services bound to it. A binding may be given as the path of
+ * the service alone, in which case its workers get the default options.
+ */
+function withDefaultOptions(opts) {
+	const readyOptions = {
+		memoryLimitMb: 512,
+		lowMemoryMultiplier: 5,
+		workerTimeoutMs: 5 * 60 * 1000,
+		cpuTimeSoftLimitMs: 50,
+		cpuTimeHardLimitMs: 100,
+		memorySoftLimitMb: 0,
+		requestWallClockLimitMs: 0,
+		maxRequestWallClockViolations: 0,
+		shutdownGracePeriodMs: 0,
+		backgroundBudgetMs: 0,
+		heapSnapshot: null,
+		cpuProfile: null,
+		logLevel: null,
+		logRateLimit: 0,
+		logByteLimit: 0,
+		bindings: {},
+		noModuleCache: false,
+		importMapPath: null,
+		envVars: [],
+		forceCreate: false,
+		netAccessDisabled: false,
+		allowRemoteModules: true,
+		customModuleRoot: '',
+		maybeEszip: null,
+		maybeEntrypoint: null,
+		maybeModuleCode: null,
+		poolPolicy: null,
+		...opts,
+	};
+
+	const { servicePath, maybeEszip } = readyOptions;
+
+	if (!maybeEszip && (!servicePath || servicePath === "")) {
+		throw new TypeError("service path must be defined");
+	}
+
+	readyOptions.bindings = ObjectFromEntries(
+		ArrayPrototypeMap(
+			ObjectEntries(readyOptions.bindings ?? {}),
+			([name, binding]) => [
+				name,
+				withDefaultOptions(
+					typeof binding === "string" ? { servicePath: binding } : binding,
+				),
+			],
+		),
+	);
+
+	return readyOptions;
+}
+
 class UserWorker {
 	constructor(key) {
 		this.key = key;
@@ -30,123 +200,102 @@ class UserWorker {
 
 	async fetch(request, options = {}) {
 		const tag = getSupabaseTag(request);
-		
-		const { method, url, headers, body, bodyUsed } = request;
-		const { signal } = options;
-
-		signal?.throwIfAborted();
 
 		if (tag === void 0) {
 			console.warn(NO_SUPABASE_TAG_WARN_MSG);
-		} 
+		}
 
-		const headersArray = Array.from(headers.entries());
-		const hasBody = !bodyUsed && !!body;
+		return await sendRequest(this.key, request, options, tag);
+	}
 
-		const userWorkerReq = {
-			method,
-			url,
-			hasBody,
-			headers: headersArray,
-		};
+	/**
+	 * Resolves to the CPU time, wall time and peak heap growth of the request
+	 * that produced `response`, once its body is done.
+	 */
+	static getUsage(response) {
+		return REQUEST_USAGES.get(response) ?? PromiseResolve(null);
+	}
 
-		const { requestRid, requestBodyRid } = await ops.op_user_worker_fetch_build(
-			userWorkerReq,
-		);
+	static async create(opts) {
+		const readyOptions = withDefaultOptions(opts);
 
-		// stream the request body
-		let requestBodyPromise = null;
+		const outcome = await op_user_worker_create(readyOptions);
 
-		if (hasBody) {
-			let writableStream = writableStreamForRid(requestBodyRid);
-			requestBodyPromise = body.pipeTo(writableStream, { signal });
-		}
+		if (outcome.bootFailed) {
+			const { message, failure } = outcome.bootFailed;
+			const { kind, specifier, referrer, line, column } = failure;
 
-		const responsePromise = op_user_worker_fetch_send(
-			this.key,
-			requestRid,
-			requestBodyRid,
-			tag.streamRid,
-			tag.watcherRid
-		);
-
-		const [requestBodyPromiseResult, responsePromiseResult] = await Promise.allSettled([
-			requestBodyPromise,
-			responsePromise
-		]);
-
-		if (requestBodyPromiseResult.status === "rejected") {
-			// console.warn(requestBodyPromiseResult.reason);
+			throw ObjectAssign(new errors.InvalidWorkerCreation(message), {
+				kind,
+				specifier,
+				referrer,
+				line,
+				column,
+			});
 		}
 
-		if (responsePromiseResult.status === "rejected") {
-			throw responsePromiseResult.reason;
-		}
+		return new UserWorker(outcome.created);
+	}
 
-		const result = responsePromiseResult.value;
-		const response = {
-			headers: result.headers,
-			status: result.status,
-			statusText: result.statusText,
-			body: null,
-		};
+	static async takeHeapSnapshot(key) {
+		return await op_user_worker_take_heap_snapshot(key);
+	}
 
-		// TODO: add a test
-		if (nullBodyStatus(result.status) || redirectStatus(result.status)) {
-			core.tryClose(result.bodyRid);
-		} else {
-			if (request.method === "HEAD" || request.method === "CONNECT") {
-				core.tryClose(result.bodyRid);
-			} else {
-				const stream = readableStreamForRid(result.bodyRid);
-
-				signal?.addEventListener("abort", () => {
-					core.tryClose(result.bodyRid);
-				});
-				
-				response.body = stream;
-			}
-		}
+	static async takeCpuProfile(key, durationMs = 1000) {
+		return await op_user_worker_take_cpu_profile(key, durationMs);
+	}
 
-		return new Response(response.body ? response.body : null, {
-			headers: response.headers,
-			status: response.status,
-			statusText: response.statusText,
-		});
+	static async list() {
+		return await op_user_worker_list();
 	}
 
-	static async create(opts) {
-		const readyOptions = {
-			memoryLimitMb: 512,
-			lowMemoryMultiplier: 5,
-			workerTimeoutMs: 5 * 60 * 1000,
-			cpuTimeSoftLimitMs: 50,
-			cpuTimeHardLimitMs: 100,
-			noModuleCache: false,
-			importMapPath: null,
-			envVars: [],
-			forceCreate: false,
-			netAccessDisabled: false,
-			allowRemoteModules: true,
-			customModuleRoot: '',
-			maybeEszip: null,
-			maybeEntrypoint: null,
-			maybeModuleCode: null,
-			...opts,
-		};
-
-		const { servicePath, maybeEszip } = readyOptions;
-
-		if (!maybeEszip && (!servicePath || servicePath === "")) {
-			throw new TypeError("service path must be defined");
-		}
+	static async getStats(key) {
+		return await op_user_worker_stats(key);
+	}
+
+	static async terminate(key) {
+		return await op_user_worker_terminate(key);
+	}
+
+	static async retire(key) {
+		return await op_user_worker_retire(key);
+	}
+}
 
-		const key = await op_user_worker_create(readyOptions);
+/**
+ * Calls a service bound to the user worker through the worker pool, without
+ * going through the network.
+ */
+class ServiceBinding {
+	#name;
 
-		return new UserWorker(key);
+	constructor(name) {
+		this.#name = name;
 	}
+
+	async fetch(input, init = {}) {
+		const request = new Request(input, init);
+		const key = await op_user_worker_binding_create(this.#name);
+
+		return await sendRequest(key, request, { signal: init.signal }, void 0);
+	}
+}
+
+let serviceBindings = null;
+
+function getServiceBindings() {
+	if (serviceBindings === null) {
+		serviceBindings = ObjectFreeze(ObjectFromEntries(
+			ArrayPrototypeMap(
+				op_user_worker_binding_names(),
+				(name) => [name, new ServiceBinding(name)],
+			),
+		));
+	}
+
+	return serviceBindings;
 }
 
 const SUPABASE_USER_WORKERS = UserWorker;
 
-export { SUPABASE_USER_WORKERS };
+export { SUPABASE_USER_WORKERS, getServiceBindings };